@@ -0,0 +1,615 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Configuration for the custom broadcaster
+//!
+//! The custom broadcaster streams transaction outputs produced by this node to websocket
+//! clients. It is disabled unless a `broadcaster-config` section is present in the node
+//! config:
+//!
+//! ```yaml
+//! broadcaster-config:
+//!   bind-address: 127.0.0.1
+//!   port: 9002
+//!   channel-capacity: 1000
+//!   broadcast-capacity: 1000
+//!   overflow-strategy: drop-oldest
+//!   send-queue-capacity: 1024
+//!   max-connections: 256
+//!   heartbeat-interval-ms: 30000
+//!   ping-interval-ms: 20000
+//!   pong-timeout-ms: 60000
+//!   retention-capacity: 1000
+//!   retention-max-bytes: 67108864
+//!   max-message-bytes: 65536
+//!   queryable-tables:
+//!     - "0x5"
+//!   max-range: 100000
+//!   require-store: true
+//!   auth-token: client-secret
+//!   tcp-nodelay: true
+//!   tcp-keepalive:
+//!     idle-ms: 60000
+//!     interval-ms: 10000
+//!     retries: 3
+//!   parquet-sink:
+//!     directory: /opt/sui/broadcast-capture
+//!     requests:
+//!       - '{"SubscribePool": "0x5"}'
+//!     row-group-rows: 10000
+//!     flush-interval-ms: 10000
+//!     rotate-interval-ms: 3600000
+//! ```
+
+use anyhow::{Result, ensure};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use sui_types::base_types::ObjectID;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BroadcasterConfig {
+    /// Interface the websocket server binds to. Defaults to all interfaces.
+    #[serde(default = "default_bind_address")]
+    pub bind_address: IpAddr,
+
+    /// Port the websocket server listens on. Must not be 0.
+    #[serde(default = "default_port")]
+    pub port: u16,
+
+    /// Capacity of the channel between the execution cache and the broadcaster.
+    ///
+    /// Updates are dropped (never blocked on) once the channel backs up, so this bounds how
+    /// far the broadcaster can fall behind execution.
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+
+    /// Number of transactions buffered for websocket connections that fall behind.
+    #[serde(default = "default_broadcast_capacity")]
+    pub broadcast_capacity: usize,
+
+    /// What happens when a connection falls more than `broadcast-capacity` transactions behind.
+    #[serde(default)]
+    pub overflow_strategy: OverflowStrategy,
+
+    /// Messages queued for each connection's socket, which a task of its own writes out.
+    ///
+    /// Under `drop-oldest`, a connection whose queue is full drops the oldest queued stream
+    /// update for each new one rather than waiting on its socket, so a slow client loses stale
+    /// updates instead of falling behind the stream; it is told how many in `Dropped` messages.
+    /// Under the other strategies, and for `ordered` connections, the connection waits for room.
+    #[serde(default = "default_send_queue_capacity")]
+    pub send_queue_capacity: usize,
+
+    /// Maximum number of concurrent websocket connections. Unlimited if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+
+    /// Interval after which an idle connection receives an application-level `Heartbeat`
+    /// message. Set to 0 to disable heartbeats.
+    #[serde(default = "default_heartbeat_interval_ms")]
+    pub heartbeat_interval_ms: u64,
+
+    /// Interval at which every connection is sent a websocket `Ping` frame. Set to 0 to
+    /// disable pings.
+    #[serde(default = "default_ping_interval_ms")]
+    pub ping_interval_ms: u64,
+
+    /// Connections from which nothing, not even a `Pong`, was received for this long are
+    /// closed with code 4009. Only applies while pings are enabled.
+    #[serde(default = "default_pong_timeout_ms")]
+    pub pong_timeout_ms: u64,
+
+    /// Number of recently ingested transactions kept for replay to reconnecting and
+    /// late-joining clients. Set to 0 to disable retention.
+    #[serde(default = "default_retention_capacity")]
+    pub retention_capacity: usize,
+
+    /// Approximate upper bound, in bytes, on the retained transactions. Only the entry count
+    /// applies if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_max_bytes: Option<usize>,
+
+    /// Largest websocket frame or message, in bytes, accepted from a client. Connections
+    /// sending a larger one are closed with code 1009.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+
+    /// Set `TCP_NODELAY` on accepted connections, so that small messages go out immediately
+    /// rather than being held back to be coalesced with later ones (Nagle's algorithm).
+    #[serde(default = "default_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+
+    /// TCP keepalive probing of accepted connections, which detects dead peers that never
+    /// closed their connection. The OS defaults apply if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive: Option<TcpKeepaliveConfig>,
+
+    /// Tables whose fields clients may read (`QueryFieldRange`, table snapshots and
+    /// `GET /query/field`). Every table may be queried if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queryable_tables: Option<BTreeSet<ObjectID>>,
+
+    /// Largest `range` of a field query window (`current_index ± range`). Every index of a
+    /// window is looked up in the store, so wider windows are refused with an error.
+    #[serde(default = "default_max_range")]
+    pub max_range: u64,
+
+    /// Report the broadcaster as not ready at `GET /ready` when it has no store to serve field
+    /// queries from, for deployments whose clients depend on them.
+    #[serde(default)]
+    pub require_store: bool,
+
+    /// Token clients must present to open a websocket connection, as a `token` query
+    /// parameter or an `Authorization: Bearer` header; the upgrade is refused with 401
+    /// otherwise. Connections are open to anyone who can reach the port if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+
+    /// Bearer token required by the admin endpoints (`GET /connections` and
+    /// `POST /connections/{id}/disconnect`). The admin endpoints are disabled if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+
+    /// Capture of the broadcast stream to Parquet files. Only honoured by nodes built with the
+    /// `parquet-sink` feature of sui-core.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parquet_sink: Option<ParquetSinkConfig>,
+}
+
+/// Keepalive probes sent on a connection: the first after `idle-ms` without traffic, then every
+/// `interval-ms` until one is answered or `retries` went unanswered and the connection is dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TcpKeepaliveConfig {
+    #[serde(default = "default_keepalive_idle_ms")]
+    pub idle_ms: u64,
+
+    #[serde(default = "default_keepalive_interval_ms")]
+    pub interval_ms: u64,
+
+    /// Ignored on Windows, where the count is fixed.
+    #[serde(default = "default_keepalive_retries")]
+    pub retries: u32,
+}
+
+impl TcpKeepaliveConfig {
+    pub fn idle(&self) -> Duration {
+        Duration::from_millis(self.idle_ms)
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            self.idle_ms >= 1000 && self.interval_ms >= 1000,
+            "broadcaster tcp-keepalive idle-ms and interval-ms must be at least 1000"
+        );
+        ensure!(
+            self.retries > 0,
+            "broadcaster tcp-keepalive retries must be positive"
+        );
+        Ok(())
+    }
+}
+
+impl Default for TcpKeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            idle_ms: default_keepalive_idle_ms(),
+            interval_ms: default_keepalive_interval_ms(),
+            retries: default_keepalive_retries(),
+        }
+    }
+}
+
+/// Writes the messages selected by `requests` to Parquet files under `directory`, one row per
+/// message, partitioned by the hour the file was started in (`date=YYYY-MM-DD/hour=HH/`).
+///
+/// The sink receives the stream like a connection, so with `overflow-strategy: block` a slow
+/// disk holds back every connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParquetSinkConfig {
+    pub directory: PathBuf,
+
+    /// Subscription requests selecting the captured messages, in the JSON clients send over
+    /// the websocket (e.g. `{"SubscribePool": "0x5"}`).
+    pub requests: Vec<String>,
+
+    /// Rows buffered before they are written out as a row group.
+    #[serde(default = "default_row_group_rows")]
+    pub row_group_rows: usize,
+
+    /// Longest time buffered rows wait before they are written out, even if fewer than
+    /// `row-group-rows`.
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+
+    /// Age at which a file is closed and the next one started.
+    #[serde(default = "default_rotate_interval_ms")]
+    pub rotate_interval_ms: u64,
+}
+
+impl ParquetSinkConfig {
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_millis(self.flush_interval_ms)
+    }
+
+    pub fn rotate_interval(&self) -> Duration {
+        Duration::from_millis(self.rotate_interval_ms)
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.requests.is_empty(),
+            "broadcaster parquet-sink requests must not be empty"
+        );
+        ensure!(
+            self.row_group_rows > 0,
+            "broadcaster parquet-sink row-group-rows must be positive"
+        );
+        ensure!(
+            self.flush_interval_ms > 0,
+            "broadcaster parquet-sink flush-interval-ms must be positive"
+        );
+        ensure!(
+            self.rotate_interval_ms > 0,
+            "broadcaster parquet-sink rotate-interval-ms must be positive"
+        );
+        Ok(())
+    }
+}
+
+/// Behaviour of the broadcaster when a websocket connection cannot keep up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverflowStrategy {
+    /// The lagging connection skips the oldest transactions it has not yet received.
+    #[default]
+    DropOldest,
+    /// The lagging connection is closed.
+    DisconnectSlowest,
+    /// The broadcaster stops fanning out new transactions until the slowest connection catches
+    /// up. Backpressure stops at the ingestion channel: the execution cache never blocks on the
+    /// broadcaster, so once that channel fills up new transactions are dropped for every
+    /// connection instead.
+    Block,
+}
+
+impl BroadcasterConfig {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// `None` when heartbeats are disabled.
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        (self.heartbeat_interval_ms > 0).then(|| Duration::from_millis(self.heartbeat_interval_ms))
+    }
+
+    /// `None` when pings are disabled.
+    pub fn ping_interval(&self) -> Option<Duration> {
+        (self.ping_interval_ms > 0).then(|| Duration::from_millis(self.ping_interval_ms))
+    }
+
+    pub fn pong_timeout(&self) -> Duration {
+        Duration::from_millis(self.pong_timeout_ms)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.port != 0, "broadcaster port must not be 0");
+        ensure!(
+            self.channel_capacity > 0,
+            "broadcaster channel-capacity must be positive"
+        );
+        ensure!(
+            self.broadcast_capacity > 0,
+            "broadcaster broadcast-capacity must be positive"
+        );
+        ensure!(
+            self.send_queue_capacity > 0,
+            "broadcaster send-queue-capacity must be positive"
+        );
+        ensure!(
+            self.max_connections != Some(0),
+            "broadcaster max-connections must be positive when set"
+        );
+        ensure!(
+            self.ping_interval_ms == 0 || self.pong_timeout_ms > 0,
+            "broadcaster pong-timeout-ms must be positive when pings are enabled"
+        );
+        ensure!(
+            self.retention_max_bytes != Some(0),
+            "broadcaster retention-max-bytes must be positive when set"
+        );
+        ensure!(
+            self.max_message_bytes > 0,
+            "broadcaster max-message-bytes must be positive"
+        );
+        ensure!(self.max_range > 0, "broadcaster max-range must be positive");
+        ensure!(
+            self.auth_token.as_deref() != Some(""),
+            "broadcaster auth-token must not be empty when set"
+        );
+        ensure!(
+            self.admin_token.as_deref() != Some(""),
+            "broadcaster admin-token must not be empty when set"
+        );
+        if let Some(keepalive) = &self.tcp_keepalive {
+            keepalive.validate()?;
+        }
+        if let Some(sink) = &self.parquet_sink {
+            sink.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for BroadcasterConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: default_bind_address(),
+            port: default_port(),
+            channel_capacity: default_channel_capacity(),
+            broadcast_capacity: default_broadcast_capacity(),
+            overflow_strategy: OverflowStrategy::default(),
+            send_queue_capacity: default_send_queue_capacity(),
+            max_connections: None,
+            heartbeat_interval_ms: default_heartbeat_interval_ms(),
+            ping_interval_ms: default_ping_interval_ms(),
+            pong_timeout_ms: default_pong_timeout_ms(),
+            retention_capacity: default_retention_capacity(),
+            retention_max_bytes: None,
+            max_message_bytes: default_max_message_bytes(),
+            tcp_nodelay: default_tcp_nodelay(),
+            tcp_keepalive: None,
+            queryable_tables: None,
+            max_range: default_max_range(),
+            require_store: false,
+            auth_token: None,
+            admin_token: None,
+            parquet_sink: None,
+        }
+    }
+}
+
+fn default_bind_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+}
+
+fn default_port() -> u16 {
+    9002
+}
+
+fn default_channel_capacity() -> usize {
+    1000
+}
+
+fn default_broadcast_capacity() -> usize {
+    1000
+}
+
+fn default_send_queue_capacity() -> usize {
+    1024
+}
+
+fn default_heartbeat_interval_ms() -> u64 {
+    30_000
+}
+
+fn default_ping_interval_ms() -> u64 {
+    20_000
+}
+
+fn default_pong_timeout_ms() -> u64 {
+    60_000
+}
+
+fn default_retention_capacity() -> usize {
+    1000
+}
+
+fn default_max_message_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_range() -> u64 {
+    100_000
+}
+
+fn default_tcp_nodelay() -> bool {
+    true
+}
+
+fn default_keepalive_idle_ms() -> u64 {
+    60_000
+}
+
+fn default_keepalive_interval_ms() -> u64 {
+    10_000
+}
+
+fn default_keepalive_retries() -> u32 {
+    3
+}
+
+fn default_row_group_rows() -> usize {
+    10_000
+}
+
+fn default_flush_interval_ms() -> u64 {
+    10_000
+}
+
+fn default_rotate_interval_ms() -> u64 {
+    3_600_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_defaults() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert_eq!(config.port, 9100);
+        assert_eq!(config.channel_capacity, 1000);
+        assert_eq!(config.overflow_strategy, OverflowStrategy::DropOldest);
+        assert_eq!(config.socket_addr().to_string(), "0.0.0.0:9100");
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_bind_address() {
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("bind-address: 127.0.0.1\nport: 9100").unwrap();
+        assert_eq!(config.socket_addr().to_string(), "127.0.0.1:9100");
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("bind-address: '::1'\nport: 9100").unwrap();
+        assert_eq!(config.socket_addr().to_string(), "[::1]:9100");
+        assert!(serde_yaml::from_str::<BroadcasterConfig>("bind-address: localhost").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_overflow_strategy() {
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("overflow-strategy: disconnect-slowest").unwrap();
+        assert_eq!(
+            config.overflow_strategy,
+            OverflowStrategy::DisconnectSlowest
+        );
+    }
+
+    #[test]
+    fn test_send_queue_capacity() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert_eq!(config.send_queue_capacity, 1024);
+        let config: BroadcasterConfig = serde_yaml::from_str("send-queue-capacity: 16").unwrap();
+        assert_eq!(config.send_queue_capacity, 16);
+        let config: BroadcasterConfig = serde_yaml::from_str("send-queue-capacity: 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_empty_auth_token() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert!(config.auth_token.is_none());
+        let config: BroadcasterConfig = serde_yaml::from_str("auth-token: ''").unwrap();
+        assert!(config.validate().is_err());
+        let config: BroadcasterConfig = serde_yaml::from_str("auth-token: secret").unwrap();
+        assert_eq!(config.auth_token.as_deref(), Some("secret"));
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_empty_admin_token() {
+        let config: BroadcasterConfig = serde_yaml::from_str("admin-token: ''").unwrap();
+        assert!(config.validate().is_err());
+        let config: BroadcasterConfig = serde_yaml::from_str("admin-token: secret").unwrap();
+        assert_eq!(config.admin_token.as_deref(), Some("secret"));
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_max_message_bytes() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert_eq!(config.max_message_bytes, 65536);
+        let config: BroadcasterConfig = serde_yaml::from_str("max-message-bytes: 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_queryable_tables() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert!(config.queryable_tables.is_none());
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("queryable-tables: ['0x5', '0x6']").unwrap();
+        let tables = config.queryable_tables.unwrap();
+        assert!(tables.contains(&ObjectID::from_single_byte(5)));
+        assert_eq!(tables.len(), 2);
+    }
+
+    #[test]
+    fn test_max_range() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert_eq!(config.max_range, 100_000);
+        let config: BroadcasterConfig = serde_yaml::from_str("max-range: 1000").unwrap();
+        assert_eq!(config.max_range, 1000);
+        let config: BroadcasterConfig = serde_yaml::from_str("max-range: 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_require_store() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert!(!config.require_store);
+        let config: BroadcasterConfig = serde_yaml::from_str("require-store: true").unwrap();
+        assert!(config.require_store);
+    }
+
+    #[test]
+    fn test_deserialize_ping_options() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert_eq!(config.ping_interval(), Some(Duration::from_secs(20)));
+        assert_eq!(config.pong_timeout(), Duration::from_secs(60));
+
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("ping-interval-ms: 0\npong-timeout-ms: 0").unwrap();
+        assert_eq!(config.ping_interval(), None);
+        config.validate().unwrap();
+
+        let config: BroadcasterConfig = serde_yaml::from_str("pong-timeout-ms: 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_tcp_options() {
+        let config: BroadcasterConfig = serde_yaml::from_str("port: 9100").unwrap();
+        assert!(config.tcp_nodelay);
+        assert!(config.tcp_keepalive.is_none());
+
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("tcp-nodelay: false\ntcp-keepalive:\n  idle-ms: 30000").unwrap();
+        assert!(!config.tcp_nodelay);
+        let keepalive = config.tcp_keepalive.as_ref().unwrap();
+        assert_eq!(keepalive.idle(), Duration::from_secs(30));
+        assert_eq!(keepalive.interval(), Duration::from_secs(10));
+        assert_eq!(keepalive.retries, 3);
+        config.validate().unwrap();
+
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("tcp-keepalive:\n  retries: 0").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_parquet_sink() {
+        let config: BroadcasterConfig = serde_yaml::from_str(
+            "parquet-sink:\n  directory: /tmp/capture\n  requests: ['\"SubscribeAll\"']",
+        )
+        .unwrap();
+        let sink = config.parquet_sink.as_ref().unwrap();
+        assert_eq!(sink.row_group_rows, 10_000);
+        assert_eq!(sink.rotate_interval(), Duration::from_secs(3600));
+        config.validate().unwrap();
+
+        let config: BroadcasterConfig =
+            serde_yaml::from_str("parquet-sink:\n  directory: /tmp/capture\n  requests: []")
+                .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_port() {
+        let config = BroadcasterConfig {
+            port: 0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
@@ -10,6 +10,7 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 use tracing::trace;
 
+pub mod broadcaster_config;
 pub mod certificate_deny_config;
 pub mod dynamic_transaction_signing_checks;
 pub mod genesis;
@@ -1,6 +1,7 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 use crate::Config;
+use crate::broadcaster_config::BroadcasterConfig;
 use crate::certificate_deny_config::CertificateDenyConfig;
 use crate::genesis;
 use crate::object_storage_config::ObjectStoreConfig;
@@ -222,6 +223,10 @@ pub struct NodeConfig {
     /// Configuration for the transaction driver.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transaction_driver_config: Option<TransactionDriverConfig>,
+
+    /// Configuration for the custom broadcaster. The broadcaster is disabled when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcaster_config: Option<BroadcasterConfig>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
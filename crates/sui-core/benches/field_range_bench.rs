@@ -0,0 +1,188 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Range queries of a table with a field at 1% of its indices: a lookup for every index of the
+//! range (`query_field_data_range`) against a read of every child of the table
+//! (`query_field_data_range_in_children`, as done by `query_field_data_range_indexed` with the
+//! children listed by the RPC index). The range stays the same while the table grows beyond
+//! it, so the point where the children scan stops paying off shows as the crossing of the two.
+//! Both make one versioned lookup per item, so the crossing is expected where the table has as
+//! many children as the range has indices, 20,001 here; the sizes are on either side of it.
+//!
+//! Then a dense range of 20,001 indices, all with a field, read one index at a time against
+//! the batched read of `query_field_data_range_batched`, which seeks a single iterator to each
+//! field instead of creating one per index. The fields are read once bounded by the version
+//! they were written at and once as of "latest".
+//!
+//! Last, the derivation of a range's field IDs alone, from scratch against lookups in a
+//! `FieldIdCache` that already holds them, as it does for the overlap of successive ranges
+//! around a pool's current tick.
+
+use criterion::*;
+use std::num::NonZeroUsize;
+
+use sui_core::authority::authority_store_tables::AuthorityPerpetualTables;
+use sui_core::field_data_query::{
+    FieldIdCache, KeyCodec, U64Codec, query_field_data_range, query_field_data_range_batched,
+    query_field_data_range_in_children,
+};
+use sui_types::TypeTag;
+use sui_types::base_types::{ObjectID, SequenceNumber};
+use sui_types::digests::TransactionDigest;
+use sui_types::dynamic_field::{DynamicFieldInfo, Field};
+use sui_types::id::UID;
+use sui_types::object::{MoveObject, OBJECT_START_VERSION, Object, Owner};
+
+const CENTER: u64 = 1_000_000;
+const RANGE: u64 = 10_000;
+/// One index in this many has a field
+const SPACING: u64 = 100;
+/// Children of the table at each step, the first being just the fields in the range and the
+/// middle one as many as the range has indices
+const TABLE_SIZES: [u64; 5] = [
+    2 * RANGE / SPACING + 1,
+    2_000,
+    10_000,
+    2 * RANGE + 1,
+    100_000,
+];
+/// Range of the dense table, which has a field at every one of its indices
+const DENSE_RANGE: u64 = 10_000;
+
+/// A `Field<u64, u64>` of `table_id` at `index`
+fn field_object(table_id: ObjectID, index: u64) -> Object {
+    let field = Field {
+        id: UID::new(U64Codec.field_id(table_id, index).unwrap()),
+        name: index,
+        value: index,
+    };
+    let type_ = DynamicFieldInfo::dynamic_field_type(TypeTag::U64, TypeTag::U64);
+    // SAFETY: the contents are a valid BCS value of `type_`
+    let move_obj = unsafe {
+        MoveObject::new_from_execution_with_limit(
+            type_.into(),
+            false,
+            OBJECT_START_VERSION,
+            bcs::to_bytes(&field).unwrap(),
+            u64::MAX,
+        )
+    }
+    .unwrap();
+    Object::new_move(
+        move_obj,
+        Owner::ObjectOwner(table_id.into()),
+        TransactionDigest::ZERO,
+    )
+}
+
+fn field_range_bench(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let table_id = ObjectID::random();
+    let mut children = vec![];
+    // Fields from the start of the range on, so the first ones fill it
+    let mut indices = (CENTER - RANGE..).step_by(SPACING as usize);
+
+    let mut group = c.benchmark_group("field-range");
+    for size in TABLE_SIZES {
+        while (children.len() as u64) < size {
+            let field = field_object(table_id, indices.next().unwrap());
+            children.push(field.id());
+            store.insert_object_test_only(field).unwrap();
+        }
+
+        group.bench_with_input(BenchmarkId::new("index lookups", size), &size, |b, _| {
+            b.iter(|| {
+                query_field_data_range(
+                    &store,
+                    table_id,
+                    CENTER,
+                    RANGE,
+                    SequenceNumber::MAX,
+                    &U64Codec,
+                )
+                .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("children scan", size), &size, |b, _| {
+            b.iter(|| {
+                query_field_data_range_in_children(
+                    &store,
+                    table_id,
+                    children.iter().copied().map(Ok),
+                    CENTER,
+                    RANGE,
+                    SequenceNumber::MAX,
+                    &U64Codec,
+                )
+                .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn dense_range_bench(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let table_id = ObjectID::random();
+    for index in CENTER - DENSE_RANGE..=CENTER + DENSE_RANGE {
+        store
+            .insert_object_test_only(field_object(table_id, index))
+            .unwrap();
+    }
+
+    let mut group = c.benchmark_group("dense-range");
+    for (bound, version) in [
+        ("written version", OBJECT_START_VERSION),
+        ("latest", SequenceNumber::MAX),
+    ] {
+        group.bench_with_input(BenchmarkId::new("per index", bound), &version, |b, v| {
+            b.iter(|| {
+                query_field_data_range(&store, table_id, CENTER, DENSE_RANGE, *v, &U64Codec)
+                    .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batched", bound), &version, |b, v| {
+            b.iter(|| {
+                query_field_data_range_batched(&store, table_id, CENTER, DENSE_RANGE, *v, &U64Codec)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn field_id_bench(c: &mut Criterion) {
+    let table_id = ObjectID::random();
+    let cache = FieldIdCache::new(NonZeroUsize::new(4 * RANGE as usize).unwrap());
+    let indices = CENTER - RANGE..=CENTER + RANGE;
+    for index in indices.clone() {
+        cache.field_id(table_id, index, &U64Codec).unwrap();
+    }
+
+    let mut group = c.benchmark_group("field-id-derivation");
+    group.bench_function("derived", |b| {
+        b.iter(|| {
+            for index in indices.clone() {
+                U64Codec.field_id(table_id, index).unwrap();
+            }
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            for index in indices.clone() {
+                cache.field_id(table_id, index, &U64Codec).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    field_range_bench,
+    dense_range_bench,
+    field_id_bench
+);
+criterion_main!(benches);
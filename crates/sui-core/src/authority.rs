@@ -803,6 +803,8 @@ pub struct ExecutionEnv {
     /// Transactions that must finish before this transaction can be executed.
     /// Used to schedule barrier transactions after non-exclusive writes.
     pub barrier_dependencies: Vec<TransactionDigest>,
+    /// The certified checkpoint containing the transaction, if executing from checkpoint.
+    pub checkpoint: Option<CheckpointSequenceNumber>,
 }
 
 impl Default for ExecutionEnv {
@@ -813,6 +815,7 @@ impl Default for ExecutionEnv {
             scheduling_source: SchedulingSource::NonFastPath,
             funds_withdraw_status: FundsWithdrawStatus::MaybeSufficient,
             barrier_dependencies: Default::default(),
+            checkpoint: None,
         }
     }
 }
@@ -852,6 +855,11 @@ impl ExecutionEnv {
         self.barrier_dependencies = barrier_dependencies.into_iter().collect();
         self
     }
+
+    pub fn with_checkpoint(mut self, checkpoint: CheckpointSequenceNumber) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -1609,6 +1617,7 @@ impl AuthorityState {
         }
 
         let scheduling_source = execution_env.scheduling_source;
+        let checkpoint = execution_env.checkpoint;
         let mysticeti_fp_outputs = if epoch_store.protocol_config().mysticeti_fastpath() {
             tx_cache_reader.get_mysticeti_fastpath_outputs(tx_digest)
         } else {
@@ -1665,7 +1674,7 @@ impl AuthorityState {
                 .write_fastpath_transaction_outputs(transaction_outputs);
         } else {
             let commit_result =
-                self.commit_certificate(certificate, transaction_outputs, epoch_store);
+                self.commit_certificate(certificate, transaction_outputs, checkpoint, epoch_store);
             if let Err(err) = commit_result {
                 error!(?tx_digest, "Error committing transaction: {err}");
                 tx_guard.release();
@@ -1911,6 +1920,7 @@ impl AuthorityState {
         &self,
         certificate: &VerifiedExecutableTransaction,
         transaction_outputs: Arc<TransactionOutputs>,
+        checkpoint: Option<CheckpointSequenceNumber>,
         epoch_store: &Arc<AuthorityPerEpochStore>,
     ) -> SuiResult {
         let _scope: Option<mysten_metrics::MonitoredScopeGuard> =
@@ -1930,8 +1940,17 @@ impl AuthorityState {
         // Allow testing what happens if we crash here.
         fail_point!("crash");
 
-        self.get_cache_writer()
-            .write_transaction_outputs(epoch_store.epoch(), transaction_outputs);
+        let cache_writer = self.get_cache_writer();
+        match checkpoint {
+            Some(checkpoint) => cache_writer.write_transaction_outputs_in_checkpoint(
+                epoch_store.epoch(),
+                transaction_outputs,
+                checkpoint,
+            ),
+            None => {
+                cache_writer.write_transaction_outputs(epoch_store.epoch(), transaction_outputs)
+            }
+        }
 
         if certificate.transaction_data().is_end_of_epoch_tx() {
             // At the end of epoch, since system packages may have been upgraded, force
@@ -139,7 +139,7 @@ pub enum PruningMode {
 
 impl AuthorityStorePruner {
     /// prunes old versions of objects based on transaction effects
-    async fn prune_objects(
+    pub(crate) async fn prune_objects(
         transaction_effects: Vec<TransactionEffects>,
         perpetual_db: &Arc<AuthorityPerpetualTables>,
         pruner_db: Option<&Arc<AuthorityPrunerTables>>,
@@ -154,7 +154,11 @@ impl AuthorityStorePruner {
         // Collect objects keys that need to be deleted from `transaction_effects`.
         let mut live_object_keys_to_prune = vec![];
         let mut object_tombstones_to_prune = vec![];
+        let mut pruned_version = perpetual_db
+            .get_highest_pruned_version()?
+            .unwrap_or_default();
         for effects in &transaction_effects {
+            pruned_version = max(pruned_version, effects.lamport_version());
             for (object_id, seq_number) in effects.modified_at_versions() {
                 live_object_keys_to_prune.push(ObjectKey(object_id, seq_number));
             }
@@ -224,6 +228,7 @@ impl AuthorityStorePruner {
         }
 
         perpetual_db.set_highest_pruned_checkpoint(&mut wb, checkpoint_number)?;
+        perpetual_db.set_highest_pruned_version(&mut wb, pruned_version)?;
         metrics.last_pruned_checkpoint.set(checkpoint_number as i64);
 
         if let Some(batch) = pruner_db_wb {
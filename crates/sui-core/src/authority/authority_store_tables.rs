@@ -14,7 +14,7 @@ use sui_types::storage::{FullObjectKey, MarkerValue};
 use tracing::error;
 use typed_store::metrics::SamplingInterval;
 use typed_store::rocks::{
-    DBBatch, DBMap, DBMapTableConfigMap, DBOptions, MetricConf, default_db_options,
+    DBBatch, DBMap, DBMapTableConfigMap, DBOptions, DBSnapshot, MetricConf, default_db_options,
     read_size_from_env,
 };
 use typed_store::traits::Map;
@@ -123,6 +123,11 @@ pub struct AuthorityPerpetualTables {
     /// A singleton table that stores latest pruned checkpoint. Used to keep objects pruner progress
     pub(crate) pruned_checkpoint: DBMap<(), CheckpointSequenceNumber>,
 
+    /// A singleton table that stores the highest lamport version among the transactions whose
+    /// superseded object versions the objects pruner has removed. Reads bounded below it may
+    /// miss versions that were pruned.
+    pub(crate) pruned_version: DBMap<(), SequenceNumber>,
+
     /// Expected total amount of SUI in the network. This is expected to remain constant
     /// throughout the lifetime of the network. We check it at the end of each epoch if
     /// expensive checks are enabled. We cannot use 10B today because in tests we often
@@ -376,6 +381,10 @@ impl AuthorityPerpetualTables {
                 "pruned_checkpoint".to_string(),
                 ThConfig::new(0, 1, KeyType::uniform(1)),
             ),
+            (
+                "pruned_version".to_string(),
+                ThConfig::new(0, 1, KeyType::uniform(1)),
+            ),
             (
                 "expected_network_sui_amount".to_string(),
                 ThConfig::new(0, 1, KeyType::uniform(1)),
@@ -464,6 +473,55 @@ impl AuthorityPerpetualTables {
         }
     }
 
+    /// A snapshot of the objects table, for reads through
+    /// [`Self::find_object_lt_or_eq_version_in`] that must all see the same point in time
+    pub fn objects_snapshot(&self) -> DBSnapshot<'_> {
+        self.objects.snapshot()
+    }
+
+    /// [`Self::find_object_lt_or_eq_version`] as of `snapshot`, ignoring writes committed after
+    /// it was taken
+    pub fn find_object_lt_or_eq_version_in(
+        &self,
+        object_id: ObjectID,
+        version: SequenceNumber,
+        snapshot: &DBSnapshot<'_>,
+    ) -> SuiResult<Option<Object>> {
+        let found = self
+            .objects
+            .multi_get_lt_or_eq(&[ObjectKey(object_id, version)], Some(snapshot))?;
+        match found.into_iter().next().flatten() {
+            // The entry before the ID's first version belongs to another object
+            Some((key, o)) if key.0 == object_id => self.object(&key, o),
+            _ => Ok(None),
+        }
+    }
+
+    /// [`Self::find_object_lt_or_eq_version`] of each of `object_ids`, in the same order
+    ///
+    /// Each ID's latest key at or below `(id, version)` is found with a single iterator sought
+    /// to the IDs in ascending order, instead of an iterator of its own.
+    pub fn multi_find_object_lt_or_eq_version(
+        &self,
+        object_ids: &[ObjectID],
+        version: SequenceNumber,
+    ) -> SuiResult<Vec<Option<Object>>> {
+        let keys: Vec<ObjectKey> = object_ids
+            .iter()
+            .map(|object_id| ObjectKey(*object_id, version))
+            .collect();
+        let found = self.objects.multi_get_lt_or_eq(&keys, None)?;
+        object_ids
+            .iter()
+            .zip(found)
+            .map(|(object_id, found)| match found {
+                // The entry before the ID's first version belongs to another object
+                Some((key, o)) if key.0 == *object_id => self.object(&key, o),
+                _ => Ok(None),
+            })
+            .collect()
+    }
+
     fn construct_object(
         &self,
         object_key: &ObjectKey,
@@ -600,6 +658,19 @@ impl AuthorityPerpetualTables {
         Ok(())
     }
 
+    pub fn get_highest_pruned_version(&self) -> Result<Option<SequenceNumber>, TypedStoreError> {
+        self.pruned_version.get(&())
+    }
+
+    pub fn set_highest_pruned_version(
+        &self,
+        wb: &mut DBBatch,
+        version: SequenceNumber,
+    ) -> SuiResult {
+        wb.insert_batch(&self.pruned_version, [((), version)])?;
+        Ok(())
+    }
+
     pub fn get_transaction(
         &self,
         digest: &TransactionDigest,
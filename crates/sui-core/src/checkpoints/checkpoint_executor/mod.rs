@@ -602,7 +602,8 @@ impl CheckpointExecutor {
         finish_stage!(pipeline_handle, WaitForTransactions);
 
         if ckpt_state.data.checkpoint.is_last_checkpoint_of_epoch() {
-            self.execute_change_epoch_tx(&tx_data).await;
+            self.execute_change_epoch_tx(sequence_number, &tx_data)
+                .await;
         }
 
         let _scope = mysten_metrics::monitored_scope("CheckpointExecutor::finalize_checkpoint");
@@ -875,7 +876,8 @@ impl CheckpointExecutor {
                         let mut env = ExecutionEnv::new()
                             .with_assigned_versions(assigned_versions)
                             .with_expected_effects_digest(*expected_fx_digest)
-                            .with_barrier_dependencies(barrier_deps);
+                            .with_barrier_dependencies(barrier_deps)
+                            .with_checkpoint(ckpt_state.data.checkpoint.sequence_number);
 
                         // Check if the expected effects indicate insufficient balance
                         if let &ExecutionStatus::Failure {
@@ -901,7 +903,11 @@ impl CheckpointExecutor {
 
     // Execute the change epoch txn
     #[instrument(level = "error", skip_all)]
-    async fn execute_change_epoch_tx(&self, tx_data: &CheckpointTransactionData) {
+    async fn execute_change_epoch_tx(
+        &self,
+        checkpoint: CheckpointSequenceNumber,
+        tx_data: &CheckpointTransactionData,
+    ) {
         let change_epoch_tx = tx_data.transactions.last().unwrap();
         let change_epoch_fx = tx_data.effects.last().unwrap();
         assert_eq!(
@@ -951,7 +957,8 @@ impl CheckpointExecutor {
                 change_epoch_tx.clone(),
                 ExecutionEnv::new()
                     .with_assigned_versions(assigned_versions)
-                    .with_expected_effects_digest(change_epoch_fx.digest()),
+                    .with_expected_effects_digest(change_epoch_fx.digest())
+                    .with_checkpoint(checkpoint),
             )],
             &self.epoch_store,
         );
@@ -1,50 +1,415 @@
+use crate::authority::authority_store_tables::AuthorityPerpetualTables;
+use crate::checkpoints::CheckpointStore;
+use crate::field_data_query::{
+    FieldData, IndexWindow, KeyCodec, KeyCodecRegistry, OrderedRangeResult, codec_for_type,
+    field_index, get_field_value, get_parent_object, query_field_data_range_batched,
+    query_field_data_stream_sparse, query_field_data_stream_windows,
+};
 use crate::transaction_outputs::TransactionOutputs;
 use axum::{
-    Router,
+    Json, Router,
+    body::Body,
     extract::{
-        State,
+        Path, Query, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    response::IntoResponse,
-    routing::get,
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    serve::ListenerExt,
 };
+use fastcrypto::encoding::{Base64, Encoding, Hex};
+use flate2::{Compression, write::GzEncoder};
+use futures::{
+    Stream, StreamExt,
+    stream::{BoxStream, Peekable, SplitStream},
+};
+use move_core_types::language_storage::StructTag;
+use parking_lot::Mutex;
+use prometheus::Registry;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, net::SocketAddr, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Write,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use subtle::ConstantTimeEq;
+use sui_config::broadcaster_config::{BroadcasterConfig, OverflowStrategy, TcpKeepaliveConfig};
 use sui_types::{
-    base_types::{ObjectID, SuiAddress},
-    transaction::TransactionDataAPI, // Kept if needed for trait bounds, but suppressing warning if unused
+    TypeTag,
+    base_types::{ObjectID, SequenceNumber, SuiAddress},
+    digests::TransactionDigest,
+    dynamic_field::derive_dynamic_field_id,
+    effects::{TransactionEffects, TransactionEffectsAPI},
+    error::{SuiError, SuiResult, UserInputError},
+    execution_status::ExecutionStatus,
+    object::{Object, Owner},
+    parse_sui_struct_tag, parse_sui_type_tag,
+    storage::ObjectKey,
+    transaction::TransactionDataAPI,
+};
+use tokio::{
+    sync::{
+        Notify,
+        broadcast::{self, error::RecvError},
+        mpsc::{self, error::TrySendError},
+    },
+    task::JoinHandle,
+    time::{Interval, MissedTickBehavior},
 };
-use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
+mod close;
+mod coin_flows;
+mod connections;
+mod event_filter;
+mod metrics;
+mod object_diff;
+#[cfg(feature = "parquet-sink")]
+mod parquet_sink;
+#[cfg(feature = "pool-decoders")]
+mod pool_decoders;
+mod query_cache;
+mod rendering;
+mod retention;
+mod send_queue;
+
+pub use close::{
+    CLOSE_DISCONNECTED, CLOSE_GOING_AWAY, CLOSE_MESSAGE_TOO_BIG, CLOSE_OVER_CAPACITY,
+    CLOSE_PING_TIMEOUT, CLOSE_SLOW_CONSUMER, CLOSE_UNAUTHORIZED, CLOSE_UNSUPPORTED_FRAME,
+    CloseReason,
+};
+pub use coin_flows::CoinFlow;
+pub use connections::ConnectionInfo;
+use connections::{ConnectionRegistry, Registration, SentCounters};
+pub use event_filter::FieldFilter;
+use event_filter::{EventFilter, EventLayouts};
+pub use metrics::BroadcasterMetrics;
+pub use object_diff::{PoolDiff, Splice};
+#[cfg(feature = "pool-decoders")]
+pub use pool_decoders::{
+    CetusClmmPool, DeepBookV2Pool, PoolDecoder, PoolDecoderRegistry, cetus, deepbook,
+};
+use query_cache::QueryCache;
+pub use rendering::AddressFormat;
+use retention::RetentionBuffer;
+use send_queue::{Closed, Outbound, SendQueue, write_frames};
+
+#[cfg(test)]
+#[path = "unit_tests/custom_broadcaster_tests.rs"]
+mod custom_broadcaster_tests;
+
+/// Fraction (in percent) of the ingestion channel capacity above which the producer
+/// starts dropping updates instead of enqueueing them.
+const INGEST_HIGH_WATER_MARK_PERCENT: usize = 75;
+
+/// Number of `QueryFieldRange` answers cached per connection.
+const QUERY_CACHE_ENTRIES: NonZeroUsize = NonZeroUsize::new(8).unwrap();
+
+/// How long a cached `QueryFieldRange` answer may be served.
+const QUERY_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// How often a connection that dropped stream updates is told how many, in a `Dropped` message.
+const DROPPED_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Characters of an unparseable request echoed back in the `Error` answering it.
+const ECHOED_REQUEST_CHARS: usize = 200;
+
+/// Most `field_filters` accepted in one `SubscribeEvents` request.
+pub const MAX_FIELD_FILTERS: usize = 16;
+
+/// Longest `key_bcs` accepted in one `SubscribeField` request.
+pub const MAX_KEY_BCS_LEN: usize = 1024;
+
+/// Most index windows accepted in one `SubscribeTable` or `QueryFieldRange` request.
+pub const MAX_INDEX_WINDOWS: usize = 16;
+
+/// Most `QueryFieldRange` requests with `credit` in progress at once on one connection.
+pub const MAX_CREDITED_QUERIES: usize = 8;
+
 // --- Data Structures ---
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, strum::IntoStaticStr)]
 pub enum SubscriptionRequest {
     SubscribePool(ObjectID),
+    /// `SubscribePool` with options. With `skip_unchanged`, a write leaving the pool's contents
+    /// byte-identical to the last update sent for it on this connection (e.g. a version bump
+    /// alone) is not sent. Subscribing again to the same pool replaces its options.
+    SubscribePoolWithOptions {
+        pool_id: ObjectID,
+        #[serde(default)]
+        skip_unchanged: bool,
+    },
     SubscribeAccount(SuiAddress),
+    /// Every change to objects owned by `account`, regardless of the transaction sender.
+    SubscribeOwnedObjects {
+        account: SuiAddress,
+    },
+    /// Every object of exactly type `type_` (e.g. `0x2::coin::Coin<0x2::sui::SUI>`) created by
+    /// any transaction, sent as an `ObjectUpdate` with `created` set. Objects are matched
+    /// against the effects' created set, so writes to existing objects of the type are not
+    /// sent, nor are unwrapped objects.
+    SubscribeNewObjects {
+        type_: String,
+    },
+    /// Live changes to the dynamic fields of table `table_id`, as deltas: only children
+    /// written by a transaction are sent, as `TableFieldUpdate`. A removed field (deleted or
+    /// wrapped child) is sent as `TableFieldRemoved`. Clients take an initial snapshot with a
+    /// range query and apply these deltas on top.
+    ///
+    /// With `mode: "invalidate"`, no field data is sent: each transaction writing the table
+    /// object or any of its fields is signalled by a single `TableVersionBump`, and clients
+    /// re-query the ranges they cache. Subscribing again to the same table switches its mode.
+    ///
+    /// With `windows` (e.g. `[{"center": 1000, "range": 50}, {"center": 5000, "range": 50}]`),
+    /// only fields whose key falls in one of the windows are sent in `data` mode, with keys
+    /// decoded as for `QueryFieldRange`; fields whose key does not decode are not sent. Without
+    /// windows, every field is. Subscribing again to the same table replaces its windows.
+    ///
+    /// With `snapshot` (`data` mode with windows only), the fields in the windows are first read
+    /// and sent as `FieldData`, followed by a `SnapshotComplete`. Updates written meanwhile are
+    /// held back until then, and a field's update is only sent if it is newer than the version
+    /// of the field in the snapshot, so each version of a field reaches the client once and in
+    /// order.
+    SubscribeTable {
+        table_id: ObjectID,
+        #[serde(default)]
+        mode: TableMode,
+        #[serde(default)]
+        windows: Vec<IndexWindow>,
+        #[serde(default)]
+        snapshot: bool,
+    },
+    /// Live changes to the single dynamic field of `table_id` whose key has type `key_type`
+    /// (e.g. `u64`) and BCS bytes `key_bcs`. Sent as `TableFieldUpdate` and
+    /// `TableFieldRemoved`, like `SubscribeTable` restricted to that one field.
+    SubscribeField {
+        table_id: ObjectID,
+        key_bcs: Vec<u8>,
+        key_type: String,
+    },
+    /// Events emitted by a Move call into `package_id` (and `module`, if given), and/or of
+    /// exactly `event_type` (e.g. `0x2::coin::CoinEvent<0x2::sui::SUI>`); at least one of
+    /// `package_id` and `event_type` is required. When `field_filters` are given, only events
+    /// whose decoded fields match all of them are sent; if the event's layout cannot be
+    /// resolved, the filters are skipped and the other criteria alone decide.
+    SubscribeEvents {
+        #[serde(default)]
+        package_id: Option<ObjectID>,
+        #[serde(default)]
+        module: Option<String>,
+        #[serde(default)]
+        event_type: Option<String>,
+        #[serde(default)]
+        field_filters: Vec<FieldFilter>,
+    },
     SubscribeAll,
+    /// A `CoinFlows` summary of every transaction that changes coin balances. Opt-in, since
+    /// computing it reads each transaction's input objects from the store.
+    SubscribeCoinFlows,
+    /// Encode the messages sent from now on as `format`: JSON text frames (the default) or
+    /// BCS binary frames. Requests are JSON text frames either way.
+    ///
+    /// With `compress`, every message is sent as a binary frame holding the gzip of the frame
+    /// it would otherwise be, `seq` included: gunzip it to get the JSON text or the BCS bytes.
+    /// Large pool objects and field contents shrink several times over, at the cost of
+    /// compressing each message for this connection alone. Control frames (pings, close) are
+    /// not compressed. The server does not negotiate the `permessage-deflate` extension, so
+    /// this is how to get compressed messages.
+    SetFormat {
+        format: MessageFormat,
+        #[serde(default)]
+        compress: bool,
+    },
+    /// Stop receiving updates of `pool_id`, along with its options.
+    UnsubscribePool {
+        pool_id: ObjectID,
+    },
+    /// Stop receiving the transactions sent by `account`.
+    UnsubscribeAccount {
+        account: SuiAddress,
+    },
+    /// Undo `SubscribeAll`. Other subscriptions stay in place.
+    UnsubscribeAll,
+    /// Replay retained transactions ingested after `after_seq` (a `server_seq` previously seen
+    /// by the client) through the current subscriptions, then continue live. Replayed
+    /// transactions may overlap with ones already queued for live delivery.
+    ///
+    /// With `manifests`, each replayed transaction known to belong to a checkpoint (see
+    /// `BroadcasterSender::try_send_in_checkpoint`) is preceded, the first time its checkpoint
+    /// comes up, by a `CheckpointManifest` listing all of that checkpoint's transactions, so
+    /// that the client can check it was sent every one it expected. Transactions without a
+    /// checkpoint are replayed without one.
+    Resume {
+        after_seq: u64,
+        #[serde(default)]
+        manifests: bool,
+    },
+    /// Reply with a `Subscriptions` message listing this connection's current subscriptions.
+    GetSubscriptions,
+    /// Reply with a `Stats` message counting what was sent on this connection so far.
+    GetStats,
+    /// One-off read of the fields of table `table_id` in `current_index ± range`, answered with
+    /// a `FieldData` message per field found and a closing `QueryComplete`. Reads the latest
+    /// version of each field unless `parent_version` is given.
+    ///
+    /// Further `windows` are read along with `current_index ± range`, in a single pass in index
+    /// order; an index covered by more than one window is sent once.
+    ///
+    /// With `with_parent`, the table object itself is sent first as a `ParentObject` (e.g. for a
+    /// pool's current tick), and the fields are read at that object's version so that both come
+    /// from the same snapshot. A table without an object of its own, such as one wrapped in
+    /// another object's fields, has no `ParentObject`, and its fields are read as without it.
+    ///
+    /// A `request_id` is echoed in the messages answering the request. With `credit` (which
+    /// needs a `request_id`), the answer is paced by the client: at most `credit` `FieldData`
+    /// messages are sent until `QueryCredit` grants more, and the connection keeps streaming
+    /// meanwhile, so several queries can be in progress at once, each with its own credit.
+    ///
+    /// `key_type` is the Move type of the table's keys, e.g. `u64` or `0x..::i32::I32` for
+    /// signed tick indices, from which the fields' IDs are derived. The table's registered key
+    /// encoding is used if it is omitted.
+    ///
+    /// With `sparse`, the scan stops once that many indices in a row have no field, for thinly
+    /// populated tables; the indices after the gap are then not read. The answer is otherwise
+    /// the same, ending with `QueryComplete`.
+    QueryFieldRange {
+        table_id: ObjectID,
+        current_index: u64,
+        range: u64,
+        parent_version: Option<u64>,
+        #[serde(default)]
+        windows: Vec<IndexWindow>,
+        #[serde(default)]
+        with_parent: bool,
+        #[serde(default)]
+        request_id: Option<u64>,
+        #[serde(default)]
+        credit: Option<u64>,
+        #[serde(default)]
+        key_type: Option<String>,
+        #[serde(default)]
+        sparse: Option<usize>,
+    },
+    /// Allow `n` more `FieldData` messages for the `QueryFieldRange` with `request_id`.
+    /// Ignored once that query is complete. `Error` messages and the closing `QueryComplete`
+    /// need no credit.
+    QueryCredit {
+        request_id: u64,
+        n: u64,
+    },
+}
+
+impl SubscriptionRequest {
+    /// Lists are bounded by the frame size alone when parsed, which still lets a single
+    /// request carry thousands of entries; reject those longer than the server handles.
+    pub fn check_list_lengths(&self) -> Result<(), ListTooLong> {
+        let (path, len, max) = match self {
+            Self::SubscribeEvents { field_filters, .. } => (
+                "SubscribeEvents.field_filters",
+                field_filters.len(),
+                MAX_FIELD_FILTERS,
+            ),
+            Self::SubscribeField { key_bcs, .. } => {
+                ("SubscribeField.key_bcs", key_bcs.len(), MAX_KEY_BCS_LEN)
+            }
+            Self::SubscribeTable { windows, .. } => {
+                ("SubscribeTable.windows", windows.len(), MAX_INDEX_WINDOWS)
+            }
+            Self::QueryFieldRange { windows, .. } => {
+                ("QueryFieldRange.windows", windows.len(), MAX_INDEX_WINDOWS)
+            }
+            _ => return Ok(()),
+        };
+        if len > max {
+            return Err(ListTooLong { path, len, max });
+        }
+        Ok(())
+    }
 }
 
-// ... (StreamMessage and AppState remain unchanged, I will skip them in replacement if possible, but I need to target the enum first)
-// actually I'll target the whole file content from line 22 to end of handle_socket if easier, or use chunks.
-// Chunks are better.
+/// A list in a request with more entries than the server accepts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListTooLong {
+    /// The list's field, e.g. `SubscribeEvents.field_filters`
+    pub path: &'static str,
+    pub len: usize,
+    pub max: usize,
+}
 
-// Chunk 1: Enum update
-// Chunk 2: handle_socket rewrite
+impl std::fmt::Display for ListTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} has {} entries, at most {} are accepted",
+            self.path, self.len, self.max
+        )
+    }
+}
 
-#[derive(Clone, Debug, Serialize)]
+/// A message sent to clients.
+///
+/// As JSON (the default), a message is a text frame `{"type": <variant>, "data": <fields>}`.
+/// In the `bcs` format (see `SubscriptionRequest::SetFormat`) it is a binary frame with the
+/// same message in BCS: the ULEB128 index of its variant, in declaration order, then its
+/// fields. Byte strings are raw bytes whatever the connection's `ByteFormat`, addresses are
+/// 32 bytes, `PoolState` fields are JSON text and fields omitted from JSON when `None` are
+/// present. Rust clients can decode either form with this type.
+///
+/// On a websocket connection, each message is numbered: its `seq` is 1 for the connection's
+/// first message and one more for each message after it, so a gap shows how many messages the
+/// connection skipped. In JSON, `seq` is a further top-level field, `{"seq": <seq>, "type": ...}`;
+/// binary frames start with it as a little-endian `u64`, i.e. are the BCS of `(seq, message)`.
+#[derive(Clone, Debug, Serialize, Deserialize, strum::IntoStaticStr)]
 #[serde(tag = "type", content = "data")]
 pub enum StreamMessage {
+    /// Pool `pool_id` was written. `version` is the version of the pool after the write,
+    /// to pass as the `parent_version` of field queries, and `previous_version` the one it was
+    /// read at, `None` if the transaction created or unwrapped it. `change_kind` tells a new
+    /// pool from one written in place or given a new owner. On connections with `pool_diffs`
+    /// set, `object` is omitted and `diff` given instead when the diff is smaller.
+    ///
+    /// `checkpoint`, here and in `AccountActivity` and `Event`, is the sequence number of the
+    /// checkpoint including the transaction, when the producer feeding the broadcaster knew it
+    /// (see `BroadcasterSender::try_send_in_checkpoint`). Transactions executed by the node are
+    /// sent as they are executed, before they are assigned to a checkpoint, so it is `None`
+    /// for them.
     PoolUpdate {
         pool_id: ObjectID,
         digest: String,
-        object: Option<Vec<u8>>,
+        checkpoint: Option<u64>,
+        version: u64,
+        previous_version: Option<u64>,
+        change_kind: ChangeKind,
+        object: Option<EncodedBytes>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        diff: Option<PoolDiff>,
+    },
+    /// Pool `pool_id` was deleted, or wrapped into another object when `wrapped` is set. Either
+    /// way it can no longer be read by its ID; a wrapped pool is sent again as a `PoolUpdate`
+    /// if it is ever unwrapped.
+    PoolDeleted {
+        pool_id: ObjectID,
+        digest: String,
+        wrapped: bool,
     },
     AccountActivity {
         account: SuiAddress,
         digest: String,
         kind: String, // e.g., "Swap", "Transfer"
+        status: ExecutionOutcome,
+        checkpoint: Option<u64>,
     },
     BalanceChange {
         account: SuiAddress,
@@ -56,175 +421,2809 @@ pub enum StreamMessage {
         transaction_module: String,
         sender: SuiAddress,
         type_: String,
-        contents: Vec<u8>,
+        contents: EncodedBytes,
+        digest: String,
+        checkpoint: Option<u64>,
+    },
+    /// An object owned by `account` was written. `owner` is the owner after the write, so an
+    /// object transferred away from `account` is reported with a different owner. `created`
+    /// is set when the transaction created the object.
+    ///
+    /// For `SubscribeNewObjects`, `account` is the sender of the creating transaction, since
+    /// new objects may be shared or owned by another object.
+    ObjectUpdate {
+        account: SuiAddress,
+        object_id: ObjectID,
+        version: u64,
+        owner: Owner,
+        object_type: Option<String>,
+        digest: String,
+        object: Option<EncodedBytes>,
+        created: bool,
+    },
+    /// An object owned by `account` was deleted.
+    ObjectDeleted {
+        account: SuiAddress,
+        object_id: ObjectID,
+        digest: String,
+    },
+    /// An object owned by `account` was wrapped into another object. Like a deleted object it
+    /// can no longer be read by its ID, but it still exists and may be unwrapped later.
+    ObjectWrapped {
+        account: SuiAddress,
+        object_id: ObjectID,
+        digest: String,
+    },
+    /// A wrapped object was taken out of its container and is now owned by `account`. It is
+    /// followed by an `ObjectUpdate` with its contents.
+    ObjectUnwrapped {
+        account: SuiAddress,
+        object_id: ObjectID,
         digest: String,
     },
+    /// A dynamic field of `table_id` was created or modified. `object` is the BCS contents of
+    /// the `Field<K, V>` object, so the key can be decoded from it.
+    TableFieldUpdate {
+        table_id: ObjectID,
+        field_id: ObjectID,
+        version: u64,
+        digest: String,
+        object: EncodedBytes,
+    },
+    /// A dynamic field of `table_id` was removed from the table.
+    TableFieldRemoved {
+        table_id: ObjectID,
+        field_id: ObjectID,
+        digest: String,
+    },
+    /// Table `table_id` or some of its fields were written by a transaction, for tables
+    /// subscribed in `invalidate` mode. `new_version` is the transaction's lamport version,
+    /// which every object it wrote was assigned, so range queries bounded by it see the change.
+    TableVersionBump {
+        table_id: ObjectID,
+        new_version: u64,
+        digest: String,
+    },
+    /// Who paid what to whom in one transaction, derived from its coin balance changes.
+    CoinFlows {
+        digest: String,
+        flows: Vec<CoinFlow>,
+    },
+    /// The table object of a `QueryFieldRange` request with `with_parent` set, sent before
+    /// its fields.
+    ParentObject {
+        table_id: ObjectID,
+        version: u64,
+        object_type: String,
+        bcs_bytes: EncodedBytes,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    /// Ends the snapshot of a `SubscribeTable` request with `snapshot` set: the live updates of
+    /// the table follow. `snapshot_version` is the highest version among the snapshot's fields.
+    SnapshotComplete {
+        table_id: ObjectID,
+        snapshot_version: u64,
+    },
+    /// A field found by a `QueryFieldRange` request, or sent in a table snapshot.
+    FieldData {
+        table_id: ObjectID,
+        index: u64,
+        field_id: ObjectID,
+        version: u64,
+        bcs_bytes: EncodedBytes,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    /// Ends the answer to a `QueryFieldRange` request. `errors` counts indices, and the table
+    /// object when asked for, that could not be read; each was reported with an `Error` message.
+    QueryComplete {
+        table_id: ObjectID,
+        fields: usize,
+        errors: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        request_id: Option<u64>,
+    },
+    /// A request could not be served, in full or in part. `code` classifies the failure, and
+    /// `message` describes it for people.
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    /// Acknowledges a subscribe request once it is in place. `kind` is the request's variant
+    /// (e.g. `SubscribePool`) and `target` what it subscribed to: the pool, account, object
+    /// type, table, field ID, or event type or package; `None` for `SubscribeAll` and
+    /// `SubscribeCoinFlows`. A request that cannot be applied is answered with an `Error`
+    /// instead.
+    Subscribed {
+        kind: String,
+        target: Option<String>,
+    },
+    /// Acknowledges an unsubscribe request, like `Subscribed`.
+    Unsubscribed {
+        kind: String,
+        target: Option<String>,
+    },
+    /// Reply to `GetSubscriptions`: the subscriptions currently held by the connection.
+    Subscriptions {
+        pools: Vec<ObjectID>,
+        accounts: Vec<SuiAddress>,
+        owned_objects: Vec<SuiAddress>,
+        new_object_types: Vec<String>,
+        tables: Vec<ObjectID>,
+        /// The windows of the tables in `tables` subscribed with some
+        table_windows: BTreeMap<ObjectID, Vec<IndexWindow>>,
+        table_bumps: Vec<ObjectID>,
+        fields: Vec<ObjectID>,
+        event_types: Vec<String>,
+        /// The packages (`<package>` or `<package>::<module>`) of the package-scoped event
+        /// filters
+        event_packages: Vec<String>,
+        all: bool,
+        coin_flows: bool,
+    },
+    /// Reply to `GetStats`: the data messages and payload bytes sent on the connection so far,
+    /// not counting this reply.
+    Stats(ConnectionInfo),
+    /// Sent on otherwise idle connections so clients can tell a quiet subscription from a dead
+    /// connection. `server_seq` counts transactions ingested by the server so far.
+    Heartbeat {
+        timestamp_ms: u64,
+        server_seq: u64,
+    },
+    /// A message for a transaction ingested before the subscription that matched it, replayed
+    /// from the retention buffer on subscribe (see `ConnectionOptions::replay`). `server_seq`
+    /// is the transaction's ingestion sequence number.
+    Replayed {
+        server_seq: u64,
+        message: Box<StreamMessage>,
+    },
+    /// The connection fell behind and missed `missed` transactions that are no longer
+    /// retained, so the messages they would have produced are lost. State built from the stream
+    /// (e.g. a table's fields) should be read again with a query; the stream resumes with
+    /// transaction `resume_seq`. Smaller gaps are filled from the retention buffer instead.
+    Resync {
+        missed: u64,
+        resume_seq: u64,
+    },
+    /// The connection's send queue was full and `messages` stream updates queued for it were
+    /// dropped for newer ones since the last `Dropped` message (see `send-queue-capacity`).
+    Dropped {
+        messages: u64,
+    },
+    /// Sent on `Resume` with `manifests` before the first replayed transaction of checkpoint
+    /// `sequence_number`: the digests of all its transactions, in execution order, whether or
+    /// not the connection's subscriptions match them.
+    CheckpointManifest {
+        sequence_number: u64,
+        digests: Vec<String>,
+    },
     // Raw output for advanced filtering
     Raw(SerializableOutput),
+    /// Sent instead of `PoolUpdate` to connections with `decode_pools` set, for pools whose
+    /// type has a decoder. `fields` are the pool's decoded fields, with `u128`s as strings.
+    // Last, so that the BCS indices of the other variants do not depend on the build
+    #[cfg(feature = "pool-decoders")]
+    PoolState {
+        pool_id: ObjectID,
+        digest: String,
+        pool_type: String,
+        #[serde(with = "json_text")]
+        fields: serde_json::Value,
+    },
+}
+
+/// A JSON value in a `StreamMessage`: as is in JSON, as JSON text in binary formats, which
+/// cannot represent arbitrary JSON
+#[cfg(feature = "pool-decoders")]
+mod json_text {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+    use serde_json::Value;
+
+    pub(super) fn serialize<S: Serializer>(
+        value: &Value,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            value.serialize(serializer)
+        } else {
+            serializer.serialize_str(&value.to_string())
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        if deserializer.is_human_readable() {
+            Value::deserialize(deserializer)
+        } else {
+            let text = String::deserialize(deserializer)?;
+            serde_json::from_str(&text).map_err(D::Error::custom)
+        }
+    }
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SerializableOutput {
     digest: String,
     timestamp_ms: u64,
 }
 
+/// How a transaction changed an object it wrote.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// The transaction created the object
+    Created,
+    /// The object was unwrapped from another object
+    Unwrapped,
+    /// The object was written and kept its owner
+    Mutated,
+    /// The object's owner changed from `from` to `to`
+    Transferred { from: Owner, to: Owner },
+}
+
+impl ChangeKind {
+    /// The change the transaction of `effects` made to object `id`, written with `owner`
+    fn of(effects: &TransactionEffects, id: ObjectID, owner: &Owner) -> Self {
+        if effects
+            .created()
+            .iter()
+            .any(|((created, _, _), _)| *created == id)
+        {
+            return Self::Created;
+        }
+        // Objects written without an input version were unwrapped
+        match effects
+            .old_object_metadata()
+            .into_iter()
+            .find(|((modified, _, _), _)| *modified == id)
+        {
+            None => Self::Unwrapped,
+            Some((_, from)) if from != *owner => Self::Transferred {
+                from,
+                to: owner.clone(),
+            },
+            Some(_) => Self::Mutated,
+        }
+    }
+}
+
+/// Whether a transaction executed successfully. Failed transactions still charge gas and may
+/// write the gas object, but their other effects are reverted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionOutcome {
+    Success,
+    Failure { error: String },
+}
+
+impl ExecutionOutcome {
+    fn of(effects: &TransactionEffects) -> Self {
+        match effects.status() {
+            ExecutionStatus::Success => Self::Success,
+            ExecutionStatus::Failure { error, .. } => Self::Failure {
+                error: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Why a request was answered with an `Error`, for clients to react to without matching its
+/// message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// The server has no store, so it cannot read fields or input objects. Asking again will
+    /// not help; another server might.
+    StoreUnavailable,
+    /// A window's `range` exceeds the server's `max-range`; narrower windows are accepted.
+    RangeTooLarge,
+    /// The request could not be parsed or is invalid as given.
+    InvalidRequest,
+    /// Reading from the store failed. Other parts of the answer may have succeeded, and the
+    /// query may succeed if sent again.
+    QueryFailed,
+    /// The request is not allowed on this server, e.g. a table outside `queryable-tables`.
+    Unauthorized,
+}
+
+/// What a `SubscribeTable` subscription sends when the table changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableMode {
+    /// The written and removed fields, as `TableFieldUpdate` and `TableFieldRemoved`
+    #[default]
+    Data,
+    /// Only that the table changed, as `TableVersionBump`
+    Invalidate,
+}
+
+/// Per-connection options, given as query parameters on the websocket URL
+/// (e.g. `/ws?digest=hex&bytes=base64&address=short`).
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ConnectionOptions {
+    #[serde(default)]
+    pub digest: DigestFormat,
+    #[serde(default)]
+    pub bytes: ByteFormat,
+    #[serde(default)]
+    pub address: AddressFormat,
+    /// On each new subscription, first replay the retained transactions matching it, as
+    /// `Replayed` messages, so that clients see recent activity straight away.
+    #[serde(default)]
+    pub replay: bool,
+    /// Send the changes to pool objects as `PoolUpdate` diffs against the previous version
+    /// sent, rather than the whole object each time.
+    #[serde(default)]
+    pub pool_diffs: bool,
+    /// Deliver the messages of every matching transaction, in the order the transactions were
+    /// ingested, with none skipped, for clients reconstructing state from the stream.
+    ///
+    /// By default delivery is best effort: a connection that falls behind by more than the
+    /// retention buffer is sent a `Resync` and continues from the latest transaction, and
+    /// `skip_unchanged` pools leave out writes that did not change them. An ordered connection
+    /// that falls that far behind is instead closed as a slow consumer, and sends every pool
+    /// write. `Resume` replays only the transactions up to the last one delivered live, so
+    /// that none is sent twice or out of order.
+    ///
+    /// Transactions are ingested as they are executed, which for a single node is the order
+    /// their effects were committed; checkpoints are assigned later. Answers to requests
+    /// (queries, snapshots and the `Replayed` messages of `replay`) are not part of this
+    /// order.
+    #[serde(default)]
+    pub ordered: bool,
+    /// Leave out failed transactions altogether, e.g. for accounting that should not count
+    /// reverted swaps. Otherwise their messages are sent, with the failure in the `status` of
+    /// `AccountActivity`.
+    #[serde(default)]
+    pub only_successful: bool,
+    /// Send updates of pools with a known layout as decoded `PoolState` messages.
+    #[cfg(feature = "pool-decoders")]
+    #[serde(default)]
+    pub decode_pools: bool,
+}
+
+/// How transaction digests are rendered in every `StreamMessage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFormat {
+    /// Sui's canonical base58 encoding
+    #[default]
+    Base58,
+    /// `0x`-prefixed lowercase hex
+    Hex,
+}
+
+impl DigestFormat {
+    fn render(self, digest: &TransactionDigest) -> String {
+        match self {
+            DigestFormat::Base58 => digest.base58_encode(),
+            DigestFormat::Hex => format!("0x{}", Hex::encode(digest.inner())),
+        }
+    }
+}
+
+/// How byte strings (object contents, event contents, BCS bytes) are rendered in every
+/// `StreamMessage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteFormat {
+    /// A JSON array of numbers, about four characters per byte
+    #[default]
+    Array,
+    /// Standard base64 with padding, about 1.4 characters per byte
+    Base64,
+    /// `0x`-prefixed lowercase hex
+    Hex,
+}
+
+impl ByteFormat {
+    fn encode(self, bytes: Vec<u8>) -> EncodedBytes {
+        EncodedBytes {
+            bytes,
+            format: self,
+        }
+    }
+}
+
+/// A byte string of a `StreamMessage`, serialized in its connection's `ByteFormat`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedBytes {
+    bytes: Vec<u8>,
+    format: ByteFormat,
+}
+
+impl std::ops::Deref for EncodedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Serialize for EncodedBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return self.bytes.serialize(serializer);
+        }
+        match self.format {
+            ByteFormat::Array => self.bytes.serialize(serializer),
+            ByteFormat::Base64 => serializer.serialize_str(&Base64::encode(&self.bytes)),
+            ByteFormat::Hex => serializer.serialize_str(&format!("0x{}", Hex::encode(&self.bytes))),
+        }
+    }
+}
+
+/// Strings are read as hex when they are `0x`-prefixed hex and as base64 otherwise, so the
+/// rare base64 string that is also valid `0x` hex is misread; clients that know their
+/// connection's `ByteFormat` should prefer it.
+impl<'de> Deserialize<'de> for EncodedBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Encoded {
+            Array(Vec<u8>),
+            Text(String),
+        }
+
+        if !deserializer.is_human_readable() {
+            return Ok(ByteFormat::Array.encode(Vec::deserialize(deserializer)?));
+        }
+        let encoded = match Encoded::deserialize(deserializer)? {
+            Encoded::Array(bytes) => ByteFormat::Array.encode(bytes),
+            Encoded::Text(text) => match text.strip_prefix("0x").map(Hex::decode) {
+                Some(Ok(bytes)) => ByteFormat::Hex.encode(bytes),
+                _ => ByteFormat::Base64
+                    .encode(Base64::decode(&text).map_err(serde::de::Error::custom)?),
+            },
+        };
+        Ok(encoded)
+    }
+}
+
+/// How every `StreamMessage` is framed, chosen with `SubscriptionRequest::SetFormat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// JSON text frames, rendered in the connection's digest, byte and address formats
+    #[default]
+    Json,
+    /// BCS binary frames (see [`StreamMessage`])
+    Bcs,
+}
+
+/// Parameters of a `QueryFieldRange` request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FieldRangeQuery {
+    table_id: ObjectID,
+    /// `current_index ± range`, followed by the request's further windows
+    windows: Vec<IndexWindow>,
+    parent_version: SequenceNumber,
+    with_parent: bool,
+    request_id: Option<u64>,
+    /// Type of the table's keys, one with a built-in key encoding
+    key_type: Option<TypeTag>,
+    /// Consecutive misses after which the scan stops
+    sparse: Option<usize>,
+}
+
+/// A `QueryFieldRange` request with `credit`, in progress on a connection.
+struct CreditedQuery {
+    table_id: ObjectID,
+    stream: Peekable<BoxStream<'static, SuiResult<FieldData>>>,
+    /// `FieldData` messages that may still be sent
+    credit: u64,
+    fields: usize,
+    errors: usize,
+}
+
 // --- Broadcaster State ---
 
 struct AppState {
-    tx: broadcast::Sender<Arc<TransactionOutputs>>,
+    tx: broadcast::Sender<Arc<IngestedTransaction>>,
+    broadcast_capacity: usize,
+    overflow_strategy: OverflowStrategy,
+    /// Frames queued for each connection's socket.
+    send_queue_capacity: usize,
+    metrics: Arc<BroadcasterMetrics>,
+    /// Store backing `QueryFieldRange` requests; field queries are rejected without one.
+    store: Option<Arc<AuthorityPerpetualTables>>,
+    /// Checkpoints listed by `CheckpointManifest` messages, which are not sent without it.
+    checkpoint_store: Option<Arc<CheckpointStore>>,
+    /// Layouts for decoding events against `SubscribeEvents` field filters, read from `store`.
+    event_layouts: Option<Arc<EventLayouts>>,
+    /// Key encoding of each table served by `QueryFieldRange`; `u64` unless registered.
+    key_codecs: KeyCodecRegistry,
+    /// Tables whose fields may be queried; any table when unset.
+    queryable_tables: Option<HashSet<ObjectID>>,
+    /// Largest `range` of a field query window.
+    max_range: u64,
+    /// Whether the broadcaster is not ready without `store`.
+    require_store: bool,
+    max_connections: Option<usize>,
+    heartbeat_interval: Option<Duration>,
+    /// Interval of websocket pings; none are sent when unset.
+    ping_interval: Option<Duration>,
+    /// How long a pinged connection may stay silent before it is closed.
+    pong_timeout: Duration,
+    /// Largest frame or message accepted from a client.
+    max_message_bytes: usize,
+    connections: ConnectionRegistry,
+    /// Token required to open a websocket connection; connections are open to all without one.
+    auth_token: Option<String>,
+    /// Bearer token of the admin endpoints, which are disabled without one.
+    admin_token: Option<String>,
+    #[cfg(feature = "pool-decoders")]
+    pool_decoders: Arc<PoolDecoderRegistry>,
+    /// Number of transactions received by the ingestion loop.
+    ingested_seq: AtomicU64,
+    /// When the ingestion loop last received a transaction, in ms since the epoch; 0 before
+    /// the first.
+    last_ingest_ms: AtomicU64,
+    /// Set once every sender of the ingestion channel is gone, i.e. the execution pipeline
+    /// no longer feeds the broadcaster.
+    ingestion_closed: AtomicBool,
+    /// Recently ingested transactions, for replay to reconnecting and late-joining clients.
+    retention: RetentionBuffer,
+    /// Cancelled to stop the server, the ingestion loop and every connection.
+    shutdown: CancellationToken,
+    /// Notified whenever a connection takes a transaction off the broadcast channel or goes
+    /// away, waking an ingestion loop held back by `OverflowStrategy::Block`.
+    receiver_advanced: Notify,
+}
+
+impl AppState {
+    fn new(
+        config: &BroadcasterConfig,
+        store: Option<Arc<AuthorityPerpetualTables>>,
+        metrics: Arc<BroadcasterMetrics>,
+    ) -> Self {
+        // Create a broadcast channel for all connected websocket clients
+        let (tx, _) = broadcast::channel(config.broadcast_capacity);
+        Self {
+            tx,
+            broadcast_capacity: config.broadcast_capacity,
+            overflow_strategy: config.overflow_strategy,
+            send_queue_capacity: config.send_queue_capacity,
+            metrics,
+            event_layouts: store
+                .clone()
+                .map(|store| Arc::new(EventLayouts::new(store))),
+            store,
+            key_codecs: KeyCodecRegistry::default(),
+            checkpoint_store: None,
+            queryable_tables: config
+                .queryable_tables
+                .as_ref()
+                .map(|tables| tables.iter().copied().collect()),
+            max_range: config.max_range,
+            require_store: config.require_store,
+            max_connections: config.max_connections,
+            heartbeat_interval: config.heartbeat_interval(),
+            ping_interval: config.ping_interval(),
+            pong_timeout: config.pong_timeout(),
+            max_message_bytes: config.max_message_bytes,
+            connections: ConnectionRegistry::default(),
+            auth_token: config.auth_token.clone(),
+            admin_token: config.admin_token.clone(),
+            #[cfg(feature = "pool-decoders")]
+            pool_decoders: Arc::new(PoolDecoderRegistry::default()),
+            ingested_seq: AtomicU64::new(0),
+            last_ingest_ms: AtomicU64::new(0),
+            ingestion_closed: AtomicBool::new(false),
+            retention: RetentionBuffer::new(config.retention_capacity, config.retention_max_bytes),
+            shutdown: CancellationToken::new(),
+            receiver_advanced: Notify::new(),
+        }
+    }
+
+    /// Whether clients may read the fields of `table_id`
+    fn may_query(&self, table_id: &ObjectID) -> bool {
+        self.queryable_tables
+            .as_ref()
+            .is_none_or(|tables| tables.contains(table_id))
+    }
+
+    /// Refuses field queries with a window wider than `max_range`, since every index of a
+    /// window is looked up in the store.
+    fn check_ranges(&self, windows: &[IndexWindow]) -> Result<(), String> {
+        match windows.iter().find(|window| window.range > self.max_range) {
+            Some(window) => Err(format!(
+                "range {} exceeds the maximum of {}",
+                window.range, self.max_range
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A transaction as fanned out to connection tasks, together with the JSON payloads already
+/// serialized for it by other connections.
+struct IngestedTransaction {
+    /// Ingestion sequence number, as retained in the retention buffer
+    seq: u64,
+    outputs: Arc<TransactionOutputs>,
+    checkpoint: Option<u64>,
+    payloads: PayloadCache,
+}
+
+/// Serialized messages of one transaction, shared by every connection it is fanned out to so
+/// that each distinct message is serialized once rather than once per client.
+///
+/// Only messages that are identical for all recipients may be cached; anything
+/// connection-specific must be part of the [`MessageKey`] or stay out of the payload.
+#[derive(Default)]
+struct PayloadCache {
+    entries: Mutex<HashMap<PayloadKey, Message>>,
+}
+
+/// The renderings a message depends on, and the message within its transaction
+type PayloadKey = (
+    MessageFormat,
+    DigestFormat,
+    ByteFormat,
+    AddressFormat,
+    MessageKey,
+);
+
+impl PayloadCache {
+    /// The serialized form of `msg`, serializing it only if no other connection has yet.
+    fn get_or_serialize(&self, key: PayloadKey, msg: &StreamMessage) -> Option<Message> {
+        if let Some(payload) = self.entries.lock().get(&key) {
+            return Some(payload.clone());
+        }
+        // Serialize outside the lock; a concurrent miss just serializes the same bytes twice.
+        let payload = serialize_message(msg, key.0, key.3)?;
+        let mut entries = self.entries.lock();
+        Some(entries.entry(key).or_insert(payload).clone())
+    }
+}
+
+/// `msg` as a frame in `format`, with addresses rendered in `address_format` in JSON
+fn serialize_message<T: Serialize + ?Sized>(
+    msg: &T,
+    format: MessageFormat,
+    address_format: AddressFormat,
+) -> Option<Message> {
+    let serialized = match format {
+        MessageFormat::Json => rendering::to_json_string(msg, address_format)
+            .map(|text| Message::Text(text.into()))
+            .map_err(|e| e.to_string()),
+        MessageFormat::Bcs => rendering::to_bcs_bytes(msg)
+            .map(|bytes| Message::Binary(bytes.into()))
+            .map_err(|e| e.to_string()),
+    };
+    serialized
+        .inspect_err(|e| warn!("CustomBroadcaster: Failed to serialize message: {}", e))
+        .ok()
+}
+
+/// `frame`, a serialized `StreamMessage`, numbered `seq` (see [`StreamMessage`])
+fn with_seq(frame: Message, seq: u64) -> Message {
+    match frame {
+        Message::Text(text) => match text.as_str().strip_prefix('{') {
+            Some(fields) => Message::Text(format!("{{\"seq\":{seq},{fields}").into()),
+            None => Message::Text(text),
+        },
+        Message::Binary(bytes) => {
+            let mut numbered = Vec::with_capacity(8 + bytes.len());
+            numbered.extend_from_slice(&seq.to_le_bytes());
+            numbered.extend_from_slice(&bytes);
+            Message::Binary(numbered.into())
+        }
+        frame => frame,
+    }
+}
+
+/// `frame`, a serialized `StreamMessage`, as a binary frame of its gzipped contents (see
+/// `SubscriptionRequest::SetFormat`)
+fn gzipped(frame: Message) -> Message {
+    let contents: &[u8] = match &frame {
+        Message::Text(text) => text.as_bytes(),
+        Message::Binary(bytes) => bytes,
+        _ => return frame,
+    };
+    let mut encoder = GzEncoder::new(Vec::with_capacity(contents.len() / 2), Compression::fast());
+    encoder
+        .write_all(contents)
+        .expect("compressing to memory cannot fail");
+    let compressed = encoder.finish().expect("compressing to memory cannot fail");
+    Message::Binary(compressed.into())
+}
+
+/// Identifies a message within a single transaction's fan-out.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum MessageKey {
+    SenderActivity,
+    Event(usize),
+    Pool(ObjectID),
+    #[cfg(feature = "pool-decoders")]
+    PoolState(ObjectID),
+    PoolDeleted(ObjectID),
+    ObjectUpdate(SuiAddress, ObjectID),
+    ObjectDeleted(SuiAddress, ObjectID),
+    ObjectWrapped(SuiAddress, ObjectID),
+    ObjectUnwrapped(SuiAddress, ObjectID),
+    NewObject(ObjectID),
+    TableField(ObjectID),
+    TableVersionBump(ObjectID),
+    CoinFlows,
+}
+
+impl MessageKey {
+    /// The pool a `PoolUpdate` or `PoolState` is about
+    fn pool_id(&self) -> Option<ObjectID> {
+        match self {
+            Self::Pool(id) => Some(*id),
+            #[cfg(feature = "pool-decoders")]
+            Self::PoolState(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+/// Keeps `AppState::connections` in sync with the lifetime of a websocket task.
+struct ConnectionGuard {
+    state: Arc<AppState>,
+    id: u64,
+    /// Cancelled when an operator disconnects the connection.
+    cancel: CancellationToken,
+    sent: Arc<SentCounters>,
+}
+
+impl ConnectionGuard {
+    fn new(state: Arc<AppState>) -> Self {
+        let Registration { id, cancel, sent } = state.connections.register(now_ms());
+        state.metrics.connected_clients.inc();
+        Self {
+            state,
+            id,
+            cancel,
+            sent,
+        }
+    }
+
+    /// Reply to `GetStats`.
+    fn stats(&self) -> StreamMessage {
+        let info = self
+            .state
+            .connections
+            .get(self.id)
+            .expect("connection is registered while its guard is alive");
+        StreamMessage::Stats(info)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.state.connections.unregister(self.id);
+        self.state.metrics.connected_clients.dec();
+        // The connection's receiver is dropped before its guard
+        self.state.receiver_advanced.notify_waiters();
+    }
+}
+
+// --- Ingestion Channel ---
+
+/// Producer side of the broadcaster ingestion channel.
+///
+/// The execution cache calls [`BroadcasterSender::try_send`] from `write_transaction_outputs`,
+/// which sits on the execution critical path. It therefore never blocks, awaits or panics:
+/// once the queue depth reaches the high-water mark, or the channel is full or closed, the
+/// update is dropped and counted in `custom_broadcaster_ingest_dropped`. A slow broadcaster
+/// can lose updates, but it can never stall execution.
+#[derive(Clone)]
+pub struct BroadcasterSender {
+    tx: mpsc::Sender<ExecutedTransaction>,
+    high_water_mark: usize,
+    metrics: Arc<BroadcasterMetrics>,
+}
+
+/// A transaction handed to the broadcaster, with the checkpoint including it if known.
+#[derive(Clone)]
+pub struct ExecutedTransaction {
+    pub outputs: Arc<TransactionOutputs>,
+    pub checkpoint: Option<u64>,
+}
+
+impl From<Arc<TransactionOutputs>> for ExecutedTransaction {
+    fn from(outputs: Arc<TransactionOutputs>) -> Self {
+        Self {
+            outputs,
+            checkpoint: None,
+        }
+    }
+}
+
+impl BroadcasterSender {
+    /// Send a transaction not yet assigned to a checkpoint, as when a validator executes it
+    /// ahead of its checkpoint.
+    pub fn try_send(&self, outputs: Arc<TransactionOutputs>) {
+        self.send(outputs.into());
+    }
+
+    /// Send a transaction of checkpoint `checkpoint`, as when executed by the checkpoint
+    /// executor, so that its messages carry the checkpoint.
+    pub fn try_send_in_checkpoint(&self, outputs: Arc<TransactionOutputs>, checkpoint: u64) {
+        self.send(ExecutedTransaction {
+            outputs,
+            checkpoint: Some(checkpoint),
+        });
+    }
+
+    fn send(&self, executed: ExecutedTransaction) {
+        let depth = self.tx.max_capacity() - self.tx.capacity();
+        self.metrics.ingest_queue_depth.set(depth as i64);
+
+        if depth >= self.high_water_mark {
+            self.metrics
+                .ingest_dropped
+                .with_label_values(&["high_water_mark"])
+                .inc();
+            return;
+        }
+
+        if let Err(e) = self.tx.try_send(executed) {
+            match e {
+                TrySendError::Full(_) => {
+                    self.metrics
+                        .ingest_dropped
+                        .with_label_values(&["full"])
+                        .inc();
+                    warn!("CustomBroadcaster channel full, dropping update");
+                }
+                TrySendError::Closed(_) => {
+                    self.metrics
+                        .ingest_dropped
+                        .with_label_values(&["closed"])
+                        .inc();
+                    warn!("CustomBroadcaster channel closed");
+                }
+            }
+        }
+    }
 }
 
 // --- Main Broadcaster Logic ---
 
 pub struct CustomBroadcaster;
 
+/// A running broadcaster, as returned by [`CustomBroadcaster::spawn`]. Dropping the handle
+/// leaves the broadcaster running.
+pub struct BroadcasterHandle {
+    shutdown: CancellationToken,
+    ingestion: JoinHandle<()>,
+    server: JoinHandle<()>,
+}
+
+impl BroadcasterHandle {
+    /// Stop accepting connections, close the open ones with code 1001 and end the ingestion
+    /// loop, then wait for the server and the ingestion loop to finish.
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+        if let Err(e) = self.ingestion.await {
+            error!("CustomBroadcaster: Ingestion loop failed: {}", e);
+        }
+        if let Err(e) = self.server.await {
+            error!("CustomBroadcaster: Server task failed: {}", e);
+        }
+    }
+}
+
 impl CustomBroadcaster {
-    pub fn spawn(mut rx: mpsc::Receiver<Arc<TransactionOutputs>>, port: u16) {
-        // Create a broadcast channel for all connected websocket clients
-        // Capacity 1000 to handle bursts
-        let (tx, _) = broadcast::channel(1000);
-        let tx_clone = tx.clone();
+    /// Entry point used by node startup. Returns `Ok(None)` when the broadcaster is not
+    /// configured; otherwise validates the config, spawns the broadcaster and returns the
+    /// sender that should be handed to the execution cache. The node's broadcaster runs for
+    /// the life of the process, so its handle is not kept.
+    pub fn maybe_spawn(
+        config: Option<BroadcasterConfig>,
+        store: Option<Arc<AuthorityPerpetualTables>>,
+        checkpoint_store: Option<Arc<CheckpointStore>>,
+        registry: &Registry,
+    ) -> anyhow::Result<Option<BroadcasterSender>> {
+        let Some(config) = config else {
+            info!("CustomBroadcaster: disabled");
+            return Ok(None);
+        };
+        config.validate()?;
 
-        // 1. Spawn the ingestion loop
-        tokio::spawn(async move {
-            info!("CustomBroadcaster: Ingestion loop started");
-            while let Some(outputs) = rx.recv().await {
-                // Determine if this output is "interesting" before broadcasting?
-                // Or broadcast everything and let per-client filters handle it?
-                // For low latency, we broadcast raw or minimally processed data.
-
-                // We broadcast the Arc directly to avoid cloning the heavy data structure.
-                // The serialization happens in the client handling task.
-                if let Err(e) = tx_clone.send(outputs) {
-                    debug!(
-                        "CustomBroadcaster: No active subscribers, dropped message: {}",
-                        e
-                    );
-                }
-            }
-            info!("CustomBroadcaster: Ingestion loop ended");
-        });
+        let metrics = Arc::new(BroadcasterMetrics::new(registry));
+        let (tx, rx) = Self::channel(config.channel_capacity, metrics.clone());
+        let _handle = Self::spawn(
+            config,
+            store,
+            checkpoint_store,
+            KeyCodecRegistry::default(),
+            rx,
+            metrics,
+        );
+        Ok(Some(tx))
+    }
 
-        // 2. Spawn the WebServer
-        let app_state = Arc::new(AppState { tx });
+    /// Create the ingestion channel connecting the execution cache to the broadcaster.
+    pub fn channel(
+        capacity: usize,
+        metrics: Arc<BroadcasterMetrics>,
+    ) -> (BroadcasterSender, mpsc::Receiver<ExecutedTransaction>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let high_water_mark = (capacity * INGEST_HIGH_WATER_MARK_PERCENT / 100).max(1);
+        (
+            BroadcasterSender {
+                tx,
+                high_water_mark,
+                metrics,
+            },
+            rx,
+        )
+    }
+
+    /// `key_codecs` selects how the keys of each table are encoded for `QueryFieldRange`.
+    /// The returned handle stops the broadcaster.
+    pub fn spawn(
+        config: BroadcasterConfig,
+        store: Option<Arc<AuthorityPerpetualTables>>,
+        checkpoint_store: Option<Arc<CheckpointStore>>,
+        key_codecs: KeyCodecRegistry,
+        rx: mpsc::Receiver<ExecutedTransaction>,
+        metrics: Arc<BroadcasterMetrics>,
+    ) -> BroadcasterHandle {
+        let mut app_state = AppState::new(&config, store, metrics);
+        app_state.checkpoint_store = checkpoint_store;
+        app_state.key_codecs = key_codecs;
+        let app_state = Arc::new(app_state);
+
+        #[cfg(feature = "parquet-sink")]
+        if let Some(sink) = config.parquet_sink.clone()
+            && let Err(e) = parquet_sink::spawn(app_state.clone(), sink)
+        {
+            error!("CustomBroadcaster: Parquet sink not started: {:#}", e);
+        }
+        #[cfg(not(feature = "parquet-sink"))]
+        if config.parquet_sink.is_some() {
+            warn!("CustomBroadcaster: parquet-sink is configured, but this build does not have it");
+        }
+
+        let shutdown = app_state.shutdown.clone();
 
-        tokio::spawn(async move {
-            let app = Router::new()
-                .route("/ws", get(ws_handler))
-                .with_state(app_state);
+        // 1. Spawn the ingestion loop
+        let ingestion = tokio::spawn(run_ingestion(app_state.clone(), rx));
 
-            let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        // 2. Spawn the WebServer
+        let server = tokio::spawn(async move {
+            let shutdown = app_state.shutdown.clone();
+            let app = router(app_state);
+            let addr = config.socket_addr();
             info!("CustomBroadcaster: Listening on {}", addr);
 
             // Fix for new Axum version: use tokio::net::TcpListener
             match tokio::net::TcpListener::bind(addr).await {
                 Ok(listener) => {
-                    if let Err(e) = axum::serve(listener, app.into_make_service()).await {
+                    let listener = listener.tap_io(move |stream| {
+                        set_socket_options(stream, config.tcp_nodelay, &config.tcp_keepalive)
+                    });
+                    // Websocket connections are closed by their own tasks, which watch the same
+                    // token; the server only stops accepting and finishes plain HTTP requests.
+                    if let Err(e) = axum::serve(listener, app.into_make_service())
+                        .with_graceful_shutdown(shutdown.cancelled_owned())
+                        .await
+                    {
                         error!("CustomBroadcaster: Server error: {}", e);
                     }
+                    info!("CustomBroadcaster: Server stopped");
                 }
                 Err(e) => {
                     error!("CustomBroadcaster: Failed to bind to address: {}", e);
                 }
             }
         });
+
+        BroadcasterHandle {
+            shutdown,
+            ingestion,
+            server,
+        }
     }
 }
 
-// --- WebSocket Handling ---
+/// The websocket endpoint and the HTTP endpoints beside it
+fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/ws", get(ws_handler))
+        .route("/validate-request", post(validate_request))
+        .route("/query/field", get(query_field))
+        .route("/query/field-range", post(query_field_range))
+        .route("/fields/{table_id}", get(get_fields))
+        .route("/ready", get(ready))
+        .route("/health", get(health))
+        .route("/connections", get(list_connections))
+        .route("/connections/{id}/disconnect", post(disconnect_connection))
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+/// Tune an accepted connection's socket as configured. Failures are logged and the connection
+/// kept with the OS defaults.
+fn set_socket_options(
+    stream: &tokio::net::TcpStream,
+    nodelay: bool,
+    keepalive: &Option<TcpKeepaliveConfig>,
+) {
+    if let Err(e) = stream.set_nodelay(nodelay) {
+        warn!("CustomBroadcaster: Failed to set TCP_NODELAY: {}", e);
+    }
+    if let Some(keepalive) = keepalive {
+        let params = socket2::TcpKeepalive::new()
+            .with_time(keepalive.idle())
+            .with_interval(keepalive.interval());
+        #[cfg(not(windows))]
+        let params = params.with_retries(keepalive.retries);
+        if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&params) {
+            warn!("CustomBroadcaster: Failed to set TCP keepalive: {}", e);
+        }
+    }
 }
 
-async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
-    let mut rx = state.tx.subscribe();
+async fn run_ingestion(state: Arc<AppState>, mut rx: mpsc::Receiver<ExecutedTransaction>) {
+    info!("CustomBroadcaster: Ingestion loop started");
+    loop {
+        let executed = tokio::select! {
+            executed = rx.recv() => executed,
+            _ = state.shutdown.cancelled() => break,
+        };
+        let Some(executed) = executed else {
+            break;
+        };
+        state.metrics.ingest_queue_depth.set(rx.len() as i64);
+        ingest(&state, executed).await;
+    }
+    state.ingestion_closed.store(true, Ordering::Relaxed);
+    info!("CustomBroadcaster: Ingestion loop ended");
+}
 
-    let mut subscriptions_pools = HashSet::new();
-    let mut subscriptions_accounts = HashSet::new();
-    let mut subscribe_all = false;
+/// Record one transaction and fan it out to the connected clients.
+async fn ingest(state: &AppState, executed: ExecutedTransaction) {
+    state.metrics.ingested.inc();
+    let seq = state.ingested_seq.fetch_add(1, Ordering::Relaxed) + 1;
+    state.last_ingest_ms.store(now_ms(), Ordering::Relaxed);
 
-    loop {
-        tokio::select! {
-            // Outbound: Send updates to client
+    let (retained, retained_bytes) = state.retention.push(seq, executed.clone());
+    state.metrics.retention_buffer_len.set(retained as i64);
+    state
+        .metrics
+        .retention_buffer_bytes
+        .set(retained_bytes as i64);
+
+    // Fast path: with nobody connected there is nothing to fan out, so skip the allocation
+    // and send entirely. The transaction is still retained for clients that resume later.
+    if state.tx.receiver_count() == 0 {
+        state.metrics.skipped_no_subscribers.inc();
+        return;
+    }
+
+    if state.overflow_strategy == OverflowStrategy::Block {
+        wait_for_slowest_receiver(state).await;
+    }
+
+    // We broadcast the Arc directly to avoid cloning the heavy data structure.
+    // Filtering happens in the client handling tasks, which share serialized payloads.
+    let ingested = Arc::new(IngestedTransaction {
+        seq,
+        outputs: executed.outputs,
+        checkpoint: executed.checkpoint,
+        payloads: PayloadCache::default(),
+    });
+    // Fails only if the last client disconnected since the check above
+    let _ = state.tx.send(ingested);
+}
+
+/// Under `OverflowStrategy::Block`, holds back the next transaction while the slowest
+/// connection still has a full broadcast buffer to get through. The ingestion channel fills up
+/// meanwhile, where `BroadcasterSender` drops rather than blocks execution.
+///
+/// Woken by connections as they receive or disconnect, and gives up on shutdown.
+async fn wait_for_slowest_receiver(state: &AppState) {
+    loop {
+        // Registered before the check, so a receive in between is not missed
+        let advanced = state.receiver_advanced.notified();
+        tokio::pin!(advanced);
+        advanced.as_mut().enable();
+        if state.tx.receiver_count() == 0 || state.tx.len() < state.broadcast_capacity {
+            return;
+        }
+        tokio::select! {
+            _ = advanced => {}
+            _ = state.shutdown.cancelled() => return,
+        }
+    }
+}
+
+// --- Per-Client Filtering ---
+
+/// Subscriptions held by a single websocket connection.
+#[derive(Default)]
+struct Subscriptions {
+    pools: HashSet<ObjectID>,
+    /// Pools subscribed with `skip_unchanged`, with the contents last sent for each
+    unchanged_skipped_pools: HashMap<ObjectID, Option<Vec<u8>>>,
+    accounts: HashSet<SuiAddress>,
+    owned_objects: HashSet<SuiAddress>,
+    /// Types of the objects whose creation is sent
+    new_object_types: HashSet<StructTag>,
+    /// Tables subscribed in `data` mode, with the windows of their fields to send; all of
+    /// their fields when empty
+    tables: HashMap<ObjectID, Vec<IndexWindow>>,
+    /// Tables subscribed in `invalidate` mode
+    table_bumps: HashSet<ObjectID>,
+    /// Individually watched fields, mapped to their table
+    fields: HashMap<ObjectID, ObjectID>,
+    /// Fields sent in a table snapshot, with the version sent, until a newer update is sent
+    snapshot_versions: HashMap<ObjectID, u64>,
+    events: Vec<EventFilter>,
+    all: bool,
+    coin_flows: bool,
+    digest_format: DigestFormat,
+    byte_format: ByteFormat,
+    address_format: AddressFormat,
+    message_format: MessageFormat,
+    /// Send every write of `skip_unchanged` pools, for ordered connections
+    ordered: bool,
+    /// Send nothing for failed transactions
+    only_successful: bool,
+    event_layouts: Option<Arc<EventLayouts>>,
+    /// Store for the input objects that coin flows are derived from, and for removed fields of
+    /// windowed tables.
+    store: Option<Arc<AuthorityPerpetualTables>>,
+    /// Key encoding of each table, for matching fields against table windows.
+    key_codecs: KeyCodecRegistry,
+    pool_diffs: bool,
+    /// Version and bytes of the last pool object sent for each pool, when sending diffs
+    pool_bases: HashMap<ObjectID, (u64, Vec<u8>)>,
+    /// Decoders of pool objects, set when the connection asked for decoded pools.
+    #[cfg(feature = "pool-decoders")]
+    pool_decoders: Option<Arc<PoolDecoderRegistry>>,
+}
+
+impl Subscriptions {
+    fn new(
+        options: &ConnectionOptions,
+        event_layouts: Option<Arc<EventLayouts>>,
+        store: Option<Arc<AuthorityPerpetualTables>>,
+    ) -> Self {
+        Self {
+            digest_format: options.digest,
+            byte_format: options.bytes,
+            address_format: options.address,
+            pool_diffs: options.pool_diffs,
+            ordered: options.ordered,
+            only_successful: options.only_successful,
+            event_layouts,
+            store,
+            ..Default::default()
+        }
+    }
+
+    /// Apply `req`, returning the reply to send for it: `Subscribed` or `Unsubscribed` once it
+    /// is in place, or an `Error` if it was rejected. Requests that are not subscriptions have
+    /// no reply here.
+    fn apply(&mut self, req: SubscriptionRequest) -> Option<StreamMessage> {
+        let kind = <&'static str>::from(&req).to_owned();
+        let subscribed = |target: Option<String>| StreamMessage::Subscribed {
+            kind: kind.clone(),
+            target,
+        };
+        let reply = match req {
+            SubscriptionRequest::SubscribePool(id) => {
+                self.pools.insert(id);
+                self.unchanged_skipped_pools.remove(&id);
+                subscribed(Some(id.to_string()))
+            }
+            SubscriptionRequest::SubscribePoolWithOptions {
+                pool_id,
+                skip_unchanged,
+            } => {
+                self.pools.insert(pool_id);
+                if skip_unchanged {
+                    self.unchanged_skipped_pools.entry(pool_id).or_default();
+                } else {
+                    self.unchanged_skipped_pools.remove(&pool_id);
+                }
+                subscribed(Some(pool_id.to_string()))
+            }
+            SubscriptionRequest::SubscribeAccount(addr) => {
+                self.accounts.insert(addr);
+                subscribed(Some(addr.to_string()))
+            }
+            SubscriptionRequest::SubscribeOwnedObjects { account } => {
+                self.owned_objects.insert(account);
+                subscribed(Some(account.to_string()))
+            }
+            SubscriptionRequest::SubscribeNewObjects { type_ } => {
+                match parse_sui_struct_tag(&type_) {
+                    Ok(tag) => {
+                        self.new_object_types.insert(tag);
+                        subscribed(Some(type_))
+                    }
+                    Err(e) => StreamMessage::Error {
+                        code: ErrorCode::InvalidRequest,
+                        message: format!("invalid object type {}: {}", type_, e),
+                    },
+                }
+            }
+            SubscriptionRequest::SubscribeTable {
+                table_id,
+                mode,
+                windows,
+                ..
+            } => {
+                match mode {
+                    TableMode::Data => {
+                        self.table_bumps.remove(&table_id);
+                        self.tables.insert(table_id, windows);
+                    }
+                    TableMode::Invalidate => {
+                        self.tables.remove(&table_id);
+                        self.table_bumps.insert(table_id);
+                    }
+                }
+                subscribed(Some(table_id.to_string()))
+            }
+            SubscriptionRequest::SubscribeField {
+                table_id,
+                key_bcs,
+                key_type,
+            } => match derive_watched_field_id(table_id, &key_bcs, &key_type) {
+                Ok(field_id) => {
+                    self.fields.insert(field_id, table_id);
+                    subscribed(Some(field_id.to_string()))
+                }
+                Err(e) => StreamMessage::Error {
+                    code: ErrorCode::InvalidRequest,
+                    message: format!("invalid field key: {}", e),
+                },
+            },
+            SubscriptionRequest::SubscribeEvents {
+                package_id,
+                module,
+                event_type,
+                field_filters,
+            } => match EventFilter::new(
+                package_id,
+                module.as_deref(),
+                event_type.as_deref(),
+                field_filters,
+            ) {
+                Ok(filter) => {
+                    let target = event_type.or_else(|| filter.scope());
+                    self.events.push(filter);
+                    subscribed(target)
+                }
+                Err(e) => StreamMessage::Error {
+                    code: ErrorCode::InvalidRequest,
+                    message: format!("invalid event filter: {}", e),
+                },
+            },
+            SubscriptionRequest::SubscribeAll => {
+                self.all = true;
+                subscribed(None)
+            }
+            SubscriptionRequest::SubscribeCoinFlows => {
+                if self.store.is_some() {
+                    self.coin_flows = true;
+                    subscribed(None)
+                } else {
+                    StreamMessage::Error {
+                        code: ErrorCode::StoreUnavailable,
+                        message: "coin flows are not available without a store".to_string(),
+                    }
+                }
+            }
+            SubscriptionRequest::UnsubscribePool { pool_id } => {
+                self.pools.remove(&pool_id);
+                self.unchanged_skipped_pools.remove(&pool_id);
+                self.pool_bases.remove(&pool_id);
+                StreamMessage::Unsubscribed {
+                    kind,
+                    target: Some(pool_id.to_string()),
+                }
+            }
+            SubscriptionRequest::UnsubscribeAccount { account } => {
+                self.accounts.remove(&account);
+                StreamMessage::Unsubscribed {
+                    kind,
+                    target: Some(account.to_string()),
+                }
+            }
+            SubscriptionRequest::UnsubscribeAll => {
+                self.all = false;
+                StreamMessage::Unsubscribed { kind, target: None }
+            }
+            // Not subscriptions; answered directly by the connection task
+            SubscriptionRequest::SetFormat { .. }
+            | SubscriptionRequest::Resume { .. }
+            | SubscriptionRequest::GetSubscriptions
+            | SubscriptionRequest::GetStats
+            | SubscriptionRequest::QueryFieldRange { .. }
+            | SubscriptionRequest::QueryCredit { .. } => return None,
+        };
+        Some(reply)
+    }
+
+    /// No subscriptions, rendering messages like this connection.
+    fn empty_like(&self) -> Self {
+        Self {
+            digest_format: self.digest_format,
+            byte_format: self.byte_format,
+            address_format: self.address_format,
+            message_format: self.message_format,
+            only_successful: self.only_successful,
+            event_layouts: self.event_layouts.clone(),
+            store: self.store.clone(),
+            key_codecs: self.key_codecs.clone(),
+            #[cfg(feature = "pool-decoders")]
+            pool_decoders: self.pool_decoders.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Messages for the retained transactions matching `req` alone, oldest first, tagged as
+    /// replayed.
+    fn replay_for(
+        &self,
+        req: &SubscriptionRequest,
+        retention: &RetentionBuffer,
+    ) -> Vec<StreamMessage> {
+        let mut only_req = self.empty_like();
+        only_req.apply(req.clone());
+        retention
+            .since(0)
+            .into_iter()
+            .flat_map(|(server_seq, executed)| {
+                only_req
+                    .messages_for(&executed.outputs, executed.checkpoint)
+                    .into_iter()
+                    .map(move |message| StreamMessage::Replayed {
+                        server_seq,
+                        message: Box::new(message),
+                    })
+            })
+            .collect()
+    }
+
+    /// The connection's subscriptions, sorted so that replies are stable.
+    fn snapshot(&self) -> StreamMessage {
+        fn sorted<T: Ord + Copy>(set: &HashSet<T>) -> Vec<T> {
+            let mut items: Vec<T> = set.iter().copied().collect();
+            items.sort();
+            items
+        }
+        StreamMessage::Subscriptions {
+            pools: sorted(&self.pools),
+            accounts: sorted(&self.accounts),
+            owned_objects: sorted(&self.owned_objects),
+            new_object_types: {
+                let mut types: Vec<String> = self
+                    .new_object_types
+                    .iter()
+                    .map(|tag| tag.to_canonical_string(true))
+                    .collect();
+                types.sort();
+                types
+            },
+            tables: {
+                let mut tables: Vec<ObjectID> = self.tables.keys().copied().collect();
+                tables.sort();
+                tables
+            },
+            table_windows: self
+                .tables
+                .iter()
+                .filter(|(_, windows)| !windows.is_empty())
+                .map(|(table_id, windows)| (*table_id, windows.clone()))
+                .collect(),
+            table_bumps: sorted(&self.table_bumps),
+            fields: {
+                let mut fields: Vec<ObjectID> = self.fields.keys().copied().collect();
+                fields.sort();
+                fields
+            },
+            event_types: self
+                .events
+                .iter()
+                .filter_map(|filter| filter.event_type.as_ref())
+                .map(|event_type| event_type.to_canonical_string(true))
+                .collect(),
+            event_packages: self.events.iter().filter_map(EventFilter::scope).collect(),
+            all: self.all,
+            coin_flows: self.coin_flows,
+        }
+    }
+
+    /// Messages this connection should receive for one transaction, in emission order.
+    fn messages_for(
+        &self,
+        outputs: &TransactionOutputs,
+        checkpoint: Option<u64>,
+    ) -> Vec<StreamMessage> {
+        self.keyed_messages_for(outputs, checkpoint)
+            .into_iter()
+            .map(|(_, msg)| msg)
+            .collect()
+    }
+
+    /// Serialized payloads for one fanned-out transaction, with the variant of their message,
+    /// reusing those already produced for other connections.
+    fn payloads_for(&mut self, ingested: &IngestedTransaction) -> Vec<(&'static str, Message)> {
+        let mut payloads = Vec::new();
+        for (key, msg) in self.keyed_messages_for(&ingested.outputs, ingested.checkpoint) {
+            if let Some(pool_id) = key.pool_id()
+                && self.is_unchanged_pool(&ingested.outputs, pool_id)
+            {
+                continue;
+            }
+            if let MessageKey::TableField(field_id) = key
+                && self.is_in_snapshot(field_id, &msg, &ingested.outputs)
+            {
+                continue;
+            }
+            // Should the pool be unwrapped, it is sent in full rather than skipped or diffed
+            if let MessageKey::PoolDeleted(pool_id) = key {
+                if let Some(last_sent) = self.unchanged_skipped_pools.get_mut(&pool_id) {
+                    *last_sent = None;
+                }
+                self.pool_bases.remove(&pool_id);
+            }
+            let message_type = <&'static str>::from(&msg);
+            let payload = match key {
+                // Diffs depend on what this connection was sent before, so are never shared
+                MessageKey::Pool(_) if self.pool_diffs => {
+                    let msg = self.diff_pool_update(msg);
+                    serialize_message(&msg, self.message_format, self.address_format)
+                }
+                key => ingested.payloads.get_or_serialize(
+                    (
+                        self.message_format,
+                        self.digest_format,
+                        self.byte_format,
+                        self.address_format,
+                        key,
+                    ),
+                    &msg,
+                ),
+            };
+            payloads.extend(payload.map(|payload| (message_type, payload)));
+        }
+        payloads
+    }
+
+    /// Whether a change to `field_id` was already covered by the table snapshot sent on this
+    /// connection. Removals are compared by the version of the transaction removing the field.
+    fn is_in_snapshot(
+        &mut self,
+        field_id: ObjectID,
+        msg: &StreamMessage,
+        outputs: &TransactionOutputs,
+    ) -> bool {
+        let Some(&snapshot_version) = self.snapshot_versions.get(&field_id) else {
+            return false;
+        };
+        let version = match msg {
+            StreamMessage::TableFieldUpdate { version, .. } => *version,
+            _ => outputs.effects.lamport_version().value(),
+        };
+        if version <= snapshot_version {
+            return true;
+        }
+        // Later changes are newer still
+        self.snapshot_versions.remove(&field_id);
+        false
+    }
+
+    /// Whether `pool_id` is subscribed with `skip_unchanged` and was written with the contents
+    /// last sent for it. Otherwise, its contents are recorded as sent. Never on ordered
+    /// connections.
+    fn is_unchanged_pool(&mut self, outputs: &TransactionOutputs, pool_id: ObjectID) -> bool {
+        if self.ordered {
+            return false;
+        }
+        let Some(last_sent) = self.unchanged_skipped_pools.get_mut(&pool_id) else {
+            return false;
+        };
+        let Some(contents) = outputs
+            .written
+            .get(&pool_id)
+            .and_then(|object| object.data.try_as_move())
+            .map(|object| object.contents())
+        else {
+            return false;
+        };
+        if last_sent.as_deref() == Some(contents) {
+            return true;
+        }
+        *last_sent = Some(contents.to_vec());
+        false
+    }
+
+    /// Replace the object of a `PoolUpdate` with its diff from the last one sent for the same
+    /// pool, when there is one and it is smaller, and remember the object as the next base.
+    fn diff_pool_update(&mut self, msg: StreamMessage) -> StreamMessage {
+        let StreamMessage::PoolUpdate {
+            pool_id,
+            digest,
+            checkpoint,
+            version,
+            previous_version,
+            change_kind,
+            object: Some(object),
+            diff: None,
+        } = msg
+        else {
+            return msg;
+        };
+        let diff = self
+            .pool_bases
+            .get(&pool_id)
+            .and_then(|(base_version, base)| {
+                PoolDiff::new(*base_version, base, version, &object, self.byte_format)
+            });
+        self.pool_bases.insert(pool_id, (version, object.to_vec()));
+        let (object, diff) = match diff {
+            Some(diff) => (None, Some(diff)),
+            None => (Some(object), None),
+        };
+        StreamMessage::PoolUpdate {
+            pool_id,
+            digest,
+            checkpoint,
+            version,
+            previous_version,
+            change_kind,
+            object,
+            diff,
+        }
+    }
+
+    fn keyed_messages_for(
+        &self,
+        outputs: &TransactionOutputs,
+        checkpoint: Option<u64>,
+    ) -> Vec<(MessageKey, StreamMessage)> {
+        if self.only_successful && !outputs.effects.status().is_ok() {
+            return Vec::new();
+        }
+        let digest = self.digest_format.render(outputs.transaction.digest());
+        let sender = outputs.transaction.sender_address();
+        let mut messages = Vec::new();
+
+        // 1. Firehose / SubscribeAll: sender activity and every event (can be heavy).
+        // Otherwise only the events matching a SubscribeEvents filter.
+        if self.all {
+            messages.push((
+                MessageKey::SenderActivity,
+                StreamMessage::AccountActivity {
+                    account: sender,
+                    digest: digest.clone(),
+                    kind: "Transaction".to_string(),
+                    status: ExecutionOutcome::of(&outputs.effects),
+                    checkpoint,
+                },
+            ));
+        }
+        if self.all || !self.events.is_empty() {
+            for (i, event) in outputs.events.data.iter().enumerate() {
+                if !self.all
+                    && !self
+                        .events
+                        .iter()
+                        .any(|filter| filter.matches(event, self.event_layouts.as_deref()))
+                {
+                    continue;
+                }
+                messages.push((
+                    MessageKey::Event(i),
+                    StreamMessage::Event {
+                        package_id: event.package_id,
+                        transaction_module: event.transaction_module.to_string(),
+                        sender: event.sender,
+                        type_: event.type_.to_string(),
+                        contents: self.byte_format.encode(event.contents.clone()),
+                        digest: digest.clone(),
+                        checkpoint,
+                    },
+                ));
+            }
+        }
+
+        // 2. Pool Updates (Written Objects) and deletions
+        for (id, object) in &outputs.written {
+            if self.pools.contains(id) {
+                #[cfg(feature = "pool-decoders")]
+                if let Some((pool_type, fields)) = self
+                    .pool_decoders
+                    .as_ref()
+                    .and_then(|decoders| decoders.decode(object))
+                {
+                    messages.push((
+                        MessageKey::PoolState(*id),
+                        StreamMessage::PoolState {
+                            pool_id: *id,
+                            digest: digest.clone(),
+                            pool_type,
+                            fields,
+                        },
+                    ));
+                    continue;
+                }
+                messages.push((
+                    MessageKey::Pool(*id),
+                    StreamMessage::PoolUpdate {
+                        pool_id: *id,
+                        digest: digest.clone(),
+                        checkpoint,
+                        version: object.version().value(),
+                        previous_version: outputs
+                            .effects
+                            .modified_at_versions()
+                            .into_iter()
+                            .find(|(modified, _)| modified == id)
+                            .map(|(_, version)| version.value()),
+                        change_kind: ChangeKind::of(&outputs.effects, *id, &object.owner),
+                        object: object
+                            .data
+                            .try_as_move()
+                            .map(|o| self.byte_format.encode(o.contents().to_vec())),
+                        diff: None,
+                    },
+                ));
+            }
+        }
+        let deleted = outputs.deleted.iter().map(|key| (key, false));
+        let wrapped = outputs.wrapped.iter().map(|key| (key, true));
+        for (ObjectKey(id, _), wrapped) in deleted.chain(wrapped) {
+            if self.pools.contains(id) {
+                messages.push((
+                    MessageKey::PoolDeleted(*id),
+                    StreamMessage::PoolDeleted {
+                        pool_id: *id,
+                        digest: digest.clone(),
+                        wrapped,
+                    },
+                ));
+            }
+        }
+
+        // 3. Account Updates (Sender)
+        if self.accounts.contains(&sender) {
+            messages.push((
+                MessageKey::SenderActivity,
+                StreamMessage::AccountActivity {
+                    account: sender,
+                    digest: digest.clone(),
+                    kind: "Transaction".to_string(),
+                    status: ExecutionOutcome::of(&outputs.effects),
+                    checkpoint,
+                },
+            ));
+        }
+
+        // 4. Owned objects, matched on the owner before and after the transaction
+        if !self.owned_objects.is_empty() {
+            self.owned_object_messages(outputs, &digest, &mut messages);
+        }
+
+        // 5. Objects created with a subscribed type, whoever owns them
+        if !self.new_object_types.is_empty() {
+            self.new_object_messages(outputs, &digest, &mut messages);
+        }
+
+        // 6. Table field deltas, matched on the parent table or the field itself
+        if !self.tables.is_empty() || !self.fields.is_empty() {
+            self.table_field_messages(outputs, &digest, &mut messages);
+        }
+        if !self.table_bumps.is_empty() {
+            self.table_bump_messages(outputs, &digest, &mut messages);
+        }
+
+        // 7. Coin flows, derived from the coin balances consumed and produced
+        if self.coin_flows
+            && let Some(store) = &self.store
+        {
+            let flows = coin_flows::coin_flows(store, outputs);
+            if !flows.is_empty() {
+                messages.push((
+                    MessageKey::CoinFlows,
+                    StreamMessage::CoinFlows { digest, flows },
+                ));
+            }
+        }
+
+        messages
+    }
+
+    fn owned_object_messages(
+        &self,
+        outputs: &TransactionOutputs,
+        digest: &str,
+        messages: &mut Vec<(MessageKey, StreamMessage)>,
+    ) {
+        // Owners of the input versions of every modified object, so that objects leaving an
+        // account (transferred, deleted or wrapped) can be attributed to it.
+        let prior_owners: HashMap<ObjectID, SuiAddress> = outputs
+            .effects
+            .old_object_metadata()
+            .into_iter()
+            .filter_map(|((id, _, _), owner)| address_owner(&owner).map(|addr| (id, addr)))
+            .collect();
+        let unwrapped: HashSet<ObjectID> = outputs
+            .effects
+            .unwrapped()
+            .into_iter()
+            .map(|((id, _, _), _)| id)
+            .collect();
+        let created: HashSet<ObjectID> = outputs
+            .effects
+            .created()
+            .into_iter()
+            .map(|((id, _, _), _)| id)
+            .collect();
+
+        for (id, object) in &outputs.written {
+            let new_owner = address_owner(&object.owner);
+            let old_owner = prior_owners
+                .get(id)
+                .copied()
+                .filter(|o| Some(*o) != new_owner);
+            if let Some(account) = new_owner
+                && unwrapped.contains(id)
+                && self.owned_objects.contains(&account)
+            {
+                messages.push((
+                    MessageKey::ObjectUnwrapped(account, *id),
+                    StreamMessage::ObjectUnwrapped {
+                        account,
+                        object_id: *id,
+                        digest: digest.to_string(),
+                    },
+                ));
+            }
+            for account in [new_owner, old_owner].into_iter().flatten() {
+                if self.owned_objects.contains(&account) {
+                    messages.push((
+                        MessageKey::ObjectUpdate(account, *id),
+                        StreamMessage::ObjectUpdate {
+                            account,
+                            object_id: *id,
+                            version: object.version().value(),
+                            owner: object.owner.clone(),
+                            object_type: object.type_().map(|t| t.to_string()),
+                            digest: digest.to_string(),
+                            object: object
+                                .data
+                                .try_as_move()
+                                .map(|o| self.byte_format.encode(o.contents().to_vec())),
+                            created: created.contains(id),
+                        },
+                    ));
+                }
+            }
+        }
+
+        for ObjectKey(id, _) in &outputs.deleted {
+            if let Some(account) = prior_owners.get(id) {
+                if self.owned_objects.contains(account) {
+                    messages.push((
+                        MessageKey::ObjectDeleted(*account, *id),
+                        StreamMessage::ObjectDeleted {
+                            account: *account,
+                            object_id: *id,
+                            digest: digest.to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+        for ObjectKey(id, _) in &outputs.wrapped {
+            if let Some(account) = prior_owners.get(id) {
+                if self.owned_objects.contains(account) {
+                    messages.push((
+                        MessageKey::ObjectWrapped(*account, *id),
+                        StreamMessage::ObjectWrapped {
+                            account: *account,
+                            object_id: *id,
+                            digest: digest.to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    fn new_object_messages(
+        &self,
+        outputs: &TransactionOutputs,
+        digest: &str,
+        messages: &mut Vec<(MessageKey, StreamMessage)>,
+    ) {
+        let sender = outputs.transaction.sender_address();
+        // Only the effects tell creations apart from writes to existing objects
+        for ((id, _, _), _) in outputs.effects.created() {
+            let Some(object) = outputs.written.get(&id) else {
+                continue;
+            };
+            if !object
+                .struct_tag()
+                .is_some_and(|tag| self.new_object_types.contains(&tag))
+            {
+                continue;
+            }
+            messages.push((
+                MessageKey::NewObject(id),
+                StreamMessage::ObjectUpdate {
+                    account: sender,
+                    object_id: id,
+                    version: object.version().value(),
+                    owner: object.owner.clone(),
+                    object_type: object.type_().map(|t| t.to_string()),
+                    digest: digest.to_string(),
+                    object: object
+                        .data
+                        .try_as_move()
+                        .map(|o| self.byte_format.encode(o.contents().to_vec())),
+                    created: true,
+                },
+            ));
+        }
+    }
+
+    /// Whether written `field` of `table_id` is sent, as a watched field or by its table's
+    /// windows
+    fn watches_field(&self, table_id: ObjectID, field: &Object) -> bool {
+        if self.fields.get(&field.id()) == Some(&table_id) {
+            return true;
+        }
+        match self.tables.get(&table_id) {
+            Some(windows) => self.in_windows(table_id, windows, field),
+            None => false,
+        }
+    }
+
+    /// Same as [`Self::watches_field`] for a field removed at `version`, which is only in the
+    /// store. Without a store, removals from windowed tables are sent regardless of the key.
+    fn watches_removed_field(&self, table_id: ObjectID, id: ObjectID, version: u64) -> bool {
+        if self.fields.get(&id) == Some(&table_id) {
+            return true;
+        }
+        let Some(windows) = self.tables.get(&table_id) else {
+            return false;
+        };
+        if windows.is_empty() {
+            return true;
+        }
+        let Some(store) = &self.store else {
+            return true;
+        };
+        match store.find_object_lt_or_eq_version(id, SequenceNumber::from_u64(version)) {
+            Ok(Some(field)) => self.in_windows(table_id, windows, &field),
+            _ => true,
+        }
+    }
+
+    fn in_windows(&self, table_id: ObjectID, windows: &[IndexWindow], field: &Object) -> bool {
+        if windows.is_empty() {
+            return true;
+        }
+        let codec = self.key_codecs.codec_for(&table_id);
+        field_index(field, &*codec).is_some_and(|index| windows.iter().any(|w| w.contains(index)))
+    }
+
+    fn table_field_messages(
+        &self,
+        outputs: &TransactionOutputs,
+        digest: &str,
+        messages: &mut Vec<(MessageKey, StreamMessage)>,
+    ) {
+        for (id, object) in &outputs.written {
+            let Owner::ObjectOwner(parent) = object.owner else {
+                continue;
+            };
+            let table_id = ObjectID::from(parent);
+            if !self.watches_field(table_id, object) {
+                continue;
+            }
+            if let Some(move_obj) = object.data.try_as_move() {
+                messages.push((
+                    MessageKey::TableField(*id),
+                    StreamMessage::TableFieldUpdate {
+                        table_id,
+                        field_id: *id,
+                        version: object.version().value(),
+                        digest: digest.to_string(),
+                        object: self.byte_format.encode(move_obj.contents().to_vec()),
+                    },
+                ));
+            }
+        }
+
+        // Removed fields are no longer in the store, so they are attributed to a table through
+        // the owner of their input version.
+        let removed: HashSet<&ObjectID> = outputs
+            .deleted
+            .iter()
+            .chain(&outputs.wrapped)
+            .map(|ObjectKey(id, _)| id)
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+        for ((id, version, _), owner) in outputs.effects.old_object_metadata() {
+            let Owner::ObjectOwner(parent) = owner else {
+                continue;
+            };
+            let table_id = ObjectID::from(parent);
+            if removed.contains(&id) && self.watches_removed_field(table_id, id, version.value()) {
+                messages.push((
+                    MessageKey::TableField(id),
+                    StreamMessage::TableFieldRemoved {
+                        table_id,
+                        field_id: id,
+                        digest: digest.to_string(),
+                    },
+                ));
+            }
+        }
+    }
+
+    fn table_bump_messages(
+        &self,
+        outputs: &TransactionOutputs,
+        digest: &str,
+        messages: &mut Vec<(MessageKey, StreamMessage)>,
+    ) {
+        let parent_of = |owner: &Owner| match owner {
+            Owner::ObjectOwner(parent) => Some(ObjectID::from(*parent)),
+            _ => None,
+        };
+        // The table objects and field parents among the written objects, and among the input
+        // versions of the removed ones
+        let removed: HashSet<&ObjectID> = outputs
+            .deleted
+            .iter()
+            .chain(&outputs.wrapped)
+            .map(|ObjectKey(id, _)| id)
+            .collect();
+        let mut bumped: BTreeSet<ObjectID> = BTreeSet::new();
+        for (id, object) in &outputs.written {
+            bumped.extend([Some(*id), parent_of(&object.owner)].into_iter().flatten());
+        }
+        if !removed.is_empty() {
+            for ((id, _, _), owner) in outputs.effects.old_object_metadata() {
+                if removed.contains(&id) {
+                    bumped.extend([Some(id), parent_of(&owner)].into_iter().flatten());
+                }
+            }
+        }
+
+        let new_version = outputs.effects.lamport_version().value();
+        for table_id in bumped {
+            if !self.table_bumps.contains(&table_id) {
+                continue;
+            }
+            messages.push((
+                MessageKey::TableVersionBump(table_id),
+                StreamMessage::TableVersionBump {
+                    table_id,
+                    new_version,
+                    digest: digest.to_string(),
+                },
+            ));
+        }
+    }
+}
+
+/// Id of the dynamic field of `table_id` with the given key, as watched by `SubscribeField`.
+fn derive_watched_field_id(
+    table_id: ObjectID,
+    key_bcs: &[u8],
+    key_type: &str,
+) -> anyhow::Result<ObjectID> {
+    let key_type = parse_sui_type_tag(key_type)?;
+    Ok(derive_dynamic_field_id(table_id, &key_type, key_bcs)?)
+}
+
+/// Error message for a request frame that is not a valid `SubscriptionRequest`: the parse
+/// error, then the frame, cut to `ECHOED_REQUEST_CHARS` characters
+fn invalid_request_message(text: &str, error: &serde_json::Error) -> String {
+    let mut echoed: String = text.chars().take(ECHOED_REQUEST_CHARS).collect();
+    if echoed.len() < text.len() {
+        echoed.push('…');
+    }
+    format!("invalid request: {}: {}", error, echoed)
+}
+
+/// The `key_type` of a field query, which must have a built-in key encoding
+fn parse_key_type(key_type: &str) -> Result<TypeTag, String> {
+    let type_tag =
+        parse_sui_type_tag(key_type).map_err(|e| format!("invalid key_type {key_type}: {e}"))?;
+    if codec_for_type(&type_tag).is_none() {
+        return Err(format!("no key encoding for key_type {key_type}"));
+    }
+    Ok(type_tag)
+}
+
+/// The encoding of `key_type` if given, otherwise the one registered for `table_id`
+fn key_codec(
+    key_codecs: &KeyCodecRegistry,
+    table_id: &ObjectID,
+    key_type: Option<&TypeTag>,
+) -> Arc<dyn KeyCodec> {
+    key_type
+        .and_then(codec_for_type)
+        .unwrap_or_else(|| key_codecs.codec_for(table_id))
+}
+
+/// The address owning an object, for both fastpath and consensus address-owned objects.
+fn address_owner(owner: &Owner) -> Option<SuiAddress> {
+    match owner {
+        Owner::AddressOwner(addr) | Owner::ConsensusAddressOwner { owner: addr, .. } => Some(*addr),
+        _ => None,
+    }
+}
+
+// --- Request Validation ---
+
+/// Reply of `POST /validate-request`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ValidationReply {
+    /// The body is a valid request; `parsed` is it as the server understood it.
+    Valid {
+        ok: bool,
+        parsed: SubscriptionRequest,
+    },
+    /// The body is not a valid request. `path` locates the offending field (e.g.
+    /// `QueryFieldRange.table_id`), and is empty when the error is not within a field.
+    Invalid {
+        ok: bool,
+        error: String,
+        path: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+/// Parse the body as a `SubscriptionRequest` without touching any connection, so that client
+/// developers can check their requests against the server's schema.
+async fn validate_request(body: String) -> Json<ValidationReply> {
+    Json(validate_request_body(&body))
+}
+
+fn validate_request_body(body: &str) -> ValidationReply {
+    let mut deserializer = serde_json::Deserializer::from_str(body);
+    let parsed = serde_path_to_error::deserialize::<_, SubscriptionRequest>(&mut deserializer)
+        .map_err(|e| (e.path().to_string(), e.into_inner()))
+        .and_then(|parsed| {
+            // Reject trailing characters, like `serde_json::from_str` does
+            deserializer.end().map_err(|e| (String::new(), e))?;
+            Ok(parsed)
+        });
+    match parsed {
+        Ok(parsed) => match parsed.check_list_lengths() {
+            Ok(()) => ValidationReply::Valid { ok: true, parsed },
+            Err(e) => ValidationReply::Invalid {
+                ok: false,
+                error: e.to_string(),
+                path: e.path.to_string(),
+                line: 0,
+                column: 0,
+            },
+        },
+        Err((path, e)) => ValidationReply::Invalid {
+            ok: false,
+            error: e.to_string(),
+            // The root of the document is rendered as `.`
+            path: if path == "." { String::new() } else { path },
+            line: e.line(),
+            column: e.column(),
+        },
+    }
+}
+
+// --- Field Queries over HTTP ---
+
+/// Parameters of `GET /query/field`.
+#[derive(Debug, Deserialize)]
+struct FieldValueQuery {
+    table: ObjectID,
+    index: u64,
+    /// Move type of the table's keys, e.g. `u64` or `0x1::string::String`; the table's
+    /// registered key encoding if omitted.
+    key_type: Option<String>,
+    /// Upper bound on the table's version, like `QueryFieldRange.parent_version`.
+    version: Option<u64>,
+}
+
+/// Reply of `GET /query/field`: the field's key and value, decoded with their Move layouts.
+#[derive(Debug, Serialize)]
+struct FieldValueReply {
+    table_id: ObjectID,
+    index: u64,
+    field_id: ObjectID,
+    version: u64,
+    /// The field object's type, `0x2::dynamic_field::Field<K, V>`
+    field_type: String,
+    name: serde_json::Value,
+    value: serde_json::Value,
+}
+
+/// `GET /query/field?table=<id>&index=<n>[&key_type=<type>][&version=<v>]`: one dynamic field
+/// of `table`, decoded to JSON. Answers 404 when the field does not exist and 400 when
+/// `key_type` is not a type with a built-in key encoding.
+async fn query_field(
+    Query(query): Query<FieldValueQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let codec = match query.key_type.as_deref().map(parse_key_type).transpose() {
+        Ok(key_type) => key_codec(&state.key_codecs, &query.table, key_type.as_ref()),
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    if !state.may_query(&query.table) {
+        return (StatusCode::FORBIDDEN, not_queryable_message(query.table)).into_response();
+    }
+    let (Some(store), Some(layouts)) = (state.store.clone(), state.event_layouts.clone()) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "field queries are not available on this server",
+        )
+            .into_response();
+    };
+    let parent_version = query
+        .version
+        .map(SequenceNumber::from_u64)
+        .unwrap_or(SequenceNumber::MAX);
+
+    // Reads the store, and the packages of the field's types when decoding
+    let read = tokio::task::spawn_blocking(move || {
+        let field = get_field_value(&store, query.table, query.index, parent_version, &*codec)?;
+        // A package is not a field, so it is reported as missing
+        Ok::<_, SuiError>(field.and_then(|field| {
+            let move_obj = field.data.try_as_move()?;
+            let tag: StructTag = move_obj.type_().clone().into();
+            let decoded = layouts.decode_struct(&tag, move_obj.contents());
+            Some((field.id(), field.version(), tag, decoded))
+        }))
+    })
+    .await;
+
+    match read {
+        Ok(Ok(Some((field_id, version, tag, decoded)))) => {
+            let Some(mut decoded) = decoded else {
+                let message = format!("failed to decode field {field_id} of type {tag}");
+                return (StatusCode::INTERNAL_SERVER_ERROR, message).into_response();
+            };
+            Json(FieldValueReply {
+                table_id: query.table,
+                index: query.index,
+                field_id,
+                version: version.value(),
+                field_type: tag.to_canonical_string(true),
+                name: decoded["name"].take(),
+                value: decoded["value"].take(),
+            })
+            .into_response()
+        }
+        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Body of `POST /query/field-range`, the parameters of `QueryFieldRange`.
+#[derive(Debug, Deserialize)]
+struct FieldRangeRequest {
+    table_id: ObjectID,
+    current_index: u64,
+    range: u64,
+    parent_version: Option<u64>,
+    #[serde(default)]
+    windows: Vec<IndexWindow>,
+    /// Encoding of the fields' bytes, as the `bytes` connection option.
+    #[serde(default)]
+    bytes: ByteFormat,
+    #[serde(default)]
+    key_type: Option<String>,
+}
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// `POST /query/field-range`: the fields of `table_id` in `current_index ± range` and further
+/// `windows`, answered with the `FieldData`, `Error` and closing `QueryComplete` messages of a
+/// websocket `QueryFieldRange`.
+///
+/// The messages are returned as a JSON array, or with `Accept: application/x-ndjson` streamed
+/// one per line as the fields are read, so that neither side holds a large range in memory.
+async fn query_field_range(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<FieldRangeRequest>,
+) -> Response {
+    if request.windows.len() > MAX_INDEX_WINDOWS {
+        let message = format!("at most {} windows may be given", MAX_INDEX_WINDOWS);
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    let key_type = match request.key_type.as_deref().map(parse_key_type).transpose() {
+        Ok(key_type) => key_type,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let first = IndexWindow {
+        center: request.current_index,
+        range: request.range,
+    };
+    let ranges = state
+        .check_ranges(&[first])
+        .and_then(|()| state.check_ranges(&request.windows));
+    if let Err(message) = ranges {
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    if !state.may_query(&request.table_id) {
+        let message = not_queryable_message(request.table_id);
+        return (StatusCode::FORBIDDEN, message).into_response();
+    }
+    let Some(store) = state.store.clone() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "field queries are not available on this server",
+        )
+            .into_response();
+    };
+
+    let codec = key_codec(&state.key_codecs, &request.table_id, key_type.as_ref());
+    let messages = field_range_messages(store, codec, request);
+    let ndjson = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
+    if ndjson {
+        let lines = messages.map(|msg| {
+            serde_json::to_string(&msg).map(|mut line| {
+                line.push('\n');
+                line
+            })
+        });
+        return ([(CONTENT_TYPE, NDJSON)], Body::from_stream(lines)).into_response();
+    }
+    Json(messages.collect::<Vec<_>>().await).into_response()
+}
+
+/// The messages answering `request`, produced as its fields are read
+fn field_range_messages(
+    store: Arc<AuthorityPerpetualTables>,
+    codec: Arc<dyn KeyCodec>,
+    request: FieldRangeRequest,
+) -> impl Stream<Item = StreamMessage> {
+    let table_id = request.table_id;
+    let byte_format = request.bytes;
+    let first = IndexWindow {
+        center: request.current_index,
+        range: request.range,
+    };
+    let windows: Vec<IndexWindow> = std::iter::once(first).chain(request.windows).collect();
+    let parent_version = request
+        .parent_version
+        .map(SequenceNumber::from_u64)
+        .unwrap_or(SequenceNumber::MAX);
+    let fields =
+        query_field_data_stream_windows(store, table_id, &windows, parent_version, codec).boxed();
+
+    // Carries the field stream with the counts for `QueryComplete`, until it has been sent
+    futures::stream::unfold(Some((fields, 0, 0)), move |state| async move {
+        let (mut fields, mut found, mut errors) = state?;
+        let msg = match fields.next().await {
+            Some(Ok(field)) => {
+                found += 1;
+                StreamMessage::FieldData {
+                    table_id,
+                    index: field.index,
+                    field_id: field.field_id,
+                    version: field.version.value(),
+                    bcs_bytes: byte_format.encode(field.bcs_bytes),
+                    request_id: None,
+                }
+            }
+            Some(Err(e)) => {
+                errors += 1;
+                StreamMessage::Error {
+                    code: ErrorCode::QueryFailed,
+                    message: format!("failed to read field of table {}: {}", table_id, e),
+                }
+            }
+            None => {
+                let msg = StreamMessage::QueryComplete {
+                    table_id,
+                    fields: found,
+                    errors,
+                    request_id: None,
+                };
+                return Some((msg, None));
+            }
+        };
+        Some((msg, Some((fields, found, errors))))
+    })
+}
+
+/// Parameters of `GET /fields/{table_id}`.
+#[derive(Debug, Deserialize)]
+struct FieldsQuery {
+    index: u64,
+    range: u64,
+    /// Upper bound on the table's version, like `QueryFieldRange.parent_version`.
+    version: Option<u64>,
+    #[serde(default)]
+    key_type: Option<String>,
+    /// Encoding of the fields' bytes, as the `bytes` connection option.
+    #[serde(default)]
+    bytes: ByteFormat,
+}
+
+/// Reply of `GET /fields/{table_id}`.
+#[derive(Debug, Serialize)]
+struct FieldsReply {
+    table_id: ObjectID,
+    /// The fields found, by ascending index
+    fields: Vec<FieldEntry>,
+    /// The indices that could not be read, which the scan went past
+    errors: Vec<FieldReadError>,
+}
+
+/// A field in a `GET /fields/{table_id}` reply, as in a `FieldData` message.
+#[derive(Debug, Serialize)]
+struct FieldEntry {
+    index: u64,
+    field_id: ObjectID,
+    version: u64,
+    bcs_bytes: EncodedBytes,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldReadError {
+    index: u64,
+    error: String,
+}
+
+/// `GET /fields/{table_id}?index=<n>&range=<r>[&version=<v>][&key_type=<type>][&bytes=<fmt>]`:
+/// the fields of `table_id` in `index ± range` as a single JSON document, for clients that do
+/// not keep a websocket open. Ranges and key types are checked as for `QueryFieldRange`, and
+/// refused with 400. The range is read in one batch (see `query_field_data_range_batched`), so
+/// a failed read fails the request with 500.
+async fn get_fields(
+    Path(table_id): Path<ObjectID>,
+    Query(query): Query<FieldsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let key_type = match query.key_type.as_deref().map(parse_key_type).transpose() {
+        Ok(key_type) => key_type,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let window = IndexWindow {
+        center: query.index,
+        range: query.range,
+    };
+    if let Err(message) = state.check_ranges(&[window]) {
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    if !state.may_query(&table_id) {
+        return (StatusCode::FORBIDDEN, not_queryable_message(table_id)).into_response();
+    }
+    let Some(store) = state.store.clone() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "field queries are not available on this server",
+        )
+            .into_response();
+    };
+    let codec = key_codec(&state.key_codecs, &table_id, key_type.as_ref());
+    let parent_version = query
+        .version
+        .map(SequenceNumber::from_u64)
+        .unwrap_or(SequenceNumber::MAX);
+
+    let read = tokio::task::spawn_blocking(move || {
+        query_field_data_range_batched(
+            &store,
+            table_id,
+            query.index,
+            query.range,
+            parent_version,
+            &*codec,
+        )
+        .map(OrderedRangeResult::from)
+    })
+    .await;
+    match read {
+        Ok(Ok(result)) => Json(FieldsReply {
+            table_id,
+            fields: result
+                .fields
+                .into_iter()
+                .map(|field| FieldEntry {
+                    index: field.index,
+                    field_id: field.field_id,
+                    version: field.version.value(),
+                    bcs_bytes: query.bytes.encode(field.bcs_bytes),
+                })
+                .collect(),
+            errors: result
+                .errors
+                .into_iter()
+                .map(|(index, e)| FieldReadError {
+                    index,
+                    error: e.to_string(),
+                })
+                .collect(),
+        })
+        .into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+// --- Readiness ---
+
+/// Reply of `GET /ready`.
+#[derive(Debug, Serialize)]
+struct ReadinessReply {
+    ready: bool,
+    /// Whether the execution pipeline still feeds the ingestion channel
+    ingesting: bool,
+    /// Whether a store backs field queries
+    store: bool,
+    /// When the last transaction was ingested, in ms since the epoch; `None` before the first.
+    /// Compare with the current time to detect a stalled pipeline.
+    last_ingest_ms: Option<u64>,
+}
+
+/// `GET /ready`: whether the broadcaster can receive updates and, if `require-store` is set,
+/// serve field queries. Answers 503 when it cannot, so orchestrators route traffic elsewhere.
+async fn ready(State(state): State<Arc<AppState>>) -> Response {
+    let ingesting = !state.ingestion_closed.load(Ordering::Relaxed);
+    let store = state.store.is_some();
+    let reply = ReadinessReply {
+        ready: ingesting && (store || !state.require_store),
+        ingesting,
+        store,
+        last_ingest_ms: Some(state.last_ingest_ms.load(Ordering::Relaxed)).filter(|ms| *ms > 0),
+    };
+    let status = if reply.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(reply)).into_response()
+}
+
+/// Reply of `GET /health`.
+#[derive(Debug, Serialize)]
+struct HealthReply {
+    /// Whether a store backs field queries
+    store: bool,
+    /// Whether the ingestion loop is still running
+    ingesting: bool,
+}
+
+/// `GET /health`: answers 200 as long as the server is up, for liveness probes, reporting what
+/// it can serve. Unlike `GET /ready`, a detached store or a stopped ingestion loop is not an
+/// error here.
+async fn health(State(state): State<Arc<AppState>>) -> Json<HealthReply> {
+    Json(HealthReply {
+        store: state.store.is_some(),
+        ingesting: !state.ingestion_closed.load(Ordering::Relaxed),
+    })
+}
+
+// --- Metrics ---
+
+/// `GET /metrics`: the broadcaster's metrics in the Prometheus text format, for scraping
+/// alongside the websocket without access to the node's metrics server.
+async fn metrics(State(state): State<Arc<AppState>>) -> Response {
+    (
+        [(CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        state.metrics.render(),
+    )
+        .into_response()
+}
+
+// --- Admin Endpoints ---
+
+/// `GET /connections`: the live websocket connections.
+async fn list_connections(headers: HeaderMap, State(state): State<Arc<AppState>>) -> Response {
+    if let Err(status) = authorize_admin(&state, &headers) {
+        return status.into_response();
+    }
+    Json(state.connections.list()).into_response()
+}
+
+/// `POST /connections/{id}/disconnect`: close connection `id`. Its task sends a `Close` frame
+/// and shuts down.
+async fn disconnect_connection(
+    Path(id): Path<u64>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> StatusCode {
+    if let Err(status) = authorize_admin(&state, &headers) {
+        return status;
+    }
+    if state.connections.disconnect(id) {
+        info!(
+            "CustomBroadcaster: Disconnecting connection {} on request",
+            id
+        );
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Admin endpoints require `Authorization: Bearer <admin-token>`, and do not exist when no
+/// admin token is configured.
+fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Some(expected) = &state.admin_token else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    if token_matches(bearer_token(headers), expected) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Whether `token` is `expected`, compared in constant time so that the time taken does not
+/// reveal how much of a guess was right
+fn token_matches(token: Option<&str>, expected: &str) -> bool {
+    token.is_some_and(|token| bool::from(token.as_bytes().ct_eq(expected.as_bytes())))
+}
+
+/// The token of an `Authorization: Bearer <token>` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// --- WebSocket Handling ---
+
+/// The client's token, as a query parameter of the websocket URL (`/ws?token=...`), for
+/// clients such as browsers that cannot set headers on the upgrade request
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(options): Query<ConnectionOptions>,
+    Query(TokenQuery { token }): Query<TokenQuery>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    // Unlike the rejections below, unauthenticated clients are refused before upgrading
+    if let Err(status) = authorize_client(&state, token.as_deref(), &headers) {
+        return status.into_response();
+    }
+    // Rejections are upgraded too, so that the client sees a close code rather than a failed
+    // handshake, which browsers do not expose
+    if let Err(reason) = admit(&state) {
+        return ws.on_upgrade(move |mut socket| async move {
+            let _ = socket.send(Message::Close(Some(reason.frame()))).await;
+        });
+    }
+    ws.max_message_size(state.max_message_bytes)
+        .max_frame_size(state.max_message_bytes)
+        .on_upgrade(move |socket| handle_socket(socket, state, options))
+}
+
+/// With an `auth-token` configured, clients must present it in the `token` query parameter
+/// or an `Authorization: Bearer` header.
+fn authorize_client(
+    state: &AppState,
+    query_token: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<(), StatusCode> {
+    let Some(expected) = &state.auth_token else {
+        return Ok(());
+    };
+    let token = query_token.or_else(|| bearer_token(headers));
+    if token_matches(token, expected) {
+        Ok(())
+    } else {
+        debug!("CustomBroadcaster: Rejecting unauthenticated connection");
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Whether a new connection may be accepted
+fn admit(state: &AppState) -> Result<(), CloseReason> {
+    if let Some(max) = state.max_connections
+        && state.connections.len() >= max
+    {
+        warn!(
+            "CustomBroadcaster: Rejecting connection, limit of {} reached",
+            max
+        );
+        return Err(CloseReason::OverCapacity);
+    }
+    Ok(())
+}
+
+/// Record that a client missed `skipped` transactions, returning why to close it, if it should
+/// be closed
+fn on_lagged(state: &AppState, skipped: u64) -> Option<CloseReason> {
+    state.metrics.lagged.inc();
+    state.metrics.lagged_skipped.inc_by(skipped);
+    if state.overflow_strategy == OverflowStrategy::DisconnectSlowest {
+        warn!(
+            "CustomBroadcaster: Disconnecting client {} transactions behind",
+            skipped
+        );
+        state.metrics.slow_disconnects.inc();
+        return Some(CloseReason::SlowConsumer);
+    }
+    debug!(
+        "CustomBroadcaster: Client lagged, skipped {} transactions",
+        skipped
+    );
+    None
+}
+
+/// The transactions after `last_seq` that a lagging connection missed, if the retention buffer
+/// still holds all `skipped` of them
+fn missed_transactions(
+    retention: &RetentionBuffer,
+    last_seq: u64,
+    skipped: u64,
+) -> Option<Vec<IngestedTransaction>> {
+    let missed: Vec<_> = retention
+        .since(last_seq)
+        .into_iter()
+        .take_while(|(seq, _)| *seq <= last_seq + skipped)
+        .map(|(seq, executed)| IngestedTransaction {
+            seq,
+            outputs: executed.outputs,
+            checkpoint: executed.checkpoint,
+            payloads: PayloadCache::default(),
+        })
+        .collect();
+    (missed.len() as u64 == skipped && missed.first().is_some_and(|tx| tx.seq == last_seq + 1))
+        .then_some(missed)
+}
+
+/// The retained transactions replayed by `Resume { after_seq }`. With `delivered_seq`, only
+/// those up to it are: the later ones are still to be delivered live.
+fn resumed_transactions(
+    retention: &RetentionBuffer,
+    after_seq: u64,
+    delivered_seq: Option<u64>,
+) -> Vec<(u64, ExecutedTransaction)> {
+    let mut resumed = retention.since(after_seq);
+    if let Some(delivered_seq) = delivered_seq {
+        resumed.retain(|(seq, _)| *seq <= delivered_seq);
+    }
+    resumed
+}
+
+/// `CheckpointManifest` of checkpoint `sequence_number`, with its transaction digests in
+/// `digest_format`
+fn checkpoint_manifest(
+    checkpoint_store: &CheckpointStore,
+    sequence_number: u64,
+    digest_format: DigestFormat,
+) -> SuiResult<StreamMessage> {
+    let Some(summary) = checkpoint_store.get_checkpoint_by_sequence_number(sequence_number)? else {
+        return Err(UserInputError::VerifiedCheckpointNotFound(sequence_number).into());
+    };
+    let Some(contents) = checkpoint_store.get_checkpoint_contents(&summary.content_digest)? else {
+        return Err(UserInputError::CheckpointContentsNotFound(summary.content_digest).into());
+    };
+    Ok(StreamMessage::CheckpointManifest {
+        sequence_number,
+        digests: contents
+            .iter()
+            .map(|digests| digest_format.render(&digests.transaction))
+            .collect(),
+    })
+}
+
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, options: ConnectionOptions) {
+    let guard = ConnectionGuard::new(state.clone());
+    let mut socket = ClientSocket::new(socket, &state, guard.sent.clone(), &options);
+    let mut rx = state.tx.subscribe();
+    // Transactions sent to this connection from now on follow this one
+    let mut last_seq = state.ingested_seq.load(Ordering::Relaxed);
+    let mut subscriptions =
+        Subscriptions::new(&options, state.event_layouts.clone(), state.store.clone());
+    #[cfg(feature = "pool-decoders")]
+    if options.decode_pools {
+        subscriptions.pool_decoders = Some(state.pool_decoders.clone());
+    }
+    subscriptions.key_codecs = state.key_codecs.clone();
+    let mut heartbeat = state.heartbeat_interval.map(|period| {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+    let mut ping = state.ping_interval.map(|period| {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+    let mut dropped_report = tokio::time::interval(DROPPED_REPORT_INTERVAL);
+    dropped_report.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_sent = Instant::now();
+    // Any frame counts as a sign of life, not just a `Pong`
+    let mut last_received = Instant::now();
+    let mut query_cache = QueryCache::new(QUERY_CACHE_ENTRIES, QUERY_CACHE_TTL);
+    let mut credited_queries: HashMap<u64, CreditedQuery> = HashMap::new();
+
+    'outer: loop {
+        tokio::select! {
+            // Outbound: Send updates to client
             res = rx.recv() => {
+                state.receiver_advanced.notify_waiters();
                 match res {
-                    Ok(outputs) => {
-                         let digest = outputs.transaction.digest();
-                         // We track if we sent anything to avoid noise or filtered logic if needed,
-                         // but for now we just process all independent categories.
-
-                         // Debug Logging [Added for Verification]
-                         let sender = outputs.transaction.sender_address();
-                         info!("CustomBroadcaster: Processing Tx {} from Sender {} (AccSubs: {}, PoolSubs: {})",
-                             digest,
-                             sender,
-                             subscriptions_accounts.len(),
-                             subscriptions_pools.len()
-                         );
-
-                         // 1. Firehose / SubscribeAll Events (Optional, can be heavy)
-                         if subscribe_all {
-                             // Account Activity (Sender)
-                             let sender = outputs.transaction.sender_address();
-                             let msg = StreamMessage::AccountActivity {
-                                 account: sender,
-                                 digest: digest.to_string(),
-                                 kind: "Transaction".to_string(),
-                             };
-                             if let Err(_) = send_json(&mut socket, &msg).await { break; }
-                         }
-
-                         // 2. Events Broadcast
-                         // If subscribe_all is true, we send all events.
-                         // In the future, we can add filter sets for events.
-                         if subscribe_all {
-                             for event in &outputs.events.data {
-                                 let msg = StreamMessage::Event {
-                                     package_id: event.package_id,
-                                     transaction_module: event.transaction_module.to_string(),
-                                     sender: event.sender,
-                                     type_: event.type_.to_string(),
-                                     contents: event.contents.clone(),
-                                     digest: digest.to_string(),
-                                 };
-                                 if let Err(_) = send_json(&mut socket, &msg).await { break; }
-                             }
-                         }
-
-                         // 3. Pool Updates (Written Objects)
-                         // We iterate through written objects to see if any match our subscribed pools
-                         for (id, object) in &outputs.written {
-                             if subscriptions_pools.contains(id) {
-                                  let object_bytes = object.data.try_as_move().map(|o| o.contents().to_vec());
-                                  let msg = StreamMessage::PoolUpdate {
-                                      pool_id: *id,
-                                      digest: digest.to_string(),
-                                      object: object_bytes,
-                                  };
-                                  if let Err(_) = send_json(&mut socket, &msg).await { break; }
-                             }
-                         }
-
-                         // 4. Account Updates (Sender)
-                         // Check if the sender is one of our subscribed accounts
-                         let sender = outputs.transaction.sender_address();
-                         if subscriptions_accounts.contains(&sender) {
-                             info!("CustomBroadcaster: Match found for Account {}", sender);
-                             let msg = StreamMessage::AccountActivity {
-                                 account: sender,
-                                 digest: digest.to_string(),
-                                 kind: "Transaction".to_string(),
-                             };
-                             if let Err(_) = send_json(&mut socket, &msg).await { break; }
-                         }
-
-                         // Note: Explicit BalanceChange extraction would require parsing the Move objects
-                         // in `outputs.written` to see if they are Coin<T> owned by `sender` and what their value is.
-                         // This is complex without a resolver. For now, AccountActivity gives the trigger.
+                    Ok(ingested) => {
+                        last_seq = ingested.seq;
+                        debug!(
+                            "CustomBroadcaster: Processing Tx {} (AccSubs: {}, PoolSubs: {})",
+                            ingested.outputs.transaction.digest(),
+                            subscriptions.accounts.len(),
+                            subscriptions.pools.len()
+                        );
+                        for (message_type, payload) in subscriptions.payloads_for(&ingested) {
+                            if socket.send_update(message_type, payload).await.is_err() {
+                                break 'outer;
+                            }
+                            last_sent = Instant::now();
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        if let Some(reason) = on_lagged(&state, skipped) {
+                            let _ = socket.send(Message::Close(Some(reason.frame()))).await;
+                            break;
+                        }
+                        match missed_transactions(&state.retention, last_seq, skipped) {
+                            Some(missed) => {
+                                for ingested in missed {
+                                    for (message_type, payload) in
+                                        subscriptions.payloads_for(&ingested)
+                                    {
+                                        if socket.send_data(message_type, payload).await.is_err() {
+                                            break 'outer;
+                                        }
+                                        last_sent = Instant::now();
+                                    }
+                                }
+                            }
+                            None if options.ordered => {
+                                warn!(
+                                    "CustomBroadcaster: Disconnecting ordered client {} \
+                                     transactions behind",
+                                    skipped
+                                );
+                                state.metrics.slow_disconnects.inc();
+                                let reason = CloseReason::SlowConsumer;
+                                let _ = socket.send(Message::Close(Some(reason.frame()))).await;
+                                break;
+                            }
+                            None => {
+                                let msg = StreamMessage::Resync {
+                                    missed: skipped,
+                                    resume_seq: last_seq + skipped + 1,
+                                };
+                                if send_message(&mut socket, &msg).await.is_err() {
+                                    break 'outer;
+                                }
+                                last_sent = Instant::now();
+                            }
+                        }
+                        last_seq += skipped;
                     }
-                    Err(_) => break, // Channel closed
+                    Err(RecvError::Closed) => break,
                 }
             }
 
@@ -232,39 +3231,854 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
             res = socket.recv() => {
                 match res {
                     Some(Ok(msg)) => {
+                        last_received = Instant::now();
                         if let Message::Text(text) = msg {
-                            if let Ok(req) = serde_json::from_str::<SubscriptionRequest>(&text) {
-                                info!("Client subscribed: {:?}", req);
-                                match req {
-                                    SubscriptionRequest::SubscribePool(id) => {
-                                        subscriptions_pools.insert(id);
+                            let req = match serde_json::from_str::<SubscriptionRequest>(&text) {
+                                Ok(req) => req,
+                                Err(e) => {
+                                    let msg = StreamMessage::Error {
+                                        code: ErrorCode::InvalidRequest,
+                                        message: invalid_request_message(&text, &e),
+                                    };
+                                    if send_message(&mut socket, &msg).await.is_err() {
+                                        break 'outer;
+                                    }
+                                    last_sent = Instant::now();
+                                    continue 'outer;
+                                }
+                            };
+                            if let Err(e) = req.check_list_lengths() {
+                                let msg = StreamMessage::Error {
+                                    code: ErrorCode::InvalidRequest,
+                                    message: e.to_string(),
+                                };
+                                if send_message(&mut socket, &msg).await.is_err() {
+                                    break 'outer;
+                                }
+                                last_sent = Instant::now();
+                                continue 'outer;
+                            }
+                            match req {
+                                SubscriptionRequest::Resume {
+                                    after_seq,
+                                    manifests,
+                                } => {
+                                    let resumed = resumed_transactions(
+                                        &state.retention,
+                                        after_seq,
+                                        options.ordered.then_some(last_seq),
+                                    );
+                                    let checkpoint_store = match &state.checkpoint_store {
+                                        Some(checkpoint_store) if manifests => {
+                                            Some(checkpoint_store.clone())
+                                        }
+                                        None if manifests => {
+                                            let msg = StreamMessage::Error {
+                                                code: ErrorCode::StoreUnavailable,
+                                                message: "checkpoint manifests are not available \
+                                                          on this server"
+                                                    .to_string(),
+                                            };
+                                            if send_message(&mut socket, &msg).await.is_err() {
+                                                break 'outer;
+                                            }
+                                            last_sent = Instant::now();
+                                            None
+                                        }
+                                        _ => None,
+                                    };
+                                    let mut manifested = None;
+                                    for (_, executed) in resumed {
+                                        let ExecutedTransaction {
+                                            outputs,
+                                            checkpoint,
+                                        } = executed;
+                                        if let Some(checkpoint_store) = &checkpoint_store
+                                            && let Some(checkpoint) = checkpoint
+                                            && manifested != Some(checkpoint)
+                                        {
+                                            manifested = Some(checkpoint);
+                                            let msg = checkpoint_manifest(
+                                                checkpoint_store,
+                                                checkpoint,
+                                                subscriptions.digest_format,
+                                            )
+                                            .unwrap_or_else(|e| StreamMessage::Error {
+                                                code: ErrorCode::QueryFailed,
+                                                message: format!(
+                                                    "manifest of checkpoint {checkpoint}: {e}"
+                                                ),
+                                            });
+                                            if send_message(&mut socket, &msg).await.is_err() {
+                                                break 'outer;
+                                            }
+                                            last_sent = Instant::now();
+                                        }
+                                        let messages =
+                                            subscriptions.messages_for(&outputs, checkpoint);
+                                        for msg in messages {
+                                            if send_message(&mut socket, &msg).await.is_err() {
+                                                break 'outer;
+                                            }
+                                            last_sent = Instant::now();
+                                        }
+                                    }
+                                }
+                                SubscriptionRequest::SetFormat { format, compress } => {
+                                    subscriptions.message_format = format;
+                                    socket.message_format = format;
+                                    socket.compress = compress;
+                                }
+                                SubscriptionRequest::GetStats => {
+                                    if send_message(&mut socket, &guard.stats())
+                                        .await
+                                        .is_err()
+                                    {
+                                        break 'outer;
+                                    }
+                                    last_sent = Instant::now();
+                                }
+                                SubscriptionRequest::GetSubscriptions => {
+                                    if send_message(&mut socket, &subscriptions.snapshot())
+                                        .await
+                                        .is_err()
+                                    {
+                                        break 'outer;
+                                    }
+                                    last_sent = Instant::now();
+                                }
+                                SubscriptionRequest::QueryFieldRange {
+                                    table_id,
+                                    current_index,
+                                    range,
+                                    parent_version,
+                                    windows,
+                                    with_parent,
+                                    request_id,
+                                    credit,
+                                    key_type,
+                                    sparse,
+                                } => {
+                                    let key_type = match key_type
+                                        .as_deref()
+                                        .map(parse_key_type)
+                                        .transpose()
+                                    {
+                                        Ok(key_type) => key_type,
+                                        Err(message) => {
+                                            let msg = StreamMessage::Error {
+                                                code: ErrorCode::InvalidRequest,
+                                                message,
+                                            };
+                                            if send_message(&mut socket, &msg).await.is_err() {
+                                                break 'outer;
+                                            }
+                                            last_sent = Instant::now();
+                                            continue 'outer;
+                                        }
+                                    };
+                                    let first = IndexWindow {
+                                        center: current_index,
+                                        range,
+                                    };
+                                    let query = FieldRangeQuery {
+                                        table_id,
+                                        windows: std::iter::once(first)
+                                            .chain(windows)
+                                            .collect(),
+                                        parent_version: parent_version
+                                            .map(SequenceNumber::from_u64)
+                                            .unwrap_or(SequenceNumber::MAX),
+                                        with_parent,
+                                        request_id,
+                                        key_type,
+                                        sparse,
+                                    };
+                                    let res = match credit {
+                                        None => {
+                                            handle_field_range_query(
+                                                &mut socket,
+                                                &state,
+                                                &mut query_cache,
+                                                options.bytes,
+                                                query,
+                                            )
+                                            .await
+                                        }
+                                        Some(credit) => {
+                                            start_credited_query(
+                                                &mut socket,
+                                                &state,
+                                                &mut credited_queries,
+                                                options.bytes,
+                                                query,
+                                                credit,
+                                            )
+                                            .await
+                                        }
+                                    };
+                                    if res.is_err() {
+                                        break 'outer;
+                                    }
+                                    last_sent = Instant::now();
+                                }
+                                SubscriptionRequest::QueryCredit { request_id, n } => {
+                                    if let Some(query) = credited_queries.get_mut(&request_id)
+                                    {
+                                        query.credit = query.credit.saturating_add(n);
                                     }
-                                    SubscriptionRequest::SubscribeAccount(addr) => {
-                                        info!("CustomBroadcaster: Client subscribed to Account {}", addr);
-                                        subscriptions_accounts.insert(addr);
+                                }
+                                req => {
+                                    info!("Client subscribed: {:?}", req);
+                                    let snapshot = match &req {
+                                        SubscriptionRequest::SubscribeTable {
+                                            table_id,
+                                            mode: TableMode::Data,
+                                            windows,
+                                            snapshot: true,
+                                        } => Some((*table_id, windows.clone())),
+                                        _ => None,
+                                    };
+                                    // A snapshot supersedes the replay of recent updates
+                                    let replayed = if options.replay && snapshot.is_none() {
+                                        subscriptions.replay_for(&req, &state.retention)
+                                    } else {
+                                        vec![]
+                                    };
+                                    // The acknowledgment comes before the replayed messages
+                                    let reply = subscriptions.apply(req);
+                                    for msg in reply.into_iter().chain(replayed) {
+                                        if send_message(&mut socket, &msg).await.is_err() {
+                                            break 'outer;
+                                        }
+                                        last_sent = Instant::now();
                                     }
-                                    SubscriptionRequest::SubscribeAll => {
-                                        subscribe_all = true;
+                                    // Updates ingested while the snapshot is read wait in
+                                    // `rx`, and are checked against it once it is sent
+                                    if let Some((table_id, windows)) = snapshot {
+                                        let Ok(versions) = send_table_snapshot(
+                                            &mut socket,
+                                            &state,
+                                            options.bytes,
+                                            table_id,
+                                            &windows,
+                                        )
+                                        .await
+                                        else {
+                                            break 'outer;
+                                        };
+                                        subscriptions.snapshot_versions.extend(versions);
+                                        last_sent = Instant::now();
                                     }
                                 }
                             }
+                        } else if let Message::Binary(_) = msg {
+                            let reason = CloseReason::UnsupportedFrame;
+                            let _ = socket.send(Message::Close(Some(reason.frame()))).await;
+                            break;
                         } else if let Message::Close(_) = msg {
                             break;
                         }
                     }
-                    Some(Err(_)) => break,
+                    Some(Err(e)) => {
+                        if is_size_limit_error(&e) {
+                            let reason = CloseReason::MessageTooBig;
+                            let _ = socket.send(Message::Close(Some(reason.frame()))).await;
+                        }
+                        break;
+                    }
                     None => break,
                 }
             }
+
+            // Credited queries, as far as their credit allows
+            (request_id, item) = next_credited_field(&mut credited_queries) => {
+                let query = credited_queries
+                    .get_mut(&request_id)
+                    .expect("polled query is in progress");
+                let table_id = query.table_id;
+                let msg = match item {
+                    Some(Ok(field)) => {
+                        query.credit -= 1;
+                        query.fields += 1;
+                        StreamMessage::FieldData {
+                            table_id,
+                            index: field.index,
+                            field_id: field.field_id,
+                            version: field.version.value(),
+                            bcs_bytes: options.bytes.encode(field.bcs_bytes),
+                            request_id: Some(request_id),
+                        }
+                    }
+                    Some(Err(e)) => {
+                        query.errors += 1;
+                        StreamMessage::Error {
+                            code: ErrorCode::QueryFailed,
+                            message: format!("failed to read field of table {}: {}", table_id, e),
+                        }
+                    }
+                    None => {
+                        let query = credited_queries
+                            .remove(&request_id)
+                            .expect("polled query is in progress");
+                        StreamMessage::QueryComplete {
+                            table_id,
+                            fields: query.fields,
+                            errors: query.errors,
+                            request_id: Some(request_id),
+                        }
+                    }
+                };
+                if send_message(&mut socket, &msg).await.is_err() {
+                    break;
+                }
+                last_sent = Instant::now();
+            }
+
+            // Disconnected by an operator
+            _ = guard.cancel.cancelled() => {
+                let frame = CloseReason::Disconnected.frame();
+                let _ = socket.send(Message::Close(Some(frame))).await;
+                break;
+            }
+
+            // The broadcaster is shutting down
+            _ = state.shutdown.cancelled() => {
+                let frame = CloseReason::GoingAway.frame();
+                let _ = socket.send(Message::Close(Some(frame))).await;
+                break;
+            }
+
+            // Application-level keepalive, only when nothing else was sent recently
+            _ = tick(&mut heartbeat) => {
+                let period = state.heartbeat_interval.unwrap_or_default();
+                if last_sent.elapsed() >= period {
+                    let msg = StreamMessage::Heartbeat {
+                        timestamp_ms: now_ms(),
+                        server_seq: state.ingested_seq.load(Ordering::Relaxed),
+                    };
+                    if send_message(&mut socket, &msg).await.is_err() {
+                        break;
+                    }
+                    last_sent = Instant::now();
+                }
+            }
+
+            // Stream updates dropped for newer ones while the send queue was full
+            _ = dropped_report.tick() => {
+                let dropped = socket.take_dropped();
+                if dropped > 0 {
+                    state.metrics.dropped_messages.inc_by(dropped);
+                    let msg = StreamMessage::Dropped { messages: dropped };
+                    if send_message(&mut socket, &msg).await.is_err() {
+                        break;
+                    }
+                    last_sent = Instant::now();
+                }
+            }
+
+            // Transport-level liveness check; the client's websocket stack answers with a `Pong`
+            _ = tick(&mut ping) => {
+                if last_received.elapsed() >= state.pong_timeout {
+                    warn!(
+                        "CustomBroadcaster: Closing connection {}, nothing received for {:?}",
+                        guard.id,
+                        last_received.elapsed()
+                    );
+                    state.metrics.ping_timeouts.inc();
+                    let frame = CloseReason::PingTimeout.frame();
+                    let _ = socket.send(Message::Close(Some(frame))).await;
+                    break;
+                }
+                if socket.send(Message::Ping(Default::default())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Whether a read failed because the client exceeded `max-message-bytes`. axum hides the
+/// tungstenite error behind `axum::Error`, which displays it unchanged: size violations are
+/// its `Capacity` variant, rendered as "Space limit exceeded: ...".
+fn is_size_limit_error(e: &axum::Error) -> bool {
+    e.to_string().starts_with("Space limit exceeded")
+}
+
+/// Answer a `QueryFieldRange` request, forwarding fields to the socket as they are read.
+/// Complete answers for an exact version are cached and replayed for repeated queries.
+async fn handle_field_range_query(
+    socket: &mut ClientSocket,
+    state: &AppState,
+    cache: &mut QueryCache,
+    byte_format: ByteFormat,
+    query: FieldRangeQuery,
+) -> Result<(), ()> {
+    if !state.may_query(&query.table_id) {
+        return send_message(socket, &not_queryable(query.table_id)).await;
+    }
+    if let Err(message) = state.check_ranges(&query.windows) {
+        let msg = StreamMessage::Error {
+            code: ErrorCode::RangeTooLarge,
+            message,
+        };
+        return send_message(socket, &msg).await;
+    }
+    if let Some(messages) = cache.get(&query) {
+        for msg in messages.iter() {
+            send_message(socket, msg).await?;
+        }
+        return Ok(());
+    }
+
+    let Some(store) = state.store.clone() else {
+        let msg = StreamMessage::Error {
+            code: ErrorCode::StoreUnavailable,
+            message: "field queries are not available on this server".to_string(),
+        };
+        return send_message(socket, &msg).await;
+    };
+
+    let timer = state.metrics.field_query_latency.start_timer();
+    let table_id = query.table_id;
+    let mut sent = Vec::new();
+    let (mut fields, mut errors) = (0, 0);
+    let mut parent_version = query.parent_version;
+    if query.with_parent {
+        let read = read_parent_object(store.clone(), byte_format, &query).await;
+        let msg = match read {
+            Ok(Some((msg, version))) => {
+                parent_version = version;
+                Some(msg)
+            }
+            Ok(None) => None,
+            Err(msg) => {
+                errors += 1;
+                Some(msg)
+            }
+        };
+        if let Some(msg) = msg {
+            send_message(socket, &msg).await?;
+            sent.push(msg);
+        }
+    }
+
+    let mut stream = query_fields(state, store, &query, parent_version);
+    while let Some(item) = stream.next().await {
+        let msg = match item {
+            Ok(field) => {
+                fields += 1;
+                StreamMessage::FieldData {
+                    table_id,
+                    index: field.index,
+                    field_id: field.field_id,
+                    version: field.version.value(),
+                    bcs_bytes: byte_format.encode(field.bcs_bytes),
+                    request_id: query.request_id,
+                }
+            }
+            Err(e) => {
+                errors += 1;
+                StreamMessage::Error {
+                    code: ErrorCode::QueryFailed,
+                    message: format!("failed to read field of table {}: {}", table_id, e),
+                }
+            }
+        };
+        send_message(socket, &msg).await?;
+        sent.push(msg);
+    }
+
+    let msg = StreamMessage::QueryComplete {
+        table_id,
+        fields,
+        errors,
+        request_id: query.request_id,
+    };
+    send_message(socket, &msg).await?;
+    timer.observe_duration();
+    // Answers with read errors are not worth replaying
+    if errors == 0 {
+        sent.push(msg);
+        cache.insert(query, sent);
+    }
+    Ok(())
+}
+
+fn not_queryable_message(table_id: ObjectID) -> String {
+    format!("table {} may not be queried on this server", table_id)
+}
+
+fn not_queryable(table_id: ObjectID) -> StreamMessage {
+    StreamMessage::Error {
+        code: ErrorCode::Unauthorized,
+        message: not_queryable_message(table_id),
+    }
+}
+
+/// The `ParentObject` message for the table of `query` and the version it was read at, `None`
+/// if the table has no object of its own at that version (wrapped, deleted, or never stored),
+/// or the `Error` message reporting why it could not be read.
+async fn read_parent_object(
+    store: Arc<AuthorityPerpetualTables>,
+    byte_format: ByteFormat,
+    query: &FieldRangeQuery,
+) -> Result<Option<(StreamMessage, SequenceNumber)>, StreamMessage> {
+    let (table_id, parent_version) = (query.table_id, query.parent_version);
+    let read =
+        tokio::task::spawn_blocking(move || get_parent_object(&store, table_id, parent_version))
+            .await;
+    let message = match read {
+        Ok(Ok(Some(parent))) => {
+            let msg = StreamMessage::ParentObject {
+                table_id,
+                version: parent.version.value(),
+                object_type: parent.object_type,
+                bcs_bytes: byte_format.encode(parent.bcs_bytes),
+                request_id: query.request_id,
+            };
+            return Ok(Some((msg, parent.version)));
+        }
+        Ok(Ok(None)) => return Ok(None),
+        Ok(Err(e)) => format!("failed to read table {}: {}", table_id, e),
+        Err(e) => format!("failed to read table {}: {}", table_id, e),
+    };
+    Err(StreamMessage::Error {
+        code: ErrorCode::QueryFailed,
+        message,
+    })
+}
+
+/// Start a `QueryFieldRange` request with `credit`, whose fields are then sent by the
+/// connection loop as credit allows. Only the table object, when asked for, is sent here.
+async fn start_credited_query(
+    socket: &mut ClientSocket,
+    state: &AppState,
+    queries: &mut HashMap<u64, CreditedQuery>,
+    byte_format: ByteFormat,
+    query: FieldRangeQuery,
+    credit: u64,
+) -> Result<(), ()> {
+    let rejection = match query.request_id {
+        None => Some((
+            ErrorCode::InvalidRequest,
+            "a query with credit needs a request_id".to_string(),
+        )),
+        Some(request_id) if queries.contains_key(&request_id) => Some((
+            ErrorCode::InvalidRequest,
+            format!("query {} is already in progress", request_id),
+        )),
+        Some(_) if queries.len() >= MAX_CREDITED_QUERIES => Some((
+            ErrorCode::InvalidRequest,
+            format!(
+                "at most {} queries with credit may be in progress",
+                MAX_CREDITED_QUERIES
+            ),
+        )),
+        Some(_) if !state.may_query(&query.table_id) => Some((
+            ErrorCode::Unauthorized,
+            not_queryable_message(query.table_id),
+        )),
+        Some(_) if state.store.is_none() => Some((
+            ErrorCode::StoreUnavailable,
+            "field queries are not available on this server".to_string(),
+        )),
+        Some(_) => state
+            .check_ranges(&query.windows)
+            .err()
+            .map(|message| (ErrorCode::RangeTooLarge, message)),
+    };
+    if let Some((code, message)) = rejection {
+        return send_message(socket, &StreamMessage::Error { code, message }).await;
+    }
+    let (Some(request_id), Some(store)) = (query.request_id, state.store.clone()) else {
+        unreachable!("checked above");
+    };
+
+    let mut errors = 0;
+    let mut parent_version = query.parent_version;
+    if query.with_parent {
+        let msg = match read_parent_object(store.clone(), byte_format, &query).await {
+            Ok(Some((msg, version))) => {
+                parent_version = version;
+                Some(msg)
+            }
+            Ok(None) => None,
+            Err(msg) => {
+                errors += 1;
+                Some(msg)
+            }
+        };
+        if let Some(msg) = msg {
+            send_message(socket, &msg).await?;
+        }
+    }
+
+    let table_id = query.table_id;
+    let stream = query_fields(state, store, &query, parent_version);
+    queries.insert(
+        request_id,
+        CreditedQuery {
+            table_id,
+            stream: stream.peekable(),
+            credit,
+            fields: 0,
+            errors,
+        },
+    );
+    Ok(())
+}
+
+/// The fields of `query` read at `parent_version`, as they are read
+fn query_fields(
+    state: &AppState,
+    store: Arc<AuthorityPerpetualTables>,
+    query: &FieldRangeQuery,
+    parent_version: SequenceNumber,
+) -> BoxStream<'static, SuiResult<FieldData>> {
+    let (table_id, windows) = (query.table_id, &query.windows);
+    let codec = key_codec(&state.key_codecs, &table_id, query.key_type.as_ref());
+    match query.sparse {
+        None => {
+            query_field_data_stream_windows(store, table_id, windows, parent_version, codec).boxed()
+        }
+        Some(max_misses) => query_field_data_stream_sparse(
+            store,
+            table_id,
+            windows,
+            parent_version,
+            codec,
+            max_misses,
+        )
+        .boxed(),
+    }
+}
+
+/// The next item of a credited query that may be sent, `None` once the query has read all its
+/// fields. Pending while no query has such an item ready.
+async fn next_credited_field(
+    queries: &mut HashMap<u64, CreditedQuery>,
+) -> (u64, Option<SuiResult<FieldData>>) {
+    std::future::poll_fn(|cx| {
+        for (request_id, query) in queries.iter_mut() {
+            // Without credit, only errors and the end of the scan may be sent
+            if query.credit == 0
+                && matches!(
+                    Pin::new(&mut query.stream).poll_peek(cx),
+                    Poll::Pending | Poll::Ready(Some(Ok(_)))
+                )
+            {
+                continue;
+            }
+            if let Poll::Ready(item) = query.stream.poll_next_unpin(cx) {
+                return Poll::Ready((*request_id, item));
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+/// Send the latest version of each field of `table_id` in `windows` as `FieldData`, followed by
+/// `SnapshotComplete`, returning the version sent of each field.
+async fn send_table_snapshot(
+    socket: &mut ClientSocket,
+    state: &AppState,
+    byte_format: ByteFormat,
+    table_id: ObjectID,
+    windows: &[IndexWindow],
+) -> Result<HashMap<ObjectID, u64>, ()> {
+    let mut versions = HashMap::new();
+    let Some(store) = state.store.clone() else {
+        let msg = StreamMessage::Error {
+            code: ErrorCode::StoreUnavailable,
+            message: "table snapshots are not available on this server".to_string(),
+        };
+        send_message(socket, &msg).await?;
+        return Ok(versions);
+    };
+    if !state.may_query(&table_id) {
+        send_message(socket, &not_queryable(table_id)).await?;
+        return Ok(versions);
+    }
+    if windows.is_empty() {
+        let msg = StreamMessage::Error {
+            code: ErrorCode::InvalidRequest,
+            message: format!("snapshot of table {} needs windows", table_id),
+        };
+        send_message(socket, &msg).await?;
+        return Ok(versions);
+    }
+    if let Err(message) = state.check_ranges(windows) {
+        let msg = StreamMessage::Error {
+            code: ErrorCode::RangeTooLarge,
+            message,
+        };
+        send_message(socket, &msg).await?;
+        return Ok(versions);
+    }
+
+    let mut stream = Box::pin(query_field_data_stream_windows(
+        store,
+        table_id,
+        windows,
+        SequenceNumber::MAX,
+        state.key_codecs.codec_for(&table_id),
+    ));
+    while let Some(item) = stream.next().await {
+        let msg = match item {
+            Ok(field) => {
+                versions.insert(field.field_id, field.version.value());
+                StreamMessage::FieldData {
+                    table_id,
+                    index: field.index,
+                    field_id: field.field_id,
+                    version: field.version.value(),
+                    bcs_bytes: byte_format.encode(field.bcs_bytes),
+                    request_id: None,
+                }
+            }
+            Err(e) => StreamMessage::Error {
+                code: ErrorCode::QueryFailed,
+                message: format!("failed to read field of table {}: {}", table_id, e),
+            },
+        };
+        send_message(socket, &msg).await?;
+    }
+
+    let msg = StreamMessage::SnapshotComplete {
+        table_id,
+        snapshot_version: versions.values().copied().max().unwrap_or_default(),
+    };
+    send_message(socket, &msg).await?;
+    Ok(versions)
+}
+
+/// Waits for the next tick, or forever when the interval is disabled.
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
         }
+        None => std::future::pending().await,
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// A client's websocket, whose frames are queued for a writer task that counts the data
+/// messages sent on it.
+struct ClientSocket {
+    stream: SplitStream<WebSocket>,
+    queue: Arc<SendQueue>,
+    /// `seq` of the last data message queued
+    seq: u64,
+    /// Rendering of the IDs and addresses in the messages serialized by [`send_message`]
+    address_format: AddressFormat,
+    /// Framing of the messages serialized by [`send_message`]
+    message_format: MessageFormat,
+    /// Whether data frames are sent gzipped (see `SubscriptionRequest::SetFormat`)
+    compress: bool,
+}
+
+impl ClientSocket {
+    /// Split `socket`, spawning the writer of its sending half. Under `drop-oldest`, stream
+    /// updates of connections that are not `ordered` may be dropped while the queue is full.
+    fn new(
+        socket: WebSocket,
+        state: &AppState,
+        sent: Arc<SentCounters>,
+        options: &ConnectionOptions,
+    ) -> Self {
+        let (sink, stream) = socket.split();
+        let drop_oldest =
+            state.overflow_strategy == OverflowStrategy::DropOldest && !options.ordered;
+        let queue = Arc::new(SendQueue::new(state.send_queue_capacity, drop_oldest));
+        tokio::spawn(write_frames(
+            queue.clone(),
+            sink,
+            sent,
+            state.metrics.clone(),
+        ));
+        Self {
+            stream,
+            queue,
+            seq: 0,
+            address_format: options.address,
+            message_format: MessageFormat::default(),
+            compress: false,
+        }
+    }
+
+    /// Send a control frame, which is not counted
+    async fn send(&mut self, msg: Message) -> Result<(), Closed> {
+        self.push(msg, None, false).await
+    }
+
+    /// Send the serialized `StreamMessage` variant `message_type`
+    async fn send_data(
+        &mut self,
+        message_type: &'static str,
+        frame: Message,
+    ) -> Result<(), Closed> {
+        self.push(frame, Some(message_type), false).await
+    }
+
+    /// Send a live stream update, which a newer one may replace if the socket falls behind
+    async fn send_update(
+        &mut self,
+        message_type: &'static str,
+        frame: Message,
+    ) -> Result<(), Closed> {
+        self.push(frame, Some(message_type), true).await
+    }
+
+    async fn push(
+        &mut self,
+        frame: Message,
+        message_type: Option<&'static str>,
+        droppable: bool,
+    ) -> Result<(), Closed> {
+        let frame = match message_type {
+            Some(_) => {
+                self.seq += 1;
+                let frame = with_seq(frame, self.seq);
+                if self.compress { gzipped(frame) } else { frame }
+            }
+            None => frame,
+        };
+        self.queue
+            .push(Outbound {
+                frame,
+                message_type,
+                droppable,
+            })
+            .await
+    }
+
+    /// Stream updates dropped since the last call
+    fn take_dropped(&self) -> u64 {
+        self.queue.take_dropped()
+    }
+
+    async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+        self.stream.next().await
+    }
+}
+
+impl Drop for ClientSocket {
+    fn drop(&mut self) {
+        // The writer sends what is still queued, e.g. a `Close` frame, then stops
+        self.queue.finish();
     }
 }
 
-async fn send_json<T: Serialize>(socket: &mut WebSocket, msg: &T) -> Result<(), ()> {
-    let text = serde_json::to_string(msg).map_err(|_| ())?;
-    // Fix: Convert String to Utf8Bytes via .into()
-    socket
-        .send(Message::Text(text.into()))
-        .await
-        .map_err(|_| ())
+async fn send_message(socket: &mut ClientSocket, msg: &StreamMessage) -> Result<(), ()> {
+    let frame = serialize_message(msg, socket.message_format, socket.address_format).ok_or(())?;
+    socket.send_data(msg.into(), frame).await.map_err(|_| ())
 }
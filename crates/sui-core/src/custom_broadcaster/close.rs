@@ -0,0 +1,78 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Close codes of the connections the server ends
+//!
+//! Every connection the server rejects or ends is upgraded (if it was not already) and sent a
+//! `Close` frame with one of these codes, so that clients can tell why without parsing the
+//! reason string. Codes in the 4000-4999 range are private to this protocol; the others are
+//! the standard codes of RFC 6455.
+
+use axum::extract::ws::{CloseFrame, close_code};
+
+/// The broadcaster is shutting down.
+pub const CLOSE_GOING_AWAY: u16 = close_code::AWAY;
+/// The connection limit was reached when the client connected; retry later.
+pub const CLOSE_OVER_CAPACITY: u16 = close_code::AGAIN;
+/// An operator disconnected the client through the admin endpoints.
+pub const CLOSE_DISCONNECTED: u16 = close_code::POLICY;
+/// The client sent a binary frame; requests are JSON text frames.
+pub const CLOSE_UNSUPPORTED_FRAME: u16 = close_code::UNSUPPORTED;
+/// The client sent a frame or message larger than the configured `max-message-bytes`.
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = close_code::SIZE;
+/// The client failed authentication.
+pub const CLOSE_UNAUTHORIZED: u16 = 4001;
+/// The client fell too far behind the stream and the server is configured to drop slow clients.
+pub const CLOSE_SLOW_CONSUMER: u16 = 4008;
+/// Nothing, not even a `Pong`, was received from the client within the configured
+/// `pong-timeout-ms`.
+pub const CLOSE_PING_TIMEOUT: u16 = 4009;
+
+/// Why the server closed a connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    GoingAway,
+    OverCapacity,
+    Disconnected,
+    UnsupportedFrame,
+    MessageTooBig,
+    Unauthorized,
+    SlowConsumer,
+    PingTimeout,
+}
+
+impl CloseReason {
+    pub fn code(self) -> u16 {
+        match self {
+            Self::GoingAway => CLOSE_GOING_AWAY,
+            Self::OverCapacity => CLOSE_OVER_CAPACITY,
+            Self::Disconnected => CLOSE_DISCONNECTED,
+            Self::UnsupportedFrame => CLOSE_UNSUPPORTED_FRAME,
+            Self::MessageTooBig => CLOSE_MESSAGE_TOO_BIG,
+            Self::Unauthorized => CLOSE_UNAUTHORIZED,
+            Self::SlowConsumer => CLOSE_SLOW_CONSUMER,
+            Self::PingTimeout => CLOSE_PING_TIMEOUT,
+        }
+    }
+
+    /// The reason string of the `Close` frame, for humans; clients should match on the code.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::GoingAway => "server shutting down",
+            Self::OverCapacity => "connection limit reached",
+            Self::Disconnected => "disconnected by operator",
+            Self::UnsupportedFrame => "binary frames are not supported",
+            Self::MessageTooBig => "message too big",
+            Self::Unauthorized => "unauthorized",
+            Self::SlowConsumer => "client too slow",
+            Self::PingTimeout => "no pong received",
+        }
+    }
+
+    pub(crate) fn frame(self) -> CloseFrame {
+        CloseFrame {
+            code: self.code(),
+            reason: self.reason().into(),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use sui_types::{
+    balance_change::{BalanceChange, derive_balance_changes},
+    base_types::SuiAddress,
+    effects::TransactionEffectsAPI,
+    object::Object,
+};
+use tracing::debug;
+
+use crate::authority::authority_store_tables::AuthorityPerpetualTables;
+use crate::transaction_outputs::TransactionOutputs;
+
+/// An amount of `coin_type` moved by a transaction from one address to another.
+///
+/// `from` is `None` for value that entered circulation among address-owned coins (minted, or
+/// taken out of a shared object such as a pool's reserves). `to` is `None` for value that left
+/// it (gas fees, burns, or deposits into a shared object).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinFlow {
+    pub from: Option<SuiAddress>,
+    pub to: Option<SuiAddress>,
+    pub coin_type: String,
+    pub amount: u128,
+}
+
+/// Coin flows of one transaction, from the balance of every `Coin<T>` it consumed and produced.
+///
+/// The transaction's outputs only carry the objects it wrote, so the input versions of the
+/// objects it modified are read from `store`. Inputs missing from the store (e.g. pruned) are
+/// skipped, which makes the flows incomplete rather than failing.
+pub(crate) fn coin_flows(
+    store: &AuthorityPerpetualTables,
+    outputs: &TransactionOutputs,
+) -> Vec<CoinFlow> {
+    let inputs: Vec<Object> = outputs
+        .effects
+        .modified_at_versions()
+        .into_iter()
+        .filter_map(|(id, version)| {
+            store
+                .get_object_by_key_fallible(&id, version)
+                .inspect_err(|e| debug!("CustomBroadcaster: Failed to read {id}: {e}"))
+                .ok()
+                .flatten()
+        })
+        .collect();
+    let written: Vec<Object> = outputs.written.values().cloned().collect();
+    match_flows(derive_balance_changes(&outputs.effects, &inputs, &written))
+}
+
+/// Pair the addresses whose balance of a coin type decreased with those whose balance
+/// increased, in address order, splitting amounts as needed. A transaction does not record who
+/// paid whom, so when several addresses send and receive the same coin type this is one
+/// consistent attribution among many; the net change of every address is always exact.
+pub(crate) fn match_flows(changes: Vec<BalanceChange>) -> Vec<CoinFlow> {
+    let mut by_type: BTreeMap<String, (Vec<(SuiAddress, u128)>, Vec<(SuiAddress, u128)>)> =
+        BTreeMap::new();
+    for change in changes {
+        let (senders, receivers) = by_type
+            .entry(change.coin_type.to_canonical_string(true))
+            .or_default();
+        if change.amount < 0 {
+            senders.push((change.address, change.amount.unsigned_abs()));
+        } else {
+            receivers.push((change.address, change.amount.unsigned_abs()));
+        }
+    }
+
+    let mut flows = Vec::new();
+    for (coin_type, (senders, receivers)) in by_type {
+        let mut senders = senders.into_iter().peekable();
+        let mut receivers = receivers.into_iter().peekable();
+        loop {
+            let flow = match (senders.peek_mut(), receivers.peek_mut()) {
+                (Some((from, sent)), Some((to, received))) => {
+                    let amount = (*sent).min(*received);
+                    *sent -= amount;
+                    *received -= amount;
+                    (Some(*from), Some(*to), amount)
+                }
+                (Some((from, sent)), None) => (Some(*from), None, std::mem::take(sent)),
+                (None, Some((to, received))) => (None, Some(*to), std::mem::take(received)),
+                (None, None) => break,
+            };
+            senders.next_if(|(_, left)| *left == 0);
+            receivers.next_if(|(_, left)| *left == 0);
+            flows.push(CoinFlow {
+                from: flow.0,
+                to: flow.1,
+                coin_type: coin_type.clone(),
+                amount: flow.2,
+            });
+        }
+    }
+    flows
+}
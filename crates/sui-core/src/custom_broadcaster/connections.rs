@@ -0,0 +1,132 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// A live websocket connection, as listed by `GET /connections`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub connected_at_ms: u64,
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+}
+
+/// Data messages sent on a connection and their payload bytes, excluding control frames.
+#[derive(Debug, Default)]
+pub(crate) struct SentCounters {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl SentCounters {
+    pub(crate) fn record(&self, bytes: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// A connection's handles, as returned by [`ConnectionRegistry::register`].
+pub(crate) struct Registration {
+    pub(crate) id: u64,
+    /// Cancelled by [`ConnectionRegistry::disconnect`].
+    pub(crate) cancel: CancellationToken,
+    pub(crate) sent: Arc<SentCounters>,
+}
+
+struct Entry {
+    connected_at_ms: u64,
+    cancel: CancellationToken,
+    sent: Arc<SentCounters>,
+}
+
+impl Entry {
+    fn info(&self, id: u64) -> ConnectionInfo {
+        ConnectionInfo {
+            id,
+            connected_at_ms: self.connected_at_ms,
+            messages_sent: self.sent.messages(),
+            bytes_sent: self.sent.bytes(),
+        }
+    }
+}
+
+/// The live websocket connections, each with a token that its task watches so that operators
+/// can disconnect it.
+#[derive(Default)]
+pub(crate) struct ConnectionRegistry {
+    next_id: AtomicU64,
+    connections: Mutex<HashMap<u64, Entry>>,
+}
+
+impl ConnectionRegistry {
+    /// Add a connection, returning its id, cancellation token and counters.
+    pub(crate) fn register(&self, connected_at_ms: u64) -> Registration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let entry = Entry {
+            connected_at_ms,
+            cancel: CancellationToken::new(),
+            sent: Arc::default(),
+        };
+        let registration = Registration {
+            id,
+            cancel: entry.cancel.clone(),
+            sent: entry.sent.clone(),
+        };
+        self.connections.lock().insert(id, entry);
+        registration
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        self.connections.lock().remove(&id);
+    }
+
+    /// Signal connection `id` to close. Returns whether it was connected.
+    pub(crate) fn disconnect(&self, id: u64) -> bool {
+        match self.connections.lock().get(&id) {
+            Some(entry) => {
+                entry.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Connection `id`, if it is live.
+    pub(crate) fn get(&self, id: u64) -> Option<ConnectionInfo> {
+        self.connections.lock().get(&id).map(|entry| entry.info(id))
+    }
+
+    /// The live connections, oldest first.
+    pub(crate) fn list(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = self
+            .connections
+            .lock()
+            .iter()
+            .map(|(id, entry)| entry.info(*id))
+            .collect();
+        connections.sort_by_key(|info| info.id);
+        connections
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.connections.lock().len()
+    }
+}
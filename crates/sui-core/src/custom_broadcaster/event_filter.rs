@@ -0,0 +1,204 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use lru::LruCache;
+use move_binary_format::CompiledModule;
+use move_bytecode_utils::module_cache::GetModule;
+use move_core_types::{
+    annotated_value as A,
+    identifier::Identifier,
+    language_storage::{ModuleId, StructTag},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{num::NonZeroUsize, sync::Arc};
+use sui_json_rpc_types::SuiMoveStruct;
+use sui_types::{
+    base_types::{ObjectID, SequenceNumber},
+    error::{SuiError, SuiResult},
+    event::Event,
+    layout_resolver::{get_layout_from_struct_tag, into_struct_layout},
+    object::bounded_visitor::BoundedVisitor,
+    parse_sui_struct_tag,
+    storage::{BackingPackageStore, PackageObject, get_module_by_id},
+};
+use tracing::debug;
+
+use crate::authority::authority_store_tables::AuthorityPerpetualTables;
+
+/// Number of event layouts kept in memory. Layouts never change for a given type, so entries
+/// are only evicted for space.
+const LAYOUT_CACHE_SIZE: usize = 1024;
+
+/// Matches a field of a decoded event against a value.
+///
+/// `path` is a dot-separated list of field names (e.g. `pool_id` or `key.tick`). Values are
+/// compared as JSON, except that numbers and strings compare equal when their text does, since
+/// 64-bit and wider integers are rendered as strings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldFilter {
+    pub path: String,
+    pub value: Value,
+}
+
+impl FieldFilter {
+    pub(crate) fn matches(&self, event: &Value) -> bool {
+        let found = self
+            .path
+            .split('.')
+            .try_fold(event, |value, field| value.get(field));
+        found.is_some_and(|found| found == &self.value || as_text(found) == as_text(&self.value))
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// An event subscription: events emitted from `package_id` (and `module`, if set), of exactly
+/// `event_type` (if set), whose fields match every filter. Either a package or an event type
+/// is required, so that a filter never matches every event.
+///
+/// The package and module are those of the Move call that emitted the event, i.e. the
+/// `package_id` and `transaction_module` of the `Event` message, not the type's.
+#[derive(Clone, Debug)]
+pub(crate) struct EventFilter {
+    pub(crate) package_id: Option<ObjectID>,
+    pub(crate) module: Option<Identifier>,
+    pub(crate) event_type: Option<StructTag>,
+    pub(crate) field_filters: Vec<FieldFilter>,
+}
+
+impl EventFilter {
+    pub(crate) fn new(
+        package_id: Option<ObjectID>,
+        module: Option<&str>,
+        event_type: Option<&str>,
+        field_filters: Vec<FieldFilter>,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            package_id.is_some() || event_type.is_some(),
+            "an event filter needs a package_id or an event_type"
+        );
+        anyhow::ensure!(
+            module.is_none() || package_id.is_some(),
+            "an event filter's module requires its package_id"
+        );
+        Ok(Self {
+            package_id,
+            module: module.map(Identifier::new).transpose()?,
+            event_type: event_type.map(parse_sui_struct_tag).transpose()?,
+            field_filters,
+        })
+    }
+
+    /// The package (and module) this filter is scoped to, as `<package>` or
+    /// `<package>::<module>`.
+    pub(crate) fn scope(&self) -> Option<String> {
+        let package_id = self.package_id?;
+        Some(match &self.module {
+            Some(module) => format!("{package_id}::{module}"),
+            None => package_id.to_string(),
+        })
+    }
+
+    /// Field filters are only applied when the event's layout can be resolved; otherwise
+    /// matching falls back to the package, module and event type alone.
+    pub(crate) fn matches(&self, event: &Event, layouts: Option<&EventLayouts>) -> bool {
+        if self
+            .package_id
+            .is_some_and(|package_id| event.package_id != package_id)
+            || self
+                .module
+                .as_ref()
+                .is_some_and(|module| &event.transaction_module != module)
+            || self
+                .event_type
+                .as_ref()
+                .is_some_and(|event_type| &event.type_ != event_type)
+        {
+            return false;
+        }
+        if self.field_filters.is_empty() {
+            return true;
+        }
+        let Some(decoded) = layouts.and_then(|layouts| layouts.decode(event)) else {
+            return true;
+        };
+        self.field_filters.iter().all(|f| f.matches(&decoded))
+    }
+}
+
+/// Resolves and caches the Move layouts needed to decode event and object contents, reading
+/// packages from the node's object store.
+pub(crate) struct EventLayouts {
+    store: Arc<AuthorityPerpetualTables>,
+    cache: Mutex<LruCache<StructTag, Option<Arc<A::MoveStructLayout>>>>,
+}
+
+impl EventLayouts {
+    pub(crate) fn new(store: Arc<AuthorityPerpetualTables>) -> Self {
+        Self {
+            store,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(LAYOUT_CACHE_SIZE).unwrap())),
+        }
+    }
+
+    /// The event's contents as JSON, or `None` if its layout cannot be resolved.
+    fn decode(&self, event: &Event) -> Option<Value> {
+        self.decode_struct(&event.type_, &event.contents)
+    }
+
+    /// BCS `contents` of a `tag` value (an event or an object) as JSON, or `None` if its layout
+    /// cannot be resolved.
+    pub(crate) fn decode_struct(&self, tag: &StructTag, contents: &[u8]) -> Option<Value> {
+        let layout = self.layout(tag)?;
+        match BoundedVisitor::deserialize_struct(contents, &layout) {
+            Ok(decoded) => Some(SuiMoveStruct::from(decoded).to_json_value()),
+            Err(e) => {
+                debug!("CustomBroadcaster: Failed to decode {}: {}", tag, e);
+                None
+            }
+        }
+    }
+
+    fn layout(&self, tag: &StructTag) -> Option<Arc<A::MoveStructLayout>> {
+        if let Some(layout) = self.cache.lock().get(tag) {
+            return layout.clone();
+        }
+        let layout = get_layout_from_struct_tag(tag.clone(), &StorePackages(&self.store))
+            .and_then(into_struct_layout)
+            .map(Arc::new)
+            .inspect_err(|e| debug!("CustomBroadcaster: No layout for {}: {}", tag, e))
+            .ok();
+        self.cache.lock().put(tag.clone(), layout.clone());
+        layout
+    }
+}
+
+/// Latest version of each package, read straight from the objects table. Struct layouts do not
+/// change across package upgrades, so any version of the defining package will do.
+struct StorePackages<'a>(&'a AuthorityPerpetualTables);
+
+impl BackingPackageStore for StorePackages<'_> {
+    fn get_package_object(&self, package_id: &ObjectID) -> SuiResult<Option<PackageObject>> {
+        Ok(self
+            .0
+            .find_object_lt_or_eq_version(*package_id, SequenceNumber::MAX)?
+            .filter(|o| o.is_package())
+            .map(PackageObject::new))
+    }
+}
+
+impl GetModule for StorePackages<'_> {
+    type Error = SuiError;
+    type Item = CompiledModule;
+
+    fn get_module_by_id(&self, id: &ModuleId) -> Result<Option<Self::Item>, Self::Error> {
+        get_module_by_id(self, id)
+    }
+}
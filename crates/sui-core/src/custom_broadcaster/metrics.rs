@@ -0,0 +1,160 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use prometheus::{
+    Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, Registry, TextEncoder,
+    register_histogram_with_registry, register_int_counter_vec_with_registry,
+    register_int_counter_with_registry, register_int_gauge_with_registry,
+};
+
+/// Prefix of the names of the broadcaster's metrics
+const METRIC_PREFIX: &str = "custom_broadcaster_";
+
+const QUERY_LATENCY_SEC_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub struct BroadcasterMetrics {
+    /// The registry the metrics were registered in, for `GET /metrics`
+    registry: Registry,
+    pub(crate) ingest_queue_depth: IntGauge,
+    pub(crate) ingested: IntCounter,
+    pub(crate) ingest_dropped: IntCounterVec,
+    pub(crate) skipped_no_subscribers: IntCounter,
+    pub(crate) retention_buffer_len: IntGauge,
+    pub(crate) retention_buffer_bytes: IntGauge,
+    pub(crate) connected_clients: IntGauge,
+    pub(crate) lagged: IntCounter,
+    pub(crate) lagged_skipped: IntCounter,
+    pub(crate) slow_disconnects: IntCounter,
+    pub(crate) ping_timeouts: IntCounter,
+    pub(crate) dropped_messages: IntCounter,
+    pub(crate) messages_sent: IntCounterVec,
+    pub(crate) bytes_sent: IntCounter,
+    pub(crate) field_query_latency: Histogram,
+}
+
+impl BroadcasterMetrics {
+    pub fn new(registry: &Registry) -> Self {
+        Self {
+            registry: registry.clone(),
+            ingest_queue_depth: register_int_gauge_with_registry!(
+                "custom_broadcaster_ingest_queue_depth",
+                "Number of transaction outputs waiting in the broadcaster ingestion channel",
+                registry,
+            )
+            .unwrap(),
+            ingested: register_int_counter_with_registry!(
+                "custom_broadcaster_ingested",
+                "Transaction outputs received by the broadcaster ingestion loop",
+                registry,
+            )
+            .unwrap(),
+            // `reason` is "high_water_mark", "full" or "closed"
+            ingest_dropped: register_int_counter_vec_with_registry!(
+                "custom_broadcaster_ingest_dropped",
+                "Transaction outputs dropped before reaching the broadcaster",
+                &["reason"],
+                registry,
+            )
+            .unwrap(),
+            skipped_no_subscribers: register_int_counter_with_registry!(
+                "custom_broadcaster_skipped_no_subscribers",
+                "Transactions not fanned out because no websocket client was connected",
+                registry,
+            )
+            .unwrap(),
+            retention_buffer_len: register_int_gauge_with_registry!(
+                "custom_broadcaster_retention_buffer_len",
+                "Number of transactions held in the replay retention buffer",
+                registry,
+            )
+            .unwrap(),
+            retention_buffer_bytes: register_int_gauge_with_registry!(
+                "custom_broadcaster_retention_buffer_bytes",
+                "Approximate size of the transactions held in the replay retention buffer",
+                registry,
+            )
+            .unwrap(),
+            connected_clients: register_int_gauge_with_registry!(
+                "custom_broadcaster_connected_clients",
+                "Open websocket connections",
+                registry,
+            )
+            .unwrap(),
+            lagged: register_int_counter_with_registry!(
+                "custom_broadcaster_lagged",
+                "Times a websocket connection fell behind the broadcast buffer",
+                registry,
+            )
+            .unwrap(),
+            lagged_skipped: register_int_counter_with_registry!(
+                "custom_broadcaster_lagged_skipped",
+                "Transactions skipped by websocket connections that fell behind",
+                registry,
+            )
+            .unwrap(),
+            slow_disconnects: register_int_counter_with_registry!(
+                "custom_broadcaster_slow_disconnects",
+                "Websocket connections closed for falling behind (disconnect-slowest)",
+                registry,
+            )
+            .unwrap(),
+            ping_timeouts: register_int_counter_with_registry!(
+                "custom_broadcaster_ping_timeouts",
+                "Websocket connections closed for not answering pings",
+                registry,
+            )
+            .unwrap(),
+            dropped_messages: register_int_counter_with_registry!(
+                "custom_broadcaster_dropped_messages",
+                "Stream updates dropped from the full send queues of websocket connections",
+                registry,
+            )
+            .unwrap(),
+            // Totals over all connections by `StreamMessage` variant; per-connection counts are
+            // only in `GetStats` replies
+            messages_sent: register_int_counter_vec_with_registry!(
+                "custom_broadcaster_messages_sent",
+                "Data messages sent to websocket clients",
+                &["type"],
+                registry,
+            )
+            .unwrap(),
+            bytes_sent: register_int_counter_with_registry!(
+                "custom_broadcaster_bytes_sent",
+                "Payload bytes of the data messages sent to websocket clients",
+                registry,
+            )
+            .unwrap(),
+            field_query_latency: register_histogram_with_registry!(
+                "custom_broadcaster_field_query_latency",
+                "Time to read and send the answer to a QueryFieldRange request not served from \
+                 cache, in seconds",
+                QUERY_LATENCY_SEC_BUCKETS.to_vec(),
+                registry,
+            )
+            .unwrap(),
+        }
+    }
+
+    /// The broadcaster's metrics in the Prometheus text format. The registry may be the node's,
+    /// so metrics not registered here are left out.
+    pub(crate) fn render(&self) -> String {
+        let families: Vec<_> = self
+            .registry
+            .gather()
+            .into_iter()
+            .filter(|family| family.name().starts_with(METRIC_PREFIX))
+            .collect();
+        let mut text = Vec::new();
+        TextEncoder::new()
+            .encode(&families, &mut text)
+            .expect("text encoding of gathered metrics does not fail");
+        String::from_utf8(text).expect("metrics text is UTF-8")
+    }
+
+    pub fn new_for_tests() -> Self {
+        Self::new(&Registry::new())
+    }
+}
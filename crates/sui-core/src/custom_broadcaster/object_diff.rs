@@ -0,0 +1,124 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Byte-level diffs of pool objects, for connections with `pool_diffs` set
+//!
+//! Hot pools change a few fields (reserves, price, tick) per transaction, so most of their
+//! bytes are the same from one version to the next. A diff lists the changed byte runs as
+//! splices over the previous version's bytes.
+
+use serde::{Deserialize, Serialize};
+
+use super::{ByteFormat, EncodedBytes};
+
+/// Equal bytes tolerated inside one splice rather than starting a new one, since each splice
+/// costs more than a few bytes of JSON
+const MAX_GAP: usize = 8;
+
+/// Approximate JSON size of a splice without its inserted bytes
+const SPLICE_OVERHEAD: usize = 32;
+
+/// How to rebuild a pool object's bytes from those of an earlier version sent to the client
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolDiff {
+    /// The version whose bytes the splices apply to: the last one sent for the pool
+    pub base_version: u64,
+    /// The version the splices produce
+    pub version: u64,
+    /// Non-overlapping, in increasing `offset` order
+    pub splices: Vec<Splice>,
+}
+
+/// Replace the `delete` bytes at `offset` of the base with `insert`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Splice {
+    pub offset: usize,
+    pub delete: usize,
+    pub insert: EncodedBytes,
+}
+
+impl PoolDiff {
+    /// The diff from `base` to `new`, or `None` if it would not be smaller than `new` itself
+    pub(crate) fn new(
+        base_version: u64,
+        base: &[u8],
+        version: u64,
+        new: &[u8],
+        format: ByteFormat,
+    ) -> Option<Self> {
+        let splices = splices(base, new);
+        let size: usize = splices
+            .iter()
+            .map(|(_, _, insert)| insert.len() + SPLICE_OVERHEAD)
+            .sum();
+        if size >= new.len() {
+            return None;
+        }
+        Some(Self {
+            base_version,
+            version,
+            splices: splices
+                .into_iter()
+                .map(|(offset, delete, insert)| Splice {
+                    offset,
+                    delete,
+                    insert: format.encode(insert),
+                })
+                .collect(),
+        })
+    }
+
+    /// The bytes of `version`, given those of `base_version`
+    pub fn apply(&self, base: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(base.len());
+        let mut cursor = 0;
+        for splice in &self.splices {
+            bytes.extend_from_slice(&base[cursor..splice.offset]);
+            bytes.extend_from_slice(&splice.insert);
+            cursor = splice.offset + splice.delete;
+        }
+        bytes.extend_from_slice(&base[cursor..]);
+        bytes
+    }
+}
+
+/// `(offset, delete, insert)` splices turning `base` into `new`
+///
+/// Objects whose length is unchanged are compared byte for byte, giving one splice per run of
+/// changed bytes. Otherwise the fields after the change have moved, so everything between the
+/// common prefix and the common suffix is a single splice.
+fn splices(base: &[u8], new: &[u8]) -> Vec<(usize, usize, Vec<u8>)> {
+    if base.len() != new.len() {
+        let prefix = base.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = base[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let delete = base.len() - prefix - suffix;
+        return vec![(prefix, delete, new[prefix..new.len() - suffix].to_vec())];
+    }
+
+    let mut splices = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        if base[i] == new[i] {
+            i += 1;
+            continue;
+        }
+        // Extend the run over changed bytes and short gaps of unchanged ones
+        let start = i;
+        let mut end = i + 1;
+        for j in end..new.len() {
+            if base[j] != new[j] {
+                end = j + 1;
+            } else if j - end >= MAX_GAP {
+                break;
+            }
+        }
+        splices.push((start, end - start, new[start..end].to_vec()));
+        i = end;
+    }
+    splices
+}
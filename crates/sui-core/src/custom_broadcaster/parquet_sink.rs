@@ -0,0 +1,251 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Capture of the broadcast stream to Parquet files
+//!
+//! The sink receives the stream like a websocket connection holding the subscriptions of its
+//! configured requests, and writes every message such a connection would be sent as a row:
+//! when it was captured, the transaction digest, the message type and the message's `data`
+//! as JSON. Rows are buffered and written out as a row group once `row-group-rows` of them
+//! accumulate or `flush-interval-ms` passes.
+//!
+//! Each file is written as `part-<start ms>.parquet.tmp` and renamed to `.parquet` when it
+//! is closed, `rotate-interval-ms` after it was started, so that readers of the directory only
+//! ever see complete files. Transactions are captured as they are executed, before they are
+//! assigned to a checkpoint, so files are partitioned by the hour they were started in.
+
+use anyhow::{Context, Result, anyhow, bail};
+use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{
+    fs::{File, create_dir_all, rename},
+    path::PathBuf,
+    sync::Arc,
+};
+use sui_config::broadcaster_config::ParquetSinkConfig;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::MissedTickBehavior,
+};
+use tracing::{error, info, warn};
+
+use super::{
+    AppState, ConnectionOptions, IngestedTransaction, StreamMessage, SubscriptionRequest,
+    Subscriptions, now_ms,
+};
+
+/// Start capturing the stream of `state` as configured by `config`.
+///
+/// Fails if one of the configured requests is not a valid `SubscriptionRequest`, or is
+/// rejected as it would be on a connection.
+pub(crate) fn spawn(state: Arc<AppState>, config: ParquetSinkConfig) -> Result<()> {
+    let mut subscriptions = Subscriptions::new(
+        &ConnectionOptions::default(),
+        state.event_layouts.clone(),
+        state.store.clone(),
+    );
+    subscriptions.key_codecs = state.key_codecs.clone();
+    for request in &config.requests {
+        let parsed: SubscriptionRequest = serde_json::from_str(request)
+            .with_context(|| format!("invalid parquet-sink request {request}"))?;
+        if let Some(StreamMessage::Error { message, .. }) = subscriptions.apply(parsed) {
+            bail!("invalid parquet-sink request {request}: {message}");
+        }
+    }
+    info!(
+        "CustomBroadcaster: Capturing stream to {}",
+        config.directory.display()
+    );
+    tokio::spawn(run(state.tx.subscribe(), subscriptions, config));
+    Ok(())
+}
+
+async fn run(
+    mut rx: broadcast::Receiver<Arc<IngestedTransaction>>,
+    subscriptions: Subscriptions,
+    config: ParquetSinkConfig,
+) {
+    let mut flush = tokio::time::interval(config.flush_interval());
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let row_group_rows = config.row_group_rows;
+    let mut sink = ParquetSink::new(config);
+
+    loop {
+        tokio::select! {
+            res = rx.recv() => match res {
+                Ok(ingested) => {
+                    let captured_ms = now_ms();
+                    let digest = ingested.outputs.transaction.digest().to_string();
+                    for msg in subscriptions.messages_for(&ingested.outputs, ingested.checkpoint) {
+                        sink.push(captured_ms, &digest, &msg);
+                    }
+                    if sink.buffered() >= row_group_rows {
+                        sink = in_blocking(sink, ParquetSink::flush).await;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "CustomBroadcaster: Parquet sink lagged, {} transactions not captured",
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => break,
+            },
+            // Also closes files that are due while no rows arrive
+            _ = flush.tick() => {
+                sink = in_blocking(sink, ParquetSink::flush).await;
+            }
+        }
+    }
+    in_blocking(sink, ParquetSink::close).await;
+}
+
+/// Run a file operation of the sink on the blocking pool
+async fn in_blocking(
+    mut sink: ParquetSink,
+    op: fn(&mut ParquetSink, u64) -> Result<()>,
+) -> ParquetSink {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = op(&mut sink, now_ms()) {
+            error!("CustomBroadcaster: Parquet sink failed: {:#}", e);
+        }
+        sink
+    })
+    .await
+    .expect("parquet sink task panicked")
+}
+
+struct Row {
+    captured_ms: u64,
+    digest: String,
+    message_type: String,
+    data: String,
+}
+
+/// Buffered rows and the file they are written to
+pub(crate) struct ParquetSink {
+    config: ParquetSinkConfig,
+    rows: Vec<Row>,
+    file: Option<PartFile>,
+}
+
+impl ParquetSink {
+    pub(crate) fn new(config: ParquetSinkConfig) -> Self {
+        Self {
+            config,
+            rows: Vec::new(),
+            file: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, captured_ms: u64, digest: &str, msg: &StreamMessage) {
+        // Messages serialize as `{"type": ..., "data": ...}`
+        let mut value = match serde_json::to_value(msg) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("CustomBroadcaster: Failed to serialize message: {}", e);
+                return;
+            }
+        };
+        self.rows.push(Row {
+            captured_ms,
+            digest: digest.to_string(),
+            message_type: value["type"].as_str().unwrap_or_default().to_string(),
+            data: value["data"].take().to_string(),
+        });
+    }
+
+    pub(crate) fn buffered(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Close the current file if it is due, then write the buffered rows as a row group
+    pub(crate) fn flush(&mut self, now_ms: u64) -> Result<()> {
+        let rotate_ms = self.config.rotate_interval_ms;
+        if self
+            .file
+            .as_ref()
+            .is_some_and(|file| now_ms.saturating_sub(file.started_ms) >= rotate_ms)
+        {
+            self.close(now_ms)?;
+        }
+        self.write_rows(now_ms)
+    }
+
+    /// Write out the buffered rows and close the current file
+    pub(crate) fn close(&mut self, now_ms: u64) -> Result<()> {
+        self.write_rows(now_ms)?;
+        if let Some(file) = self.file.take() {
+            file.writer.close()?;
+            rename(&file.tmp_path, &file.path)?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows as one row group, starting a new file if there is none
+    fn write_rows(&mut self, now_ms: u64) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let batch = to_batch(std::mem::take(&mut self.rows))?;
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => PartFile::create(&self.config, &batch, now_ms)?,
+        };
+        file.writer.write(&batch)?;
+        // End the row group here rather than when the writer's own size limit is reached
+        file.writer.flush()?;
+        self.file = Some(file);
+        Ok(())
+    }
+}
+
+fn to_batch(rows: Vec<Row>) -> Result<RecordBatch> {
+    let captured_ms: UInt64Array = rows.iter().map(|row| row.captured_ms).collect();
+    let digest: StringArray = rows.iter().map(|row| Some(row.digest.as_str())).collect();
+    let message_type: StringArray = rows
+        .iter()
+        .map(|row| Some(row.message_type.as_str()))
+        .collect();
+    let data: StringArray = rows.iter().map(|row| Some(row.data.as_str())).collect();
+    Ok(RecordBatch::try_from_iter([
+        ("captured_ms", Arc::new(captured_ms) as ArrayRef),
+        ("digest", Arc::new(digest) as ArrayRef),
+        ("message_type", Arc::new(message_type) as ArrayRef),
+        ("data", Arc::new(data) as ArrayRef),
+    ])?)
+}
+
+/// A file being written, under its temporary name until closed
+struct PartFile {
+    started_ms: u64,
+    tmp_path: PathBuf,
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+}
+
+impl PartFile {
+    fn create(config: &ParquetSinkConfig, batch: &RecordBatch, started_ms: u64) -> Result<Self> {
+        let started = chrono::DateTime::from_timestamp_millis(started_ms as i64)
+            .ok_or_else(|| anyhow!("invalid timestamp {started_ms}"))?;
+        let dir = config
+            .directory
+            .join(started.format("date=%Y-%m-%d").to_string())
+            .join(started.format("hour=%H").to_string());
+        create_dir_all(&dir)?;
+        let path = dir.join(format!("part-{started_ms}.parquet"));
+        let tmp_path = path.with_extension("parquet.tmp");
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer =
+            ArrowWriter::try_new(File::create(&tmp_path)?, batch.schema(), Some(properties))?;
+        Ok(Self {
+            started_ms,
+            tmp_path,
+            path,
+            writer,
+        })
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed decoding of pool objects for `SubscribePool`
+//!
+//! A `PoolUpdate` carries the pool's raw BCS contents, which every client otherwise has to
+//! decode with its own copy of the protocol's struct layout. A [`PoolDecoder`] moves that
+//! layout into the server: connections opting in with `?decode_pools=true` receive a
+//! `PoolState` with the decoded fields instead, for every pool whose type has a decoder in the
+//! [`PoolDecoderRegistry`]. Pools without one, or that fail to decode, are still sent as
+//! `PoolUpdate`.
+
+use move_core_types::language_storage::StructTag;
+use serde::{Deserialize, Serialize, Serializer, de::DeserializeOwned};
+use std::collections::HashMap;
+use sui_types::{
+    balance::Balance,
+    base_types::ObjectID,
+    collection_types::{LinkedTable, Table},
+    object::Object,
+    parse_sui_struct_tag,
+};
+use tracing::debug;
+
+/// The Move struct layout of one protocol's pool objects
+pub trait PoolDecoder {
+    /// Rust mirror of the Move struct, field for field in declaration order, so that it
+    /// decodes from the object's BCS contents
+    type Pool: DeserializeOwned + Serialize;
+
+    /// The pool's type without type parameters, e.g. `0xdee9::clob_v2::Pool`
+    fn type_str() -> &'static str;
+}
+
+type DecodeFn = fn(&[u8]) -> Result<serde_json::Value, String>;
+
+/// Decoders keyed by the pool type they decode, ignoring type parameters
+pub struct PoolDecoderRegistry {
+    decoders: HashMap<StructTag, DecodeFn>,
+}
+
+impl PoolDecoderRegistry {
+    /// A registry without any decoders
+    pub fn empty() -> Self {
+        Self {
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Decode pools of type `D::type_str()` with `D`, replacing any decoder registered before
+    ///
+    /// # Panics
+    /// If `D::type_str()` is not a struct type.
+    pub fn register<D: PoolDecoder>(&mut self) {
+        let tag = parse_sui_struct_tag(D::type_str())
+            .unwrap_or_else(|e| panic!("invalid pool type {}: {e}", D::type_str()));
+        self.decoders.insert(untyped(&tag), decode_as::<D>);
+    }
+
+    /// The decoded fields of `object` and its type, if it is a pool with a decoder
+    pub fn decode(&self, object: &Object) -> Option<(String, serde_json::Value)> {
+        let move_obj = object.data.try_as_move()?;
+        let tag: StructTag = move_obj.type_().clone().into();
+        let decode = self.decoders.get(&untyped(&tag))?;
+        match decode(move_obj.contents()) {
+            Ok(fields) => Some((tag.to_canonical_string(true), fields)),
+            Err(e) => {
+                debug!(
+                    "CustomBroadcaster: Failed to decode pool {}: {}",
+                    object.id(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+impl Default for PoolDecoderRegistry {
+    /// The built-in decoders: [`DeepBookV2Pool`] and [`CetusClmmPool`]
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register::<DeepBookV2Pool>();
+        registry.register::<CetusClmmPool>();
+        registry
+    }
+}
+
+fn untyped(tag: &StructTag) -> StructTag {
+    StructTag {
+        type_params: vec![],
+        ..tag.clone()
+    }
+}
+
+fn decode_as<D: PoolDecoder>(bytes: &[u8]) -> Result<serde_json::Value, String> {
+    let pool: D::Pool = bcs::from_bytes(bytes).map_err(|e| e.to_string())?;
+    serde_json::to_value(&pool).map_err(|e| e.to_string())
+}
+
+/// Renders `u128`s as decimal strings, since JSON numbers lose precision above 2^53.
+fn u128_string<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// DeepBook v2 order book pools, `0xdee9::clob_v2::Pool<Base, Quote>`
+pub struct DeepBookV2Pool;
+
+impl PoolDecoder for DeepBookV2Pool {
+    type Pool = deepbook::Pool;
+
+    fn type_str() -> &'static str {
+        "0xdee9::clob_v2::Pool"
+    }
+}
+
+/// Cetus concentrated liquidity pools, `<cetus clmm>::pool::Pool<CoinA, CoinB>`, as published
+/// at `0x1eabed72c53feb3805120a081dc15963c204dc8d091542592abaf7a35689b2fb`
+pub struct CetusClmmPool;
+
+impl PoolDecoder for CetusClmmPool {
+    type Pool = cetus::Pool;
+
+    fn type_str() -> &'static str {
+        "0x1eabed72c53feb3805120a081dc15963c204dc8d091542592abaf7a35689b2fb::pool::Pool"
+    }
+}
+
+pub mod deepbook {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Pool {
+        pub id: ObjectID,
+        pub bids: CritbitTree,
+        pub asks: CritbitTree,
+        pub next_bid_order_id: u64,
+        pub next_ask_order_id: u64,
+        pub usr_open_orders: Table,
+        pub taker_fee_rate: u64,
+        pub maker_rebate_rate: u64,
+        pub tick_size: u64,
+        pub lot_size: u64,
+        pub base_custodian: Custodian,
+        pub quote_custodian: Custodian,
+        pub creation_fee: Balance,
+        pub base_asset_trading_fees: Balance,
+        pub quote_asset_trading_fees: Balance,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct CritbitTree {
+        pub root: u64,
+        pub internal_nodes: Table,
+        pub leaves: Table,
+        pub min_leaf: u64,
+        pub max_leaf: u64,
+        pub next_internal_node_index: u64,
+        pub next_leaf_index: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Custodian {
+        pub id: ObjectID,
+        pub account_balances: Table,
+    }
+}
+
+pub mod cetus {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Pool {
+        pub id: ObjectID,
+        pub coin_a: Balance,
+        pub coin_b: Balance,
+        pub tick_spacing: u32,
+        pub fee_rate: u64,
+        #[serde(serialize_with = "u128_string")]
+        pub liquidity: u128,
+        #[serde(serialize_with = "u128_string")]
+        pub current_sqrt_price: u128,
+        pub current_tick_index: I32,
+        #[serde(serialize_with = "u128_string")]
+        pub fee_growth_global_a: u128,
+        #[serde(serialize_with = "u128_string")]
+        pub fee_growth_global_b: u128,
+        pub fee_protocol_coin_a: u64,
+        pub fee_protocol_coin_b: u64,
+        pub tick_manager: TickManager,
+        pub rewarder_manager: RewarderManager,
+        pub position_manager: PositionManager,
+        pub is_pause: bool,
+        pub index: u64,
+        pub url: String,
+    }
+
+    /// Two's-complement tick index
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct I32 {
+        pub bits: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct TickManager {
+        pub tick_spacing: u32,
+        pub ticks: SkipList,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct SkipList {
+        pub id: ObjectID,
+        pub head: Vec<OptionU64>,
+        pub tail: OptionU64,
+        pub level: u64,
+        pub max_level: u64,
+        pub list_p: u64,
+        pub size: u64,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct OptionU64 {
+        pub is_none: bool,
+        pub v: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct RewarderManager {
+        pub rewarders: Vec<Rewarder>,
+        #[serde(serialize_with = "u128_string")]
+        pub points_released: u128,
+        #[serde(serialize_with = "u128_string")]
+        pub points_growth_global: u128,
+        pub last_updated_time: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Rewarder {
+        /// `std::type_name::TypeName`
+        pub reward_coin: String,
+        #[serde(serialize_with = "u128_string")]
+        pub emissions_per_second: u128,
+        #[serde(serialize_with = "u128_string")]
+        pub growth_global: u128,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PositionManager {
+        pub tick_spacing: u32,
+        pub position_index: u64,
+        pub positions: LinkedTable<ObjectID>,
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use lru::LruCache;
+use std::{
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use sui_types::base_types::SequenceNumber;
+
+use super::{FieldRangeQuery, StreamMessage};
+
+/// Recent `QueryFieldRange` answers of one connection, so that a client re-issuing the same
+/// query (e.g. on every UI re-render) does not rescan the store.
+///
+/// Only queries pinned to an exact `parent_version` are cached: the fields visible at a given
+/// version never change, so a cached answer is always correct. Queries for the latest version
+/// are never cached. Entries also expire after a short TTL to bound memory held by idle
+/// connections.
+pub(crate) struct QueryCache {
+    entries: LruCache<FieldRangeQuery, (Instant, Arc<[StreamMessage]>)>,
+    ttl: Duration,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+            ttl,
+        }
+    }
+
+    pub(crate) fn get(&mut self, query: &FieldRangeQuery) -> Option<Arc<[StreamMessage]>> {
+        let (inserted, messages) = self.entries.get(query)?;
+        if inserted.elapsed() < self.ttl {
+            return Some(messages.clone());
+        }
+        self.entries.pop(query);
+        None
+    }
+
+    pub(crate) fn insert(&mut self, query: FieldRangeQuery, messages: Vec<StreamMessage>) {
+        if query.parent_version == SequenceNumber::MAX {
+            return;
+        }
+        self.entries.put(query, (Instant::now(), messages.into()));
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rendering of messages in the connection's chosen form
+//!
+//! `ObjectID` and `SuiAddress` always serialize as `0x`-prefixed 32-byte hex. Rather than
+//! rendering each field of every message by hand, messages are serialized through a
+//! serializer that passes everything on unchanged except those two newtypes, which it
+//! recognizes by name wherever they appear: fields, map keys, owners and nested messages.
+//! Hex strings that merely look like addresses (digests, 32-byte contents) are left alone.
+//!
+//! BCS frames go through the same serializer, for a different reason: BCS has no field names,
+//! so a field left out by `skip_serializing_if` would shift every field after it. In binary
+//! formats skipped fields, all `Option`s that are `None` in `StreamMessage`, are written as
+//! `None` instead. Addresses are left to their binary form there.
+
+use serde::{
+    Deserialize, Serialize, Serializer,
+    ser::{
+        SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+
+/// How object IDs and addresses are rendered in every `StreamMessage`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFormat {
+    /// `0x`-prefixed, padded to 32 bytes (e.g. `0x00…02`)
+    #[default]
+    Full,
+    /// `0x`-prefixed, without leading zeros (e.g. `0x2`)
+    Short,
+    /// Padded to 32 bytes, without the `0x` prefix
+    Unprefixed,
+}
+
+impl AddressFormat {
+    /// `full`, an address in its canonical `0x`-prefixed 32-byte form, in this format
+    fn render(self, full: &str) -> String {
+        let hex = full.strip_prefix("0x").unwrap_or(full);
+        match self {
+            AddressFormat::Full => full.to_string(),
+            AddressFormat::Short => match hex.trim_start_matches('0') {
+                "" => "0x0".to_string(),
+                short => format!("0x{short}"),
+            },
+            AddressFormat::Unprefixed => hex.to_string(),
+        }
+    }
+}
+
+/// `value` as JSON, with its object IDs and addresses rendered in `format`
+pub(crate) fn to_json_string<T: Serialize + ?Sized>(
+    value: &T,
+    format: AddressFormat,
+) -> serde_json::Result<String> {
+    if format == AddressFormat::Full {
+        return serde_json::to_string(value);
+    }
+    let mut out = Vec::new();
+    value.serialize(Rendering {
+        inner: &mut serde_json::Serializer::new(&mut out),
+        format,
+    })?;
+    // serde_json only writes valid UTF-8
+    Ok(String::from_utf8(out).expect("serde_json wrote invalid UTF-8"))
+}
+
+/// `value` as BCS, with every field present
+pub(crate) fn to_bcs_bytes<T: Serialize + ?Sized>(value: &T) -> bcs::Result<Vec<u8>> {
+    bcs::to_bytes(&Rendered {
+        value,
+        format: AddressFormat::Full,
+    })
+}
+
+/// Names of the newtypes rendered in the connection's format
+const ADDRESS_NEWTYPES: &[&str] = &["ObjectID", "SuiAddress"];
+
+/// A value to be serialized through [`Rendering`]
+struct Rendered<'a, T: ?Sized> {
+    value: &'a T,
+    format: AddressFormat,
+}
+
+impl<T: Serialize + ?Sized> Serialize for Rendered<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(Rendering {
+            inner: serializer,
+            format: self.format,
+        })
+    }
+}
+
+/// Forwards to `inner`, rendering address newtypes in `format`
+struct Rendering<S> {
+    inner: S,
+    format: AddressFormat,
+}
+
+impl<S> Rendering<S> {
+    fn wrap<'a, T: ?Sized>(&self, value: &'a T) -> Rendered<'a, T> {
+        Rendered {
+            value,
+            format: self.format,
+        }
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<S::Ok, S::Error> {
+                self.inner.$method($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<S: Serializer> Serializer for Rendering<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_some(&value)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        if ADDRESS_NEWTYPES.contains(&name)
+            && self.inner.is_human_readable()
+            && let Ok(serde_json::Value::String(full)) = serde_json::to_value(value)
+        {
+            return self.inner.serialize_str(&self.format.render(&full));
+        }
+        let value = self.wrap(value);
+        self.inner.serialize_newtype_struct(name, &value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        let value = self.wrap(value);
+        self.inner
+            .serialize_newtype_variant(name, index, variant, &value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self.inner.serialize_seq(len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self.inner.serialize_tuple(len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self.inner.serialize_tuple_struct(name, len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self
+            .inner
+            .serialize_tuple_variant(name, index, variant, len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self.inner.serialize_map(len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self.inner.serialize_struct(name, len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        let format = self.format;
+        let human_readable = self.inner.is_human_readable();
+        let inner = self
+            .inner
+            .serialize_struct_variant(name, index, variant, len)?;
+        Ok(Compound {
+            inner,
+            format,
+            human_readable,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Forwards the parts of a compound value to `inner`, each through [`Rendering`]
+struct Compound<C> {
+    inner: C,
+    format: AddressFormat,
+    /// Whether skipped fields may be left out, rather than written as `None`
+    human_readable: bool,
+}
+
+impl<C> Compound<C> {
+    fn wrap<'a, T: ?Sized>(&self, value: &'a T) -> Rendered<'a, T> {
+        Rendered {
+            value,
+            format: self.format,
+        }
+    }
+}
+
+impl<C: SerializeSeq> SerializeSeq for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTuple> SerializeTuple for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_element(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleStruct> SerializeTupleStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeTupleVariant> SerializeTupleVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeMap> SerializeMap for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), C::Error> {
+        let key = self.wrap(key);
+        self.inner.serialize_key(&key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStruct> SerializeStruct for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        if self.human_readable {
+            self.inner.skip_field(key)
+        } else {
+            self.inner.serialize_field(key, &None::<()>)
+        }
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C: SerializeStructVariant> SerializeStructVariant for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), C::Error> {
+        let value = self.wrap(value);
+        self.inner.serialize_field(key, &value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+        if self.human_readable {
+            self.inner.skip_field(key)
+        } else {
+            self.inner.serialize_field(key, &None::<()>)
+        }
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use parking_lot::Mutex;
+use std::{collections::VecDeque, sync::Arc};
+
+use super::ExecutedTransaction;
+use crate::transaction_outputs::TransactionOutputs;
+
+/// Most recently ingested transactions, tagged with their ingestion sequence number.
+///
+/// Entries are `Arc`s shared with the broadcast channel, so retaining them is cheap; the
+/// buffer is bounded by both an entry count and an approximate byte budget. It backs replay
+/// for reconnecting and late-joining clients.
+#[derive(Clone)]
+pub(crate) struct RetentionBuffer {
+    entries: Arc<Mutex<VecDeque<(u64, ExecutedTransaction)>>>,
+    max_entries: usize,
+    max_bytes: Option<usize>,
+    bytes: Arc<Mutex<usize>>,
+}
+
+impl RetentionBuffer {
+    /// A `max_entries` of 0 disables retention.
+    pub(crate) fn new(max_entries: usize, max_bytes: Option<usize>) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(max_entries.min(1024)))),
+            max_entries,
+            max_bytes,
+            bytes: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Append `executed`, evicting the oldest entries to stay within bounds. Returns the number
+    /// of retained entries and their approximate size.
+    pub(crate) fn push(&self, seq: u64, executed: ExecutedTransaction) -> (usize, usize) {
+        if self.max_entries == 0 {
+            return (0, 0);
+        }
+        let mut entries = self.entries.lock();
+        let mut bytes = self.bytes.lock();
+        *bytes += approximate_size(&executed.outputs);
+        entries.push_back((seq, executed));
+
+        while entries.len() > self.max_entries
+            || (entries.len() > 1 && self.max_bytes.is_some_and(|max| *bytes > max))
+        {
+            let (_, evicted) = entries.pop_front().expect("buffer is not empty");
+            *bytes -= approximate_size(&evicted.outputs);
+        }
+        (entries.len(), *bytes)
+    }
+
+    /// Retained entries with a sequence number greater than `after_seq`, oldest first.
+    pub(crate) fn since(&self, after_seq: u64) -> Vec<(u64, ExecutedTransaction)> {
+        let entries = self.entries.lock();
+        let start = entries.partition_point(|(seq, _)| *seq <= after_seq);
+        entries.range(start..).cloned().collect()
+    }
+}
+
+/// Rough memory footprint of a transaction's outputs: written object contents plus event
+/// payloads, which dominate for the transactions clients care about.
+fn approximate_size(outputs: &TransactionOutputs) -> usize {
+    let objects: usize = outputs
+        .written
+        .values()
+        .map(|o| o.object_size_for_gas_metering())
+        .sum();
+    let events: usize = outputs.events.data.iter().map(|e| e.contents.len()).sum();
+    objects + events
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Outbound frames of a websocket connection, written to its socket by a task of its own
+//!
+//! The connection's task queues its frames and goes back to the stream, so that a slow socket
+//! holds up only its writer. When the queue is full, a stream update may take the place of the
+//! oldest queued update (`drop-oldest`); other frames wait for room.
+
+use axum::extract::ws::Message;
+use futures::{Sink, SinkExt};
+use parking_lot::Mutex;
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::Notify;
+
+use super::{BroadcasterMetrics, SentCounters};
+
+/// The writer stopped, the socket having failed
+#[derive(Debug)]
+pub(crate) struct Closed;
+
+pub(crate) struct Outbound {
+    pub(crate) frame: Message,
+    /// The `StreamMessage` variant of a data frame; control frames have none and are not
+    /// counted as sent.
+    pub(crate) message_type: Option<&'static str>,
+    /// Whether the frame may be dropped for a newer one while the queue is full
+    pub(crate) droppable: bool,
+}
+
+struct State {
+    frames: VecDeque<Outbound>,
+    /// Frames dropped since `take_dropped` was last called
+    dropped: u64,
+    /// Set when the writer stops
+    closed: bool,
+    /// Set once no more frames are queued
+    finished: bool,
+}
+
+pub(crate) struct SendQueue {
+    state: Mutex<State>,
+    capacity: usize,
+    drop_oldest: bool,
+    /// Notified when a frame is queued or the queue is finished
+    queued: Notify,
+    /// Notified when a frame is taken or the writer stops
+    taken: Notify,
+}
+
+impl SendQueue {
+    /// A queue of up to `capacity` frames. With `drop_oldest`, droppable frames queued while it
+    /// is full replace the oldest droppable one instead of waiting.
+    pub(crate) fn new(capacity: usize, drop_oldest: bool) -> Self {
+        Self {
+            state: Mutex::new(State {
+                frames: VecDeque::with_capacity(capacity),
+                dropped: 0,
+                closed: false,
+                finished: false,
+            }),
+            capacity,
+            drop_oldest,
+            queued: Notify::new(),
+            taken: Notify::new(),
+        }
+    }
+
+    /// Queue `outbound`, waiting for room unless it can replace an older droppable frame
+    pub(crate) async fn push(&self, outbound: Outbound) -> Result<(), Closed> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                if state.closed {
+                    return Err(Closed);
+                }
+                if state.frames.len() < self.capacity {
+                    state.frames.push_back(outbound);
+                    drop(state);
+                    self.queued.notify_one();
+                    return Ok(());
+                }
+                if self.drop_oldest
+                    && outbound.droppable
+                    && let Some(oldest) = state.frames.iter().position(|queued| queued.droppable)
+                {
+                    // The writer has been notified of the frames already queued
+                    state.frames.remove(oldest);
+                    state.frames.push_back(outbound);
+                    state.dropped += 1;
+                    return Ok(());
+                }
+            }
+            self.taken.notified().await;
+        }
+    }
+
+    /// The number of frames dropped since the last call
+    pub(crate) fn take_dropped(&self) -> u64 {
+        std::mem::take(&mut self.state.lock().dropped)
+    }
+
+    /// Let the writer stop once the queued frames are written
+    pub(crate) fn finish(&self) {
+        self.state.lock().finished = true;
+        self.queued.notify_one();
+    }
+
+    /// The next frame to write, or `None` once the queue is finished and empty
+    async fn next(&self) -> Option<Outbound> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                if let Some(outbound) = state.frames.pop_front() {
+                    drop(state);
+                    self.taken.notify_one();
+                    return Some(outbound);
+                }
+                if state.finished {
+                    return None;
+                }
+            }
+            self.queued.notified().await;
+        }
+    }
+
+    /// Discard the queued frames and fail further pushes
+    fn close(&self) {
+        let mut state = self.state.lock();
+        state.closed = true;
+        state.frames.clear();
+        drop(state);
+        self.taken.notify_one();
+    }
+}
+
+/// Write the frames of `queue` to `sink` until the queue is finished, a `Close` frame is
+/// written or the sink fails, counting the data frames as they are written.
+pub(crate) async fn write_frames<S: Sink<Message> + Unpin>(
+    queue: Arc<SendQueue>,
+    mut sink: S,
+    sent: Arc<SentCounters>,
+    metrics: Arc<BroadcasterMetrics>,
+) {
+    while let Some(Outbound {
+        frame,
+        message_type,
+        ..
+    }) = queue.next().await
+    {
+        let len = match &frame {
+            Message::Text(text) => text.len(),
+            Message::Binary(bytes) => bytes.len(),
+            _ => 0,
+        };
+        let is_close = matches!(frame, Message::Close(_));
+        if sink.send(frame).await.is_err() {
+            break;
+        }
+        if let Some(message_type) = message_type {
+            sent.record(len);
+            metrics
+                .messages_sent
+                .with_label_values(&[message_type])
+                .inc();
+            metrics.bytes_sent.inc_by(len as u64);
+        }
+        if is_close {
+            break;
+        }
+    }
+    queue.close();
+}
@@ -7,6 +7,7 @@ use crate::authority::authority_store::{ExecutionLockWriteGuard, SuiLockResult};
 use crate::authority::backpressure::BackpressureManager;
 use crate::authority::epoch_start_configuration::EpochFlag;
 use crate::authority::epoch_start_configuration::EpochStartConfiguration;
+use crate::custom_broadcaster::BroadcasterSender;
 use crate::global_state_hasher::GlobalStateHashStore;
 use crate::transaction_outputs::TransactionOutputs;
 use either::Either;
@@ -113,8 +114,7 @@ pub fn build_execution_cache(
     prometheus_registry: &Registry,
     store: &Arc<AuthorityStore>,
     backpressure_manager: Arc<BackpressureManager>,
-    // [NEW] Channel for custom broadcaster
-    broadcaster_tx: Option<tokio::sync::mpsc::Sender<Arc<TransactionOutputs>>>,
+    broadcaster_tx: Option<BroadcasterSender>,
 ) -> ExecutionCacheTraitPointers {
     let execution_cache_metrics = Arc::new(ExecutionCacheMetrics::new(prometheus_registry));
 
@@ -601,6 +601,15 @@ pub trait ExecutionCacheWrite: Send + Sync {
     /// in question.
     fn write_transaction_outputs(&self, epoch_id: EpochId, tx_outputs: Arc<TransactionOutputs>);
 
+    /// Same as `write_transaction_outputs`, for a transaction executed as part of the
+    /// certified checkpoint `checkpoint`, which is then known to downstream consumers.
+    fn write_transaction_outputs_in_checkpoint(
+        &self,
+        epoch_id: EpochId,
+        tx_outputs: Arc<TransactionOutputs>,
+        checkpoint: CheckpointSequenceNumber,
+    );
+
     /// Write the output of a Mysticeti fastpath certified transaction.
     /// Such output cannot be written to the dirty cache right away because
     /// the transaction may end up rejected by consensus later. We need to make sure
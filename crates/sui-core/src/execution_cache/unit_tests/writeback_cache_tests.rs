@@ -30,6 +30,7 @@ use tokio::sync::RwLock;
 use super::*;
 use crate::{
     authority::{AuthorityState, AuthorityStore, test_authority_builder::TestAuthorityBuilder},
+    custom_broadcaster::{BroadcasterMetrics, CustomBroadcaster},
     execution_cache::ExecutionCacheAPI,
 };
 
@@ -81,6 +82,7 @@ impl Scenario {
             store.clone(),
             (*METRICS).clone(),
             BackpressureManager::new_for_tests(),
+            None,
         ));
         Self {
             authority,
@@ -384,6 +386,7 @@ impl Scenario {
             self.store.clone(),
             self.cache.metrics.clone(),
             BackpressureManager::new_for_tests(),
+            None,
         ));
 
         // reset the scenario state to match the db
@@ -1183,6 +1186,7 @@ async fn latest_object_cache_race_test() {
         store.clone(),
         (*METRICS).clone(),
         BackpressureManager::new_for_tests(),
+        None,
     ));
 
     let object_id = ObjectID::random();
@@ -1347,3 +1351,31 @@ async fn test_transaction_cache_race() {
     t1.join().unwrap();
     t2.join().unwrap();
 }
+
+#[tokio::test]
+async fn test_broadcaster_receives_checkpoint_of_outputs() {
+    let authority = TestAuthorityBuilder::new().build().await;
+    let store = authority.database_for_testing().clone();
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    let (broadcaster_tx, mut broadcaster_rx) = CustomBroadcaster::channel(4, metrics);
+    let cache = WritebackCache::new(
+        &Default::default(),
+        store,
+        ExecutionCacheMetrics::new(&prometheus::Registry::new()).into(),
+        BackpressureManager::new_for_tests(),
+        Some(broadcaster_tx),
+    );
+
+    // Executed ahead of any checkpoint, e.g. from consensus
+    cache.write_transaction_outputs(1, Arc::new(Scenario::new_outputs()));
+    // Executed from a certified checkpoint
+    ExecutionCacheWrite::write_transaction_outputs_in_checkpoint(
+        &cache,
+        1,
+        Arc::new(Scenario::new_outputs()),
+        7,
+    );
+
+    assert_eq!(broadcaster_rx.try_recv().unwrap().checkpoint, None);
+    assert_eq!(broadcaster_rx.try_recv().unwrap().checkpoint, Some(7));
+}
@@ -45,6 +45,7 @@ use crate::authority::authority_store::{
 use crate::authority::authority_store_tables::LiveObject;
 use crate::authority::backpressure::BackpressureManager;
 use crate::authority::epoch_start_configuration::{EpochFlag, EpochStartConfiguration};
+use crate::custom_broadcaster::BroadcasterSender;
 use crate::fallback_fetch::{do_fallback_lookup, do_fallback_lookup_fallible};
 use crate::global_state_hasher::GlobalStateHashStore;
 use crate::transaction_outputs::TransactionOutputs;
@@ -470,9 +471,8 @@ pub struct WritebackCache {
     backpressure_threshold: u64,
     backpressure_manager: Arc<BackpressureManager>,
     metrics: Arc<ExecutionCacheMetrics>,
-    // [NEW] Channel for custom broadcaster
-    // "A separate async task... should receive updates from the channel"
-    broadcaster_tx: Option<tokio::sync::mpsc::Sender<Arc<TransactionOutputs>>>,
+    // Channel for custom broadcaster
+    broadcaster_tx: Option<BroadcasterSender>,
 }
 
 macro_rules! check_cache_entry_by_version {
@@ -518,7 +518,7 @@ impl WritebackCache {
         store: Arc<AuthorityStore>,
         metrics: Arc<ExecutionCacheMetrics>,
         backpressure_manager: Arc<BackpressureManager>,
-        broadcaster_tx: Option<tokio::sync::mpsc::Sender<Arc<TransactionOutputs>>>,
+        broadcaster_tx: Option<BroadcasterSender>,
     ) -> Self {
         let packages = MokaCache::builder(8)
             .max_capacity(randomize_cache_capacity_in_tests(
@@ -917,22 +917,23 @@ impl WritebackCache {
         &self.store
     }
 
-    #[instrument(level = "debug", skip_all)]
     fn write_transaction_outputs(&self, epoch_id: EpochId, tx_outputs: Arc<TransactionOutputs>) {
-        // [NEW] Broadcast hook
-        // "Identify the module... insert a hook that sends the state update"
-        // "This hook MUST NOT: block, await, panic"
+        self.write_outputs(epoch_id, tx_outputs, None);
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    fn write_outputs(
+        &self,
+        epoch_id: EpochId,
+        tx_outputs: Arc<TransactionOutputs>,
+        checkpoint: Option<CheckpointSequenceNumber>,
+    ) {
+        // Hand the outputs to the custom broadcaster. Sending never blocks, so a slow
+        // broadcaster drops updates rather than stalling execution.
         if let Some(tx) = &self.broadcaster_tx {
-            // try_send is non-blocking. If full, it returns error (dropped).
-            // "If the channel is full, the update should be dropped with a log warning."
-            if let Err(e) = tx.try_send(tx_outputs.clone()) {
-                use tokio::sync::mpsc::error::TrySendError;
-                match e {
-                    TrySendError::Full(_) => {
-                        tracing::warn!("CustomBroadcaster channel full, dropping update")
-                    }
-                    TrySendError::Closed(_) => tracing::warn!("CustomBroadcaster channel closed"),
-                }
+            match checkpoint {
+                Some(checkpoint) => tx.try_send_in_checkpoint(tx_outputs.clone(), checkpoint),
+                None => tx.try_send(tx_outputs.clone()),
             }
         }
 
@@ -2323,6 +2324,15 @@ impl ExecutionCacheWrite for WritebackCache {
         WritebackCache::write_transaction_outputs(self, epoch_id, tx_outputs);
     }
 
+    fn write_transaction_outputs_in_checkpoint(
+        &self,
+        epoch_id: EpochId,
+        tx_outputs: Arc<TransactionOutputs>,
+        checkpoint: CheckpointSequenceNumber,
+    ) {
+        self.write_outputs(epoch_id, tx_outputs, Some(checkpoint));
+    }
+
     fn write_fastpath_transaction_outputs(&self, tx_outputs: Arc<TransactionOutputs>) {
         let tx_digest = *tx_outputs.transaction.digest();
         debug!(
@@ -0,0 +1,134 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memoized field ID derivations
+//!
+//! Deriving a field ID BCS-encodes the key and hashes it together with the table ID and the
+//! key's type. A range re-read around a moving index (a pool's current tick on every update)
+//! derives mostly the same IDs each time; a [`FieldIdCache`] shared by those queries derives
+//! each of them once.
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::fmt;
+use std::num::NonZeroUsize;
+use sui_types::{TypeTag, base_types::ObjectID, error::SuiResult};
+
+use super::KeyCodec;
+
+/// The most recently used field IDs, by table, key type and index
+pub struct FieldIdCache {
+    ids: Mutex<LruCache<(ObjectID, TypeTag, u64), ObjectID>>,
+}
+
+impl FieldIdCache {
+    /// A cache of up to `capacity` field IDs
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            ids: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// ID of the dynamic field of `table_id` at `index` under `codec`, derived on a miss
+    pub fn field_id(
+        &self,
+        table_id: ObjectID,
+        index: u64,
+        codec: &dyn KeyCodec,
+    ) -> SuiResult<ObjectID> {
+        let key = (table_id, codec.type_tag(), index);
+        if let Some(field_id) = self.ids.lock().get(&key) {
+            return Ok(*field_id);
+        }
+        // Derived outside the lock; a concurrent miss on the same key derives the same ID
+        let field_id = codec.field_id(table_id, index)?;
+        self.ids.lock().put(key, field_id);
+        Ok(field_id)
+    }
+
+    /// Number of field IDs held
+    pub fn len(&self) -> usize {
+        self.ids.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for FieldIdCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = self.ids.lock();
+        f.debug_struct("FieldIdCache")
+            .field("len", &ids.len())
+            .field("capacity", &ids.cap())
+            .finish()
+    }
+}
+
+/// `codec`, with its field IDs looked up in `cache`
+pub(super) struct CachedCodec<'a> {
+    pub(super) codec: &'a dyn KeyCodec,
+    pub(super) cache: &'a FieldIdCache,
+}
+
+impl KeyCodec for CachedCodec<'_> {
+    fn type_tag(&self) -> TypeTag {
+        self.codec.type_tag()
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        self.codec.encode(index)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        self.codec.decode(bytes)
+    }
+
+    fn field_id(&self, table_id: ObjectID, index: u64) -> SuiResult<ObjectID> {
+        self.cache.field_id(table_id, index, self.codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_data_query::{U64Codec, U128Codec};
+
+    #[test]
+    fn test_cached_ids_match_derivation() {
+        let cache = FieldIdCache::new(NonZeroUsize::new(4).unwrap());
+        let table_id = ObjectID::random();
+        for _ in 0..2 {
+            for index in 0..3 {
+                assert_eq!(
+                    cache.field_id(table_id, index, &U64Codec).unwrap(),
+                    U64Codec.field_id(table_id, index).unwrap()
+                );
+            }
+        }
+        assert_eq!(cache.len(), 3);
+
+        // The key type is part of the entry
+        assert_eq!(
+            cache.field_id(table_id, 0, &U128Codec).unwrap(),
+            U128Codec.field_id(table_id, 0).unwrap()
+        );
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn test_least_recently_used_id_is_evicted() {
+        let cache = FieldIdCache::new(NonZeroUsize::new(2).unwrap());
+        let table_id = ObjectID::random();
+        cache.field_id(table_id, 1, &U64Codec).unwrap();
+        cache.field_id(table_id, 2, &U64Codec).unwrap();
+        cache.field_id(table_id, 1, &U64Codec).unwrap();
+        cache.field_id(table_id, 3, &U64Codec).unwrap();
+
+        let ids = cache.ids.lock();
+        assert!(ids.contains(&(table_id, TypeTag::U64, 1)));
+        assert!(!ids.contains(&(table_id, TypeTag::U64, 2)));
+        assert!(ids.contains(&(table_id, TypeTag::U64, 3)));
+    }
+}
@@ -0,0 +1,556 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Key encodings for the tables scanned by range queries
+//!
+//! Range queries walk a table by integer index, but tables key their fields by many Move
+//! types. A [`KeyCodec`] maps an index to the BCS bytes of the corresponding key (and back),
+//! together with the key's `TypeTag`, which is part of the dynamic field ID derivation. Using
+//! the wrong codec derives IDs of fields that do not exist, so a query silently finds nothing.
+//!
+//! Keys that do not map onto indices are read by value instead, through [`FieldKey`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use sui_types::{
+    TypeTag,
+    base_types::{ObjectID, SuiAddress, move_utf8_str_layout},
+    dynamic_field::derive_dynamic_field_id,
+    error::{SuiErrorKind, SuiResult},
+};
+
+/// Encoding of a table's keys in terms of range query indices
+pub trait KeyCodec: Send + Sync {
+    /// Move type of the table's keys
+    fn type_tag(&self) -> TypeTag;
+
+    /// BCS bytes of the key at `index`
+    fn encode(&self, index: u64) -> Vec<u8>;
+
+    /// The index of the key BCS-encoded at the start of `bytes`, or `None` if they do not
+    /// start with a key of this codec. Trailing bytes (e.g. a field's value) are ignored.
+    fn decode(&self, bytes: &[u8]) -> Option<u64>;
+
+    /// ID of the dynamic field of `table_id` at `index`, derived the same way Move does
+    fn field_id(&self, table_id: ObjectID, index: u64) -> SuiResult<ObjectID> {
+        derive_field_id(table_id, &self.type_tag(), &self.encode(index))
+    }
+}
+
+/// ID of the dynamic field of `table_id` under the key `key_bytes` of type `type_tag`
+fn derive_field_id(
+    table_id: ObjectID,
+    type_tag: &TypeTag,
+    key_bytes: &[u8],
+) -> SuiResult<ObjectID> {
+    let field_id = derive_dynamic_field_id(table_id, type_tag, key_bytes).map_err(|e| {
+        SuiErrorKind::ObjectSerializationError {
+            error: format!("BCS error: {}", e),
+        }
+    })?;
+    Ok(field_id)
+}
+
+/// `u64` keys
+#[derive(Debug, Clone, Copy, Default)]
+pub struct U64Codec;
+
+impl KeyCodec for U64Codec {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::U64
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        index.to_le_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        Some(u64::from_le_bytes(bytes.get(..8)?.try_into().ok()?))
+    }
+}
+
+/// `u32` keys; only the first 2^32 indices are addressable
+///
+/// Higher indices have no key: deriving their field ID fails, so that a range reaching past
+/// `u32::MAX` records them as errors instead of reading the fields of their low 32 bits.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct U32Codec;
+
+impl KeyCodec for U32Codec {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::U32
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        (index as u32).to_le_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as u64)
+    }
+
+    fn field_id(&self, table_id: ObjectID, index: u64) -> SuiResult<ObjectID> {
+        let key = u32::try_from(index).map_err(|_| SuiErrorKind::ObjectSerializationError {
+            error: format!("index {index} does not fit in a u32 key"),
+        })?;
+        derive_field_id(table_id, &TypeTag::U32, &key.to_le_bytes())
+    }
+}
+
+/// `u128` keys; only the first 2^64 keys are addressable by index
+#[derive(Debug, Clone, Copy, Default)]
+pub struct U128Codec;
+
+impl KeyCodec for U128Codec {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::U128
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        (index as u128).to_le_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        u128::from_le_bytes(bytes.get(..16)?.try_into().ok()?)
+            .try_into()
+            .ok()
+    }
+}
+
+/// `address` keys, with the index in the low 8 bytes (index 5 is address `0x5`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AddressCodec;
+
+impl KeyCodec for AddressCodec {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::Address
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        let mut bytes = [0u8; SuiAddress::LENGTH];
+        bytes[SuiAddress::LENGTH - 8..].copy_from_slice(&index.to_be_bytes());
+        bytes.to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        let address = bytes.get(..SuiAddress::LENGTH)?;
+        let (high, low) = address.split_at(SuiAddress::LENGTH - 8);
+        if high.iter().any(|b| *b != 0) {
+            return None;
+        }
+        Some(u64::from_be_bytes(low.try_into().ok()?))
+    }
+}
+
+/// `0x1::string::String` keys holding the decimal index (index 5 is `"5"`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StringCodec;
+
+impl KeyCodec for StringCodec {
+    fn type_tag(&self) -> TypeTag {
+        TypeTag::Struct(Box::new(move_utf8_str_layout().type_))
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        bcs::to_bytes(&index.to_string()).expect("strings are always serializable")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        // A u64 has at most 20 decimal digits, so the ULEB128 length is a single byte
+        let (&len, rest) = bytes.split_first()?;
+        let digits = std::str::from_utf8(rest.get(..len as usize)?).ok()?;
+        digits.parse().ok()
+    }
+}
+
+/// Keys wrapping a two's-complement signed integer, such as `I32 { bits: u32 }` tick indices
+///
+/// The index holds the integer's bits, as produced by
+/// [`tick_to_index`](super::tick_to_index) for 32-bit ticks. The wrapper's type is defined by
+/// each protocol's package, so it has to be given.
+#[derive(Debug, Clone)]
+pub struct SignedIntCodec {
+    type_tag: TypeTag,
+    width: SignedIntWidth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SignedIntWidth {
+    I32,
+    I64,
+}
+
+impl SignedIntCodec {
+    /// Keys of type `type_tag`, a struct wrapping a single `u32`
+    pub fn i32(type_tag: TypeTag) -> Self {
+        Self {
+            type_tag,
+            width: SignedIntWidth::I32,
+        }
+    }
+
+    /// Keys of type `type_tag`, a struct wrapping a single `u64`
+    pub fn i64(type_tag: TypeTag) -> Self {
+        Self {
+            type_tag,
+            width: SignedIntWidth::I64,
+        }
+    }
+}
+
+impl KeyCodec for SignedIntCodec {
+    fn type_tag(&self) -> TypeTag {
+        self.type_tag.clone()
+    }
+
+    fn encode(&self, index: u64) -> Vec<u8> {
+        match self.width {
+            SignedIntWidth::I32 => (index as u32).to_le_bytes().to_vec(),
+            SignedIntWidth::I64 => index.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Option<u64> {
+        match self.width {
+            SignedIntWidth::I32 => {
+                Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as u64)
+            }
+            SignedIntWidth::I64 => U64Codec.decode(bytes),
+        }
+    }
+}
+
+/// The built-in codec for keys of type `type_tag`, if there is one
+///
+/// Structs named `I32` or `I64` are taken to be signed integer wrappers; other structs
+/// (except `0x1::string::String`) have no built-in codec.
+pub fn codec_for_type(type_tag: &TypeTag) -> Option<Arc<dyn KeyCodec>> {
+    let codec: Arc<dyn KeyCodec> = match type_tag {
+        TypeTag::U32 => Arc::new(U32Codec),
+        TypeTag::U64 => Arc::new(U64Codec),
+        TypeTag::U128 => Arc::new(U128Codec),
+        TypeTag::Address => Arc::new(AddressCodec),
+        t if *t == StringCodec.type_tag() => Arc::new(StringCodec),
+        TypeTag::Struct(tag) if tag.type_params.is_empty() => match tag.name.as_str() {
+            "I32" => Arc::new(SignedIntCodec::i32(type_tag.clone())),
+            "I64" => Arc::new(SignedIntCodec::i64(type_tag.clone())),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(codec)
+}
+
+/// Codecs selected per table, falling back to a default (`u64` unless set otherwise)
+#[derive(Clone)]
+pub struct KeyCodecRegistry {
+    tables: HashMap<ObjectID, Arc<dyn KeyCodec>>,
+    default: Arc<dyn KeyCodec>,
+}
+
+impl KeyCodecRegistry {
+    pub fn new(default: Arc<dyn KeyCodec>) -> Self {
+        Self {
+            tables: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Use `codec` for the keys of `table_id`, replacing any codec registered before
+    pub fn register(&mut self, table_id: ObjectID, codec: Arc<dyn KeyCodec>) {
+        self.tables.insert(table_id, codec);
+    }
+
+    /// The codec for the keys of `table_id`
+    pub fn codec_for(&self, table_id: &ObjectID) -> Arc<dyn KeyCodec> {
+        self.tables.get(table_id).unwrap_or(&self.default).clone()
+    }
+}
+
+impl Default for KeyCodecRegistry {
+    fn default() -> Self {
+        Self::new(Arc::new(U64Codec))
+    }
+}
+
+/// A key value of a table, for reading the field under a key rather than at an index
+pub trait FieldKey {
+    /// BCS bytes of the key
+    fn to_key_bytes(&self) -> Result<Vec<u8>, bcs::Error>;
+
+    /// Move type of the key
+    fn key_type(&self) -> TypeTag;
+
+    /// ID of the dynamic field of `table_id` under this key, derived the same way Move does
+    fn field_id(&self, table_id: ObjectID) -> SuiResult<ObjectID> {
+        let bcs_error = |e: bcs::Error| SuiErrorKind::ObjectSerializationError {
+            error: format!("BCS error: {}", e),
+        };
+        let key_bytes = self.to_key_bytes().map_err(bcs_error)?;
+        let field_id =
+            derive_dynamic_field_id(table_id, &self.key_type(), &key_bytes).map_err(bcs_error)?;
+        Ok(field_id)
+    }
+}
+
+/// The key at `index` under `codec`, for reading a table by index as by key
+pub(super) struct IndexKey<'a> {
+    pub(super) codec: &'a dyn KeyCodec,
+    pub(super) index: u64,
+}
+
+impl FieldKey for IndexKey<'_> {
+    fn to_key_bytes(&self) -> Result<Vec<u8>, bcs::Error> {
+        Ok(self.codec.encode(self.index))
+    }
+
+    fn key_type(&self) -> TypeTag {
+        self.codec.type_tag()
+    }
+
+    // Through the codec, which may refuse the index or have its ID cached
+    fn field_id(&self, table_id: ObjectID) -> SuiResult<ObjectID> {
+        self.codec.field_id(table_id, self.index)
+    }
+}
+
+macro_rules! primitive_field_key {
+    ($($ty:ty => $tag:ident),* $(,)?) => {$(
+        impl FieldKey for $ty {
+            fn to_key_bytes(&self) -> Result<Vec<u8>, bcs::Error> {
+                bcs::to_bytes(self)
+            }
+
+            fn key_type(&self) -> TypeTag {
+                TypeTag::$tag
+            }
+        }
+    )*};
+}
+
+primitive_field_key!(u32 => U32, u64 => U64, u128 => U128, SuiAddress => Address);
+
+/// An `I32 { bits: u32 }` key holding a signed integer, such as a tick index
+///
+/// The wrapper's type is defined by each protocol's package, so it has to be given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct I32 {
+    pub type_tag: TypeTag,
+    pub value: i32,
+}
+
+impl I32 {
+    pub fn new(type_tag: TypeTag, value: i32) -> Self {
+        Self { type_tag, value }
+    }
+}
+
+impl FieldKey for I32 {
+    fn to_key_bytes(&self) -> Result<Vec<u8>, bcs::Error> {
+        bcs::to_bytes(&(self.value as u32))
+    }
+
+    fn key_type(&self) -> TypeTag {
+        self.type_tag.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_data_query::{index_to_tick, tick_to_index};
+
+    /// Every codec must agree with BCS and round-trip its indices
+    #[test]
+    fn test_codecs_match_bcs() {
+        let index = 1234u64;
+        let address = SuiAddress::from_bytes({
+            let mut bytes = [0u8; 32];
+            bytes[24..].copy_from_slice(&index.to_be_bytes());
+            bytes
+        })
+        .unwrap();
+        let i32_type: TypeTag = "0x42::i32::I32".parse().unwrap();
+
+        let cases: Vec<(Box<dyn KeyCodec>, Vec<u8>)> = vec![
+            (Box::new(U32Codec), bcs::to_bytes(&(index as u32)).unwrap()),
+            (Box::new(U64Codec), bcs::to_bytes(&index).unwrap()),
+            (
+                Box::new(U128Codec),
+                bcs::to_bytes(&(index as u128)).unwrap(),
+            ),
+            (Box::new(AddressCodec), bcs::to_bytes(&address).unwrap()),
+            (Box::new(StringCodec), bcs::to_bytes("1234").unwrap()),
+            (
+                Box::new(SignedIntCodec::i32(i32_type)),
+                bcs::to_bytes(&1234u32).unwrap(),
+            ),
+        ];
+        for (codec, expected) in cases {
+            assert_eq!(codec.encode(index), expected, "{}", codec.type_tag());
+            let mut with_value = expected.clone();
+            with_value.extend_from_slice(&[0xff; 8]);
+            assert_eq!(
+                codec.decode(&with_value),
+                Some(index),
+                "{}",
+                codec.type_tag()
+            );
+        }
+    }
+
+    /// Field IDs must be those Move derives from the key's type and value
+    #[test]
+    fn test_field_ids_match_dynamic_field_derivation() {
+        let table_id = ObjectID::random();
+        let index = 1u64 << 40;
+        let address = SuiAddress::from_bytes({
+            let mut bytes = [0u8; 32];
+            bytes[24..].copy_from_slice(&index.to_be_bytes());
+            bytes
+        })
+        .unwrap();
+
+        let cases: Vec<(Box<dyn KeyCodec>, TypeTag, Vec<u8>)> = vec![
+            (
+                Box::new(U64Codec),
+                TypeTag::U64,
+                bcs::to_bytes(&index).unwrap(),
+            ),
+            (
+                Box::new(U128Codec),
+                TypeTag::U128,
+                bcs::to_bytes(&(index as u128)).unwrap(),
+            ),
+            (
+                Box::new(AddressCodec),
+                TypeTag::Address,
+                bcs::to_bytes(&address).unwrap(),
+            ),
+        ];
+        for (codec, key_type, key_bcs) in cases {
+            let expected = derive_dynamic_field_id(table_id, &key_type, &key_bcs).unwrap();
+            assert_eq!(
+                codec.field_id(table_id, index).unwrap(),
+                expected,
+                "{key_type}"
+            );
+        }
+        // Keys of different types never share a field
+        assert_ne!(
+            U64Codec.field_id(table_id, index).unwrap(),
+            U128Codec.field_id(table_id, index).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_signed_int_codec_negative_ticks() {
+        let codec = SignedIntCodec::i32("0x42::i32::I32".parse().unwrap());
+        let index = tick_to_index(-5);
+        assert_eq!(codec.encode(index), bcs::to_bytes(&(-5i32 as u32)).unwrap());
+        assert_eq!(
+            index_to_tick(codec.decode(&codec.encode(index)).unwrap()),
+            -5
+        );
+    }
+
+    #[test]
+    fn test_codec_for_type() {
+        let i32_type: TypeTag = "0x42::i32::I32".parse().unwrap();
+        assert_eq!(
+            codec_for_type(&i32_type).unwrap().type_tag(),
+            i32_type,
+            "signed int wrappers keep their own type"
+        );
+        assert_eq!(
+            codec_for_type(&"0x1::string::String".parse().unwrap())
+                .unwrap()
+                .encode(7),
+            bcs::to_bytes("7").unwrap()
+        );
+        assert!(codec_for_type(&"0x42::pool::Key".parse().unwrap()).is_none());
+        assert!(codec_for_type(&TypeTag::Bool).is_none());
+        assert_eq!(
+            codec_for_type(&TypeTag::U32).unwrap().encode(7),
+            bcs::to_bytes(&7u32).unwrap()
+        );
+    }
+
+    /// Every key must encode as BCS does and derive the field IDs Move does
+    #[test]
+    fn test_field_keys() {
+        let table_id = ObjectID::random();
+        let address = SuiAddress::random_for_testing_only();
+        let i32_type: TypeTag = "0x42::i32::I32".parse().unwrap();
+
+        let cases: Vec<(Box<dyn FieldKey>, TypeTag, Vec<u8>)> = vec![
+            (Box::new(7u32), TypeTag::U32, bcs::to_bytes(&7u32).unwrap()),
+            (Box::new(7u64), TypeTag::U64, bcs::to_bytes(&7u64).unwrap()),
+            (
+                Box::new(7u128),
+                TypeTag::U128,
+                bcs::to_bytes(&7u128).unwrap(),
+            ),
+            (
+                Box::new(address),
+                TypeTag::Address,
+                bcs::to_bytes(&address).unwrap(),
+            ),
+            (
+                Box::new(I32::new(i32_type.clone(), -5)),
+                i32_type,
+                bcs::to_bytes(&(-5i32 as u32)).unwrap(),
+            ),
+        ];
+        for (key, key_type, key_bcs) in cases {
+            assert_eq!(key.key_type(), key_type);
+            assert_eq!(key.to_key_bytes().unwrap(), key_bcs, "{key_type}");
+            let expected = derive_dynamic_field_id(table_id, &key_type, &key_bcs).unwrap();
+            assert_eq!(key.field_id(table_id).unwrap(), expected, "{key_type}");
+        }
+    }
+
+    #[test]
+    fn test_u32_codec_rejects_wide_indices() {
+        let table_id = ObjectID::random();
+        assert!(U32Codec.field_id(table_id, u32::MAX as u64).is_ok());
+        assert!(U32Codec.field_id(table_id, u32::MAX as u64 + 1).is_err());
+        // Rather than aliasing index 0
+        assert!(U32Codec.field_id(table_id, 1 << 32).is_err());
+    }
+
+    /// Keys and codecs of the same type find the same fields
+    #[test]
+    fn test_field_keys_agree_with_codecs() {
+        let table_id = ObjectID::random();
+        let i32_type: TypeTag = "0x42::i32::I32".parse().unwrap();
+        assert_eq!(
+            7u32.field_id(table_id).unwrap(),
+            U32Codec.field_id(table_id, 7).unwrap()
+        );
+        assert_eq!(
+            7u64.field_id(table_id).unwrap(),
+            U64Codec.field_id(table_id, 7).unwrap()
+        );
+        assert_eq!(
+            I32::new(i32_type.clone(), -5).field_id(table_id).unwrap(),
+            SignedIntCodec::i32(i32_type)
+                .field_id(table_id, tick_to_index(-5))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_registry_falls_back_to_default() {
+        let table = ObjectID::random();
+        let mut registry = KeyCodecRegistry::default();
+        assert_eq!(registry.codec_for(&table).type_tag(), TypeTag::U64);
+
+        registry.register(table, Arc::new(U128Codec));
+        assert_eq!(registry.codec_for(&table).type_tag(), TypeTag::U128);
+        assert_eq!(
+            registry.codec_for(&ObjectID::random()).type_tag(),
+            TypeTag::U64
+        );
+    }
+}
@@ -0,0 +1,3530 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use move_core_types::language_storage::StructTag;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use sui_test_transaction_builder::TestTransactionBuilder;
+use sui_types::{
+    TypeTag,
+    base_types::{FullObjectRef, SequenceNumber, random_object_ref},
+    crypto::{AccountKeyPair, get_key_pair_from_rng},
+    digests::TransactionDigest,
+    effects::TestEffectsBuilder,
+    event::Event,
+    execution_status::ExecutionFailureStatus,
+    object::{MoveObject, OBJECT_START_VERSION, Object},
+    transaction::VerifiedTransaction,
+};
+
+use super::*;
+
+fn new_outputs() -> TransactionOutputs {
+    build_outputs(|effects| effects)
+}
+
+/// Builds outputs for a dummy transfer, letting the caller add object changes to the effects.
+fn build_outputs(
+    effects: impl FnOnce(TestEffectsBuilder) -> TestEffectsBuilder,
+) -> TransactionOutputs {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let (sender, keypair): (SuiAddress, AccountKeyPair) = get_key_pair_from_rng(&mut rng);
+    let (receiver, _): (SuiAddress, AccountKeyPair) = get_key_pair_from_rng(&mut rng);
+
+    let tx = TestTransactionBuilder::new(sender, random_object_ref(), 100)
+        .transfer(
+            FullObjectRef::from_fastpath_ref(random_object_ref()),
+            receiver,
+        )
+        .build_and_sign(&keypair);
+    let tx = VerifiedTransaction::new_unchecked(tx);
+    let effects = effects(TestEffectsBuilder::new(tx.inner())).build();
+
+    TransactionOutputs {
+        transaction: Arc::new(tx),
+        effects,
+        events: Default::default(),
+        unchanged_loaded_runtime_objects: Default::default(),
+        accumulator_events: Default::default(),
+        markers: Default::default(),
+        wrapped: Default::default(),
+        deleted: Default::default(),
+        locks_to_delete: Default::default(),
+        new_locks_to_init: Default::default(),
+        written: Default::default(),
+        output_keys: Default::default(),
+    }
+}
+
+fn new_object(id: ObjectID, owner: Owner) -> Object {
+    Object::new_move(
+        MoveObject::new_gas_coin(OBJECT_START_VERSION, id, 100),
+        owner,
+        TransactionDigest::ZERO,
+    )
+}
+
+fn sender_of(outputs: &TransactionOutputs) -> SuiAddress {
+    outputs.transaction.sender_address()
+}
+
+#[tokio::test]
+async fn test_checkpoint_given_by_producer_is_sent() {
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    let (tx, mut rx) = CustomBroadcaster::channel(4, metrics.clone());
+    let state = AppState::new(&BroadcasterConfig::default(), None, metrics);
+    let mut fanned_out = state.tx.subscribe();
+    let outputs = Arc::new(new_outputs());
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeAccount(sender_of(&outputs)));
+
+    tx.try_send_in_checkpoint(outputs.clone(), 42);
+    tx.try_send(outputs);
+    for expected in [serde_json::json!(42), serde_json::Value::Null] {
+        ingest(&state, rx.recv().await.unwrap()).await;
+        let ingested = fanned_out.recv().await.unwrap();
+        let payloads = subscriptions.payloads_for(&ingested);
+        let [(_, Message::Text(text))] = &payloads[..] else {
+            panic!("unexpected payloads {payloads:?}");
+        };
+        let msg: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(msg["type"], "AccountActivity");
+        assert_eq!(msg["data"]["checkpoint"], expected);
+    }
+    // Retained along with the transaction
+    let retained: Vec<_> = state
+        .retention
+        .since(0)
+        .into_iter()
+        .map(|(_, executed)| executed.checkpoint)
+        .collect();
+    assert_eq!(retained, [Some(42), None]);
+}
+
+#[tokio::test]
+async fn test_resume_sends_checkpoint_manifests() {
+    use futures::SinkExt;
+    use sui_protocol_config::ProtocolConfig;
+    use sui_types::committee::Committee;
+    use sui_types::messages_checkpoint::{
+        CertifiedCheckpointSummary, CheckpointContents, CheckpointSummary, VerifiedCheckpoint,
+    };
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let mut state = AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    );
+    // Checkpoint 5 holds a transaction the broadcaster never saw besides the two it did
+    let in_checkpoint = [Arc::new(new_outputs()), Arc::new(new_outputs())];
+    let unseen = new_outputs();
+    let contents = CheckpointContents::new_with_digests_only_for_tests(
+        [&in_checkpoint[0], &in_checkpoint[1], &unseen]
+            .iter()
+            .map(|outputs| outputs.effects.execution_digests()),
+    );
+    let summary = CheckpointSummary::new(
+        &ProtocolConfig::get_for_max_version_UNSAFE(),
+        0,
+        5,
+        3,
+        &contents,
+        None,
+        Default::default(),
+        None,
+        0,
+        vec![],
+        vec![],
+    );
+    let (committee, keys) = Committee::new_simple_test_committee();
+    let checkpoint = VerifiedCheckpoint::new_unchecked(
+        CertifiedCheckpointSummary::new_from_keypairs_for_testing(summary, &keys, &committee),
+    );
+    let checkpoint_store = CheckpointStore::new_for_tests();
+    checkpoint_store
+        .insert_verified_checkpoint(&checkpoint)
+        .unwrap();
+    checkpoint_store
+        .insert_checkpoint_contents(contents)
+        .unwrap();
+    state.checkpoint_store = Some(checkpoint_store);
+    let state = Arc::new(state);
+
+    for outputs in &in_checkpoint {
+        let executed = ExecutedTransaction {
+            outputs: outputs.clone(),
+            checkpoint: Some(5),
+        };
+        ingest(&state, executed).await;
+    }
+    // One without a checkpoint, and one of a checkpoint not in the store
+    ingest(&state, Arc::new(new_outputs()).into()).await;
+    let executed = ExecutedTransaction {
+        outputs: Arc::new(new_outputs()),
+        checkpoint: Some(6),
+    };
+    ingest(&state, executed).await;
+
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+    let sender = sender_of(&in_checkpoint[0]);
+    for request in [
+        format!(r#"{{"SubscribeAccount":"{sender}"}}"#),
+        r#"{"Resume":{"after_seq":0,"manifests":true}}"#.to_string(),
+    ] {
+        client.send(WsMessage::text(request)).await.unwrap();
+    }
+    assert_eq!(next_json(&mut client).await["type"], "Subscribed");
+
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "CheckpointManifest");
+    assert_eq!(msg["data"]["sequence_number"], 5);
+    let digests: Vec<String> = [&in_checkpoint[0], &in_checkpoint[1], &unseen]
+        .iter()
+        .map(|outputs| outputs.transaction.digest().to_string())
+        .collect();
+    assert_eq!(msg["data"]["digests"], serde_json::json!(digests));
+    // Once for the checkpoint, followed by its transactions
+    for (digest, checkpoint) in [(&digests[0], 5), (&digests[1], 5)] {
+        let msg = next_json(&mut client).await;
+        assert_eq!(msg["type"], "AccountActivity");
+        assert_eq!(msg["data"]["digest"], *digest);
+        assert_eq!(msg["data"]["checkpoint"], checkpoint);
+    }
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "AccountActivity");
+    assert_eq!(msg["data"]["checkpoint"], serde_json::Value::Null);
+
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "Error");
+    assert_eq!(msg["data"]["code"], "QueryFailed");
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "AccountActivity");
+    assert_eq!(msg["data"]["checkpoint"], 6);
+}
+
+#[tokio::test]
+async fn test_sender_drops_above_high_water_mark() {
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    let (tx, mut rx) = CustomBroadcaster::channel(4, metrics.clone());
+
+    // Nobody drains the receiver, so the queue fills up to the high-water mark (3 of 4)
+    // and every further update is dropped without blocking.
+    let outputs = Arc::new(new_outputs());
+    for _ in 0..6 {
+        tx.try_send(outputs.clone());
+    }
+
+    assert_eq!(rx.len(), 3);
+    assert_eq!(
+        metrics
+            .ingest_dropped
+            .with_label_values(&["high_water_mark"])
+            .get(),
+        3
+    );
+    assert_eq!(metrics.ingest_queue_depth.get(), 3);
+
+    rx.close();
+    while rx.recv().await.is_some() {}
+    tx.try_send(outputs);
+    assert_eq!(
+        metrics.ingest_dropped.with_label_values(&["closed"]).get(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_maybe_spawn_respects_config() {
+    let registry = Registry::new();
+    assert!(
+        CustomBroadcaster::maybe_spawn(None, None, None, &registry)
+            .unwrap()
+            .is_none()
+    );
+
+    let invalid = BroadcasterConfig {
+        port: 0,
+        ..Default::default()
+    };
+    let checkpoint_store = Some(CheckpointStore::new_for_tests());
+    assert!(
+        CustomBroadcaster::maybe_spawn(Some(invalid), None, checkpoint_store, &registry).is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_set_socket_options() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap());
+    let (_client, accepted) = tokio::join!(client, listener.accept());
+    let (stream, _) = accepted.unwrap();
+
+    set_socket_options(&stream, true, &Some(TcpKeepaliveConfig::default()));
+    assert!(stream.nodelay().unwrap());
+    assert!(socket2::SockRef::from(&stream).keepalive().unwrap());
+
+    set_socket_options(&stream, false, &None);
+    assert!(!stream.nodelay().unwrap());
+}
+
+#[test]
+fn test_owned_objects_received_object() {
+    let receiver = SuiAddress::random_for_testing_only();
+    let object_id = ObjectID::random();
+    let mut outputs = build_outputs(|effects| {
+        effects.with_created_objects([(object_id, Owner::AddressOwner(receiver))])
+    });
+    outputs.written.insert(
+        object_id,
+        new_object(object_id, Owner::AddressOwner(receiver)),
+    );
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeOwnedObjects { account: receiver });
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::ObjectUpdate { account, object_id: id, created: true, .. }
+            if *account == receiver && *id == object_id
+    ));
+}
+
+#[test]
+fn test_only_successful_leaves_out_failed_transactions() {
+    let failed = build_outputs(|effects| {
+        effects.with_status(ExecutionStatus::new_failure(
+            ExecutionFailureStatus::InsufficientGas,
+            None,
+        ))
+    });
+    let sender = sender_of(&failed);
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeAccount(sender));
+    let messages = subscriptions.messages_for(&failed, None);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::AccountActivity { status: ExecutionOutcome::Failure { error }, .. }
+            if error == "Insufficient Gas."
+    ));
+
+    let options: ConnectionOptions = serde_json::from_str(r#"{"only_successful":true}"#).unwrap();
+    let mut subscriptions = Subscriptions::new(&options, None, None);
+    subscriptions.apply(SubscriptionRequest::SubscribeAccount(sender));
+    assert!(subscriptions.messages_for(&failed, None).is_empty());
+    let messages = subscriptions.messages_for(&new_outputs(), None);
+    assert!(matches!(
+        &messages[..],
+        [StreamMessage::AccountActivity {
+            status: ExecutionOutcome::Success,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn test_owned_objects_mutated_object() {
+    let owner = SuiAddress::random_for_testing_only();
+    let object_id = ObjectID::random();
+    let mut outputs = build_outputs(|effects| {
+        effects.with_mutated_objects([(
+            object_id,
+            SequenceNumber::from_u64(3),
+            Owner::AddressOwner(owner),
+        )])
+    });
+    outputs
+        .written
+        .insert(object_id, new_object(object_id, Owner::AddressOwner(owner)));
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeOwnedObjects { account: owner });
+
+    // Written but not created
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1, "{messages:?}");
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::ObjectUpdate { account, object_id: id, owner: new_owner, created: false, .. }
+            if *account == owner && *id == object_id && *new_owner == Owner::AddressOwner(owner)
+    ));
+}
+
+#[test]
+fn test_owned_objects_transferred_and_deleted() {
+    let receiver = SuiAddress::random_for_testing_only();
+    let transferred = ObjectID::random();
+    let deleted = ObjectID::random();
+    let version = SequenceNumber::from_u64(3);
+    let mut outputs = build_outputs(|effects| {
+        effects
+            .with_mutated_objects([(transferred, version, Owner::AddressOwner(receiver))])
+            .with_deleted_objects([(deleted, version)])
+    });
+    outputs.written.insert(
+        transferred,
+        new_object(transferred, Owner::AddressOwner(receiver)),
+    );
+    outputs.deleted.push(ObjectKey(deleted, version));
+    let sender = sender_of(&outputs);
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeOwnedObjects { account: sender });
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 2);
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::ObjectUpdate { account, object_id, owner, .. }
+            if *account == sender
+                && *object_id == transferred
+                && *owner == Owner::AddressOwner(receiver)
+    ));
+    assert!(matches!(
+        &messages[1],
+        StreamMessage::ObjectDeleted { account, object_id, .. }
+            if *account == sender && *object_id == deleted
+    ));
+}
+
+#[test]
+fn test_owned_objects_wrapped_and_unwrapped() {
+    let wrapped = ObjectID::random();
+    let unwrapped = ObjectID::random();
+    let version = SequenceNumber::from_u64(3);
+    let mut outputs = build_outputs(|effects| {
+        effects
+            .with_wrapped_objects([(wrapped, version)])
+            .with_unwrapped_objects([(unwrapped, Owner::AddressOwner(SuiAddress::ZERO))])
+    });
+    let sender = sender_of(&outputs);
+    outputs.written.insert(
+        unwrapped,
+        new_object(unwrapped, Owner::AddressOwner(sender)),
+    );
+    outputs.wrapped.push(ObjectKey(wrapped, version));
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeOwnedObjects { account: sender });
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 3, "{messages:?}");
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::ObjectUnwrapped { account, object_id, .. }
+            if *account == sender && *object_id == unwrapped
+    ));
+    assert!(matches!(
+        &messages[1],
+        StreamMessage::ObjectUpdate { object_id, created: false, .. } if *object_id == unwrapped
+    ));
+    assert!(matches!(
+        &messages[2],
+        StreamMessage::ObjectWrapped { account, object_id, .. }
+            if *account == sender && *object_id == wrapped
+    ));
+}
+
+#[test]
+fn test_pool_update_carries_versions() {
+    let mutated = ObjectID::random();
+    let created = ObjectID::random();
+    let shared = Owner::Shared {
+        initial_shared_version: OBJECT_START_VERSION,
+    };
+    let mut outputs = build_outputs(|effects| {
+        effects
+            .with_mutated_objects([(mutated, SequenceNumber::from_u64(4), shared.clone())])
+            .with_created_objects([(created, shared.clone())])
+    });
+    for id in [mutated, created] {
+        outputs.written.insert(id, new_object(id, shared.clone()));
+    }
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribePool(mutated));
+    subscriptions.apply(SubscriptionRequest::SubscribePool(created));
+
+    let versions: HashMap<_, _> = subscriptions
+        .messages_for(&outputs, None)
+        .into_iter()
+        .map(|msg| match msg {
+            StreamMessage::PoolUpdate {
+                pool_id,
+                version,
+                previous_version,
+                ..
+            } => (pool_id, (version, previous_version)),
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect();
+    let version = OBJECT_START_VERSION.value();
+    assert_eq!(versions[&mutated], (version, Some(4)));
+    assert_eq!(versions[&created], (version, None));
+}
+
+#[test]
+fn test_pool_update_change_kinds() {
+    let created = ObjectID::random();
+    let mutated = ObjectID::random();
+    let transferred = ObjectID::random();
+    let unwrapped = ObjectID::random();
+    // The sender owns the input objects of test effects
+    let sender = Owner::AddressOwner(sender_of(&new_outputs()));
+    let receiver = Owner::AddressOwner(SuiAddress::random_for_testing_only());
+    let version = SequenceNumber::from_u64(4);
+    let mut outputs = build_outputs(|effects| {
+        effects
+            .with_created_objects([(created, sender.clone())])
+            .with_mutated_objects([
+                (mutated, version, sender.clone()),
+                (transferred, version, receiver.clone()),
+            ])
+            .with_unwrapped_objects([(unwrapped, sender.clone())])
+    });
+    let mut subscriptions = Subscriptions::default();
+    for (id, owner) in [
+        (created, &sender),
+        (mutated, &sender),
+        (transferred, &receiver),
+        (unwrapped, &sender),
+    ] {
+        outputs.written.insert(id, new_object(id, owner.clone()));
+        subscriptions.apply(SubscriptionRequest::SubscribePool(id));
+    }
+
+    let kinds: HashMap<_, _> = subscriptions
+        .messages_for(&outputs, None)
+        .into_iter()
+        .map(|msg| match msg {
+            StreamMessage::PoolUpdate {
+                pool_id,
+                change_kind,
+                ..
+            } => (pool_id, change_kind),
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect();
+    assert_eq!(kinds[&created], ChangeKind::Created);
+    assert_eq!(kinds[&mutated], ChangeKind::Mutated);
+    assert_eq!(
+        kinds[&transferred],
+        ChangeKind::Transferred {
+            from: sender,
+            to: receiver
+        }
+    );
+    assert_eq!(kinds[&unwrapped], ChangeKind::Unwrapped);
+}
+
+#[test]
+fn test_subscribed_pools_deleted_and_wrapped() {
+    let deleted = ObjectID::random();
+    let wrapped = ObjectID::random();
+    let unsubscribed = ObjectID::random();
+    let version = SequenceNumber::from_u64(3);
+    let mut outputs = new_outputs();
+    outputs.deleted.push(ObjectKey(deleted, version));
+    outputs.deleted.push(ObjectKey(unsubscribed, version));
+    outputs.wrapped.push(ObjectKey(wrapped, version));
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribePool(deleted));
+    subscriptions.apply(SubscriptionRequest::SubscribePool(wrapped));
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 2, "{messages:?}");
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::PoolDeleted { pool_id, wrapped: false, .. } if *pool_id == deleted
+    ));
+    assert!(matches!(
+        &messages[1],
+        StreamMessage::PoolDeleted { pool_id, wrapped: true, .. } if *pool_id == wrapped
+    ));
+}
+
+#[test]
+fn test_new_objects_matched_on_created_set_and_type() {
+    let created = ObjectID::random();
+    let mutated = ObjectID::random();
+    let unwrapped = ObjectID::random();
+    let owner = Owner::Shared {
+        initial_shared_version: OBJECT_START_VERSION,
+    };
+    let mut outputs = build_outputs(|effects| {
+        effects
+            .with_created_objects([(created, owner.clone())])
+            .with_mutated_objects([(mutated, OBJECT_START_VERSION, owner.clone())])
+            .with_unwrapped_objects([(unwrapped, owner.clone())])
+    });
+    for id in [created, mutated, unwrapped] {
+        outputs.written.insert(id, new_object(id, owner.clone()));
+    }
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeNewObjects {
+        type_: "0x2::coin::Coin<0x2::sui::SUI>".to_string(),
+    });
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1, "{messages:?}");
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::ObjectUpdate { account, object_id, created: true, .. }
+            if *account == sender_of(&outputs) && *object_id == created
+    ));
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeNewObjects {
+        type_: "0x2::coin::Coin<0x2::coin::COIN>".to_string(),
+    });
+    assert!(subscriptions.messages_for(&outputs, None).is_empty());
+}
+
+#[test]
+fn test_heartbeat_serialization() {
+    let msg = StreamMessage::Heartbeat {
+        timestamp_ms: 1_700_000_000_000,
+        server_seq: 42,
+    };
+    assert_eq!(
+        serde_json::to_value(&msg).unwrap(),
+        serde_json::json!({
+            "type": "Heartbeat",
+            "data": { "timestamp_ms": 1_700_000_000_000u64, "server_seq": 42 },
+        })
+    );
+}
+
+#[test]
+fn test_address_formats() {
+    let object_id = ObjectID::from_single_byte(0xab);
+    let owner = SuiAddress::from(ObjectID::from_single_byte(2));
+    let msg = StreamMessage::ObjectUpdate {
+        account: owner,
+        object_id,
+        version: 1,
+        owner: Owner::AddressOwner(owner),
+        object_type: None,
+        // A digest rendered as hex looks like an address but is left alone
+        digest: format!("0x{}", "00".repeat(31) + "02"),
+        object: Some(ByteFormat::Hex.encode(vec![0; 32])),
+        created: false,
+    };
+    let render = |format| {
+        let text = rendering::to_json_string(&msg, format).unwrap();
+        serde_json::from_str::<serde_json::Value>(&text).unwrap()["data"].take()
+    };
+
+    let full = render(AddressFormat::Full);
+    assert_eq!(full, serde_json::to_value(&msg).unwrap()["data"]);
+    assert_eq!(full["object_id"], format!("0x{}", "00".repeat(31) + "ab"));
+
+    let short = render(AddressFormat::Short);
+    assert_eq!(short["object_id"], "0xab");
+    assert_eq!(short["account"], "0x2");
+    assert_eq!(short["owner"]["AddressOwner"], "0x2");
+    assert_eq!(short["digest"], full["digest"]);
+    assert_eq!(short["object"], full["object"]);
+
+    let unprefixed = render(AddressFormat::Unprefixed);
+    assert_eq!(unprefixed["object_id"], "00".repeat(31) + "ab");
+
+    // Map keys are rendered too
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id: object_id,
+        mode: TableMode::Data,
+        windows: vec![IndexWindow {
+            center: 10,
+            range: 1,
+        }],
+        snapshot: false,
+    });
+    let text = rendering::to_json_string(&subscriptions.snapshot(), AddressFormat::Short);
+    let snapshot: serde_json::Value = serde_json::from_str(&text.unwrap()).unwrap();
+    assert_eq!(snapshot["data"]["tables"][0], "0xab");
+    assert!(snapshot["data"]["table_windows"].get("0xab").is_some());
+}
+
+/// A message of every variant, with skippable fields both set and not
+fn every_message() -> Vec<StreamMessage> {
+    let id = ObjectID::from_single_byte(0xab);
+    let account = SuiAddress::from(ObjectID::from_single_byte(2));
+    let digest = TransactionDigest::random().to_string();
+    let bytes = |format: ByteFormat| format.encode(vec![0, 1, 2, 0xff]);
+    let field_data = |request_id| StreamMessage::FieldData {
+        table_id: id,
+        index: 7,
+        field_id: id,
+        version: 3,
+        bcs_bytes: bytes(ByteFormat::Base64),
+        request_id,
+    };
+    let messages = vec![
+        StreamMessage::PoolUpdate {
+            pool_id: id,
+            digest: digest.clone(),
+            checkpoint: Some(12),
+            version: 4,
+            previous_version: Some(3),
+            change_kind: ChangeKind::Transferred {
+                from: Owner::AddressOwner(SuiAddress::ZERO),
+                to: Owner::Immutable,
+            },
+            object: Some(bytes(ByteFormat::Array)),
+            diff: None,
+        },
+        StreamMessage::PoolUpdate {
+            pool_id: id,
+            digest: digest.clone(),
+            checkpoint: None,
+            version: 2,
+            previous_version: None,
+            change_kind: ChangeKind::Created,
+            object: None,
+            diff: Some(PoolDiff {
+                base_version: 1,
+                version: 2,
+                splices: vec![Splice {
+                    offset: 4,
+                    delete: 1,
+                    insert: bytes(ByteFormat::Hex),
+                }],
+            }),
+        },
+        StreamMessage::PoolDeleted {
+            pool_id: id,
+            digest: digest.clone(),
+            wrapped: true,
+        },
+        StreamMessage::AccountActivity {
+            account,
+            digest: digest.clone(),
+            kind: "Transaction".to_string(),
+            status: ExecutionOutcome::Failure {
+                error: "InsufficientGas".to_string(),
+            },
+            checkpoint: Some(12),
+        },
+        StreamMessage::BalanceChange {
+            account,
+            coin_type: "0x2::sui::SUI".to_string(),
+            new_balance: 10,
+        },
+        StreamMessage::Event {
+            package_id: id,
+            transaction_module: "pool".to_string(),
+            sender: account,
+            type_: "0xab::pool::SwapEvent".to_string(),
+            contents: bytes(ByteFormat::Array),
+            digest: digest.clone(),
+            checkpoint: None,
+        },
+        StreamMessage::ObjectUpdate {
+            account,
+            object_id: id,
+            version: 3,
+            owner: Owner::AddressOwner(account),
+            object_type: Some("0x2::coin::Coin<0x2::sui::SUI>".to_string()),
+            digest: digest.clone(),
+            object: None,
+            created: true,
+        },
+        StreamMessage::ObjectDeleted {
+            account,
+            object_id: id,
+            digest: digest.clone(),
+        },
+        StreamMessage::ObjectWrapped {
+            account,
+            object_id: id,
+            digest: digest.clone(),
+        },
+        StreamMessage::ObjectUnwrapped {
+            account,
+            object_id: id,
+            digest: digest.clone(),
+        },
+        StreamMessage::TableFieldUpdate {
+            table_id: id,
+            field_id: id,
+            version: 3,
+            digest: digest.clone(),
+            object: bytes(ByteFormat::Array),
+        },
+        StreamMessage::TableFieldRemoved {
+            table_id: id,
+            field_id: id,
+            digest: digest.clone(),
+        },
+        StreamMessage::TableVersionBump {
+            table_id: id,
+            new_version: 4,
+            digest: digest.clone(),
+        },
+        StreamMessage::CoinFlows {
+            digest: digest.clone(),
+            flows: vec![CoinFlow {
+                from: Some(account),
+                to: None,
+                coin_type: "0x2::sui::SUI".to_string(),
+                amount: u128::MAX,
+            }],
+        },
+        StreamMessage::ParentObject {
+            table_id: id,
+            version: 3,
+            object_type: "0x2::table::Table<u64, u64>".to_string(),
+            bcs_bytes: bytes(ByteFormat::Array),
+            request_id: None,
+        },
+        StreamMessage::SnapshotComplete {
+            table_id: id,
+            snapshot_version: 3,
+        },
+        field_data(Some(9)),
+        StreamMessage::QueryComplete {
+            table_id: id,
+            fields: 1,
+            errors: 0,
+            request_id: None,
+        },
+        StreamMessage::Error {
+            code: ErrorCode::QueryFailed,
+            message: "no such table".to_string(),
+        },
+        StreamMessage::Subscribed {
+            kind: "SubscribePool".to_string(),
+            target: Some(id.to_string()),
+        },
+        StreamMessage::Unsubscribed {
+            kind: "UnsubscribeAll".to_string(),
+            target: None,
+        },
+        StreamMessage::Subscriptions {
+            pools: vec![id],
+            accounts: vec![account],
+            owned_objects: vec![],
+            new_object_types: vec![],
+            tables: vec![id],
+            table_windows: BTreeMap::from([(
+                id,
+                vec![IndexWindow {
+                    center: 10,
+                    range: 2,
+                }],
+            )]),
+            table_bumps: vec![],
+            fields: vec![],
+            event_types: vec![],
+            event_packages: vec![],
+            all: false,
+            coin_flows: true,
+        },
+        StreamMessage::Stats(ConnectionInfo {
+            id: 1,
+            connected_at_ms: 2,
+            messages_sent: 3,
+            bytes_sent: 4,
+        }),
+        StreamMessage::Heartbeat {
+            timestamp_ms: 5,
+            server_seq: 6,
+        },
+        // A skipped field in a nested message
+        StreamMessage::Replayed {
+            server_seq: 6,
+            message: Box::new(field_data(None)),
+        },
+        StreamMessage::Resync {
+            missed: 7,
+            resume_seq: 8,
+        },
+        StreamMessage::Dropped { messages: 9 },
+        StreamMessage::CheckpointManifest {
+            sequence_number: 10,
+            digests: vec![digest.clone()],
+        },
+        StreamMessage::Raw(SerializableOutput {
+            digest,
+            timestamp_ms: 9,
+        }),
+    ];
+    #[cfg(feature = "pool-decoders")]
+    let messages = [
+        messages,
+        vec![StreamMessage::PoolState {
+            pool_id: id,
+            digest: "digest".to_string(),
+            pool_type: "cetus".to_string(),
+            fields: serde_json::json!({ "liquidity": "5", "tick": { "bits": 1 } }),
+        }],
+    ]
+    .concat();
+    messages
+}
+
+#[test]
+fn test_messages_round_trip_in_both_formats() {
+    for msg in every_message() {
+        let Some(Message::Text(text)) =
+            serialize_message(&msg, MessageFormat::Json, AddressFormat::Full)
+        else {
+            panic!("{msg:?} not serialized as a text frame");
+        };
+        let from_json: StreamMessage = serde_json::from_str(&text).unwrap();
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), text.as_str());
+
+        let Some(Message::Binary(bytes)) =
+            serialize_message(&msg, MessageFormat::Bcs, AddressFormat::Full)
+        else {
+            panic!("{msg:?} not serialized as a binary frame");
+        };
+        let from_bcs: StreamMessage = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(rendering::to_bcs_bytes(&from_bcs).unwrap(), bytes);
+
+        // Both frames carry the same message
+        assert_eq!(rendering::to_bcs_bytes(&from_json).unwrap(), bytes);
+    }
+
+    // Skipped fields are left out of JSON only
+    let msg = StreamMessage::QueryComplete {
+        table_id: ObjectID::ZERO,
+        fields: 0,
+        errors: 0,
+        request_id: None,
+    };
+    let json = serde_json::to_value(&msg).unwrap();
+    assert!(json["data"].get("request_id").is_none());
+    let with_none = rendering::to_bcs_bytes(&msg).unwrap();
+    assert_eq!(with_none.last(), Some(&0));
+}
+
+#[tokio::test]
+async fn test_set_format_switches_to_binary_frames() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""SubscribeAll""#))
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#"{"SetFormat":{"format":"bcs"}}"#))
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""GetSubscriptions""#))
+        .await
+        .unwrap();
+    // The acknowledgment was sent before the switch
+    assert_eq!(next_json(&mut client).await["type"], "Subscribed");
+    let WsMessage::Binary(bytes) = client.next().await.unwrap().unwrap() else {
+        panic!("expected a binary frame");
+    };
+    match bcs::from_bytes(&bytes).unwrap() {
+        (2u64, StreamMessage::Subscriptions { all, .. }) => assert!(all),
+        other => panic!("unexpected message {other:?}"),
+    }
+
+    // Fanned-out messages too, without sharing the JSON payloads of other connections
+    ingest(&state, Arc::new(new_outputs()).into()).await;
+    let WsMessage::Binary(bytes) = client.next().await.unwrap().unwrap() else {
+        panic!("expected a binary frame");
+    };
+    assert!(matches!(
+        bcs::from_bytes(&bytes).unwrap(),
+        (3u64, StreamMessage::AccountActivity { .. })
+    ));
+
+    client
+        .send(WsMessage::text(r#"{"SetFormat":{"format":"json"}}"#))
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""GetSubscriptions""#))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Subscriptions");
+}
+
+/// The contents of a gzipped frame
+fn gunzip(bytes: &[u8]) -> Vec<u8> {
+    use std::io::Read;
+
+    let mut contents = vec![];
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_end(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[tokio::test]
+async fn test_compressed_format_sends_gzipped_frames() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+    for request in [
+        r#"{"SetFormat":{"format":"json","compress":true}}"#,
+        r#""GetSubscriptions""#,
+        r#"{"SetFormat":{"format":"bcs","compress":true}}"#,
+        r#""GetSubscriptions""#,
+        r#"{"SetFormat":{"format":"json"}}"#,
+        r#""GetSubscriptions""#,
+    ] {
+        client.send(WsMessage::text(request)).await.unwrap();
+    }
+
+    let WsMessage::Binary(bytes) = client.next().await.unwrap().unwrap() else {
+        panic!("expected a binary frame");
+    };
+    let msg: serde_json::Value = serde_json::from_slice(&gunzip(&bytes)).unwrap();
+    assert_eq!(msg["seq"], 1);
+    assert_eq!(msg["type"], "Subscriptions");
+
+    let WsMessage::Binary(bytes) = client.next().await.unwrap().unwrap() else {
+        panic!("expected a binary frame");
+    };
+    assert!(matches!(
+        bcs::from_bytes(&gunzip(&bytes)).unwrap(),
+        (2u64, StreamMessage::Subscriptions { .. })
+    ));
+
+    // Uncompressed again
+    assert_eq!(next_json(&mut client).await["seq"], 3);
+}
+
+#[test]
+fn test_compression_ratio_of_pool_update() {
+    // A stand-in for the contents of a CLMM pool: its ID, balances, prices, fee growth,
+    // the head of its tick skip list and the IDs of its tables
+    let pool_id = ObjectID::random();
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut contents = bcs::to_bytes(&(
+        pool_id,
+        (rng.r#gen::<u64>(), rng.r#gen::<u64>(), 60u32, 2500u64),
+        (rng.r#gen::<u128>(), rng.r#gen::<u128>(), 0xffff_fd8au32),
+        (rng.r#gen::<u128>(), rng.r#gen::<u128>(), 0u64, 0u64),
+    ))
+    .unwrap();
+    for level in 0..16u64 {
+        contents.extend(bcs::to_bytes(&(level >= 12, rng.r#gen::<u64>() % 1024)).unwrap());
+    }
+    for _ in 0..6 {
+        contents.extend(bcs::to_bytes(&(ObjectID::random(), 0u64)).unwrap());
+    }
+    contents.extend(bcs::to_bytes("https://app.cetus.zone/pool").unwrap());
+
+    for byte_format in [ByteFormat::default(), ByteFormat::Base64] {
+        let msg = StreamMessage::PoolUpdate {
+            pool_id,
+            digest: TransactionDigest::random().to_string(),
+            checkpoint: None,
+            version: 42,
+            previous_version: Some(41),
+            change_kind: ChangeKind::Mutated,
+            object: Some(byte_format.encode(contents.clone())),
+            diff: None,
+        };
+        let frame = serialize_message(&msg, MessageFormat::Json, AddressFormat::default()).unwrap();
+        let frame = with_seq(frame, 1);
+        let Message::Binary(compressed) = gzipped(frame.clone()) else {
+            panic!("expected a binary frame");
+        };
+        let text = frame.to_text().unwrap();
+        assert_eq!(gunzip(&compressed), text.as_bytes());
+        let ratio = compressed.len() as f64 / text.len() as f64;
+        println!(
+            "{byte_format:?}: {} bytes of JSON gzipped to {} ({ratio:.2})",
+            text.len(),
+            compressed.len()
+        );
+        // Bytes as arrays of numbers are mostly punctuation; even base64 leaves the IDs and
+        // field names to compress
+        let expected = match byte_format {
+            ByteFormat::Base64 => 0.9,
+            _ => 0.5,
+        };
+        assert!(ratio < expected, "{byte_format:?}: {ratio:.2}");
+    }
+}
+
+#[tokio::test]
+async fn test_messages_are_numbered_per_connection() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""SubscribeAll""#))
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""GetSubscriptions""#))
+        .await
+        .unwrap();
+    let first = next_json(&mut client).await;
+    assert_eq!(first["type"], "Subscribed");
+    assert_eq!(first["seq"], 1);
+    assert_eq!(next_json(&mut client).await["seq"], 2);
+
+    let outputs = Arc::new(new_outputs());
+    for _ in 0..3 {
+        ingest(&state, outputs.clone().into()).await;
+    }
+    client.send(WsMessage::text(r#""GetStats""#)).await.unwrap();
+    let mut seqs = vec![];
+    loop {
+        let msg = next_json(&mut client).await;
+        seqs.push(msg["seq"].as_u64().unwrap());
+        // Still a `StreamMessage`, the extra field aside
+        let msg: StreamMessage = serde_json::from_value(msg).unwrap();
+        if matches!(msg, StreamMessage::Stats(_)) {
+            break;
+        }
+    }
+    assert_eq!(seqs, (3..=6).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_subscribe_requests_are_acknowledged() {
+    use crate::field_data_query::{KeyCodec, U64Codec};
+
+    let id = ObjectID::random();
+    let account = SuiAddress::random_for_testing_only();
+    let mut subscriptions = Subscriptions::new(&ConnectionOptions::default(), None, None);
+    let mut ack = |req: SubscriptionRequest| match subscriptions.apply(req) {
+        Some(StreamMessage::Subscribed { kind, target }) => (true, kind, target),
+        Some(StreamMessage::Unsubscribed { kind, target }) => (false, kind, target),
+        other => panic!("unexpected reply {other:?}"),
+    };
+    let subscribed = |kind: &str, target: Option<String>| (true, kind.to_string(), target);
+
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribePool(id)),
+        subscribed("SubscribePool", Some(id.to_string()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribePoolWithOptions {
+            pool_id: id,
+            skip_unchanged: true,
+        }),
+        subscribed("SubscribePoolWithOptions", Some(id.to_string()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeAccount(account)),
+        subscribed("SubscribeAccount", Some(account.to_string()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeOwnedObjects { account }),
+        subscribed("SubscribeOwnedObjects", Some(account.to_string()))
+    );
+    let coin_type = "0x2::coin::Coin<0x2::sui::SUI>".to_string();
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeNewObjects {
+            type_: coin_type.clone(),
+        }),
+        subscribed("SubscribeNewObjects", Some(coin_type.clone()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeTable {
+            table_id: id,
+            mode: TableMode::Invalidate,
+            windows: vec![],
+            snapshot: false,
+        }),
+        subscribed("SubscribeTable", Some(id.to_string()))
+    );
+    let key_bcs = bcs::to_bytes(&7u64).unwrap();
+    let field_id = U64Codec.field_id(id, 7).unwrap();
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeField {
+            table_id: id,
+            key_bcs,
+            key_type: "u64".to_string(),
+        }),
+        subscribed("SubscribeField", Some(field_id.to_string()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeEvents {
+            package_id: Some(id),
+            module: Some("pool".to_string()),
+            event_type: None,
+            field_filters: vec![],
+        }),
+        subscribed("SubscribeEvents", Some(format!("{id}::pool")))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeEvents {
+            package_id: None,
+            module: None,
+            event_type: Some(coin_type.clone()),
+            field_filters: vec![],
+        }),
+        subscribed("SubscribeEvents", Some(coin_type))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::SubscribeAll),
+        subscribed("SubscribeAll", None)
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::UnsubscribePool { pool_id: id }),
+        (false, "UnsubscribePool".to_string(), Some(id.to_string()))
+    );
+    assert_eq!(
+        ack(SubscriptionRequest::UnsubscribeAll),
+        (false, "UnsubscribeAll".to_string(), None)
+    );
+
+    // Rejected requests are answered with an error, and requests that are not subscriptions
+    // not at all
+    let rejected = [
+        SubscriptionRequest::SubscribeNewObjects {
+            type_: "not a type".to_string(),
+        },
+        SubscriptionRequest::SubscribeEvents {
+            package_id: None,
+            module: None,
+            event_type: None,
+            field_filters: vec![],
+        },
+        // Not available without a store
+        SubscriptionRequest::SubscribeCoinFlows,
+    ];
+    for req in rejected {
+        let reply = subscriptions.apply(req.clone());
+        assert!(
+            matches!(reply, Some(StreamMessage::Error { .. })),
+            "{req:?}: {reply:?}"
+        );
+    }
+    assert!(subscriptions.apply(SubscriptionRequest::GetStats).is_none());
+}
+
+#[tokio::test]
+async fn test_malformed_request_is_answered_with_error() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#"{"SubscribePool":"#))
+        .await
+        .unwrap();
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "Error");
+    assert_eq!(msg["data"]["code"], "InvalidRequest");
+    let message = msg["data"]["message"].as_str().unwrap();
+    assert!(
+        message.starts_with("invalid request: EOF while parsing"),
+        "{message}"
+    );
+    // Followed by the offending frame
+    assert!(message.ends_with(r#": {"SubscribePool":"#), "{message}");
+
+    // Unknown requests, with long frames cut short
+    let garbage = format!(r#"{{"SubscribePools":"{}"}}"#, "x".repeat(1000));
+    client.send(WsMessage::text(garbage)).await.unwrap();
+    let msg = next_json(&mut client).await;
+    assert_eq!(msg["type"], "Error");
+    let message = msg["data"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("unknown variant `SubscribePools`"),
+        "{message}"
+    );
+    assert!(message.ends_with(&format!(r#"{{"SubscribePools":"{}…"#, "x".repeat(181))));
+
+    // The connection is still served
+    client
+        .send(WsMessage::text(r#""SubscribeAll""#))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Subscribed");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_disabled_heartbeat_never_ticks() {
+    let mut heartbeat = None;
+    let ticked = tokio::time::timeout(Duration::from_secs(3600), tick(&mut heartbeat)).await;
+    assert!(ticked.is_err());
+}
+
+#[test]
+fn test_retention_buffer_evicts_oldest() {
+    let buffer = RetentionBuffer::new(3, None);
+    let outputs = Arc::new(new_outputs());
+    for seq in 1..=5 {
+        buffer.push(seq, outputs.clone().into());
+    }
+
+    let seqs = |entries: Vec<(u64, ExecutedTransaction)>| {
+        entries.into_iter().map(|(seq, _)| seq).collect::<Vec<_>>()
+    };
+    assert_eq!(seqs(buffer.since(0)), vec![3, 4, 5]);
+    assert_eq!(seqs(buffer.since(4)), vec![5]);
+    assert!(buffer.since(5).is_empty());
+}
+
+#[test]
+fn test_lag_filled_from_retention_or_resynced() {
+    let buffer = RetentionBuffer::new(3, None);
+    let outputs = Arc::new(new_outputs());
+    for seq in 1..=5 {
+        buffer.push(seq, outputs.clone().into());
+    }
+    let seqs =
+        |missed: Vec<IngestedTransaction>| missed.iter().map(|tx| tx.seq).collect::<Vec<_>>();
+
+    // Missed 3 and 4 after 2, both still retained
+    assert_eq!(
+        seqs(missed_transactions(&buffer, 2, 2).unwrap()),
+        vec![3, 4]
+    );
+    assert_eq!(
+        seqs(missed_transactions(&buffer, 2, 3).unwrap()),
+        vec![3, 4, 5]
+    );
+    // 2 was evicted
+    assert!(missed_transactions(&buffer, 1, 3).is_none());
+    // 6 was never retained
+    assert!(missed_transactions(&buffer, 4, 2).is_none());
+
+    let resync = StreamMessage::Resync {
+        missed: 3,
+        resume_seq: 5,
+    };
+    assert_eq!(
+        serde_json::to_value(&resync).unwrap(),
+        serde_json::json!({"type": "Resync", "data": {"missed": 3, "resume_seq": 5}})
+    );
+}
+
+/// Serve `state` on a free local port, returning the URL of its websocket endpoint
+async fn serve(state: Arc<AppState>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(state)).await.unwrap() });
+    format!("ws://{addr}/ws")
+}
+
+/// The next text message received by a websocket client, as JSON
+async fn next_json<S>(client: &mut S) -> serde_json::Value
+where
+    S: Stream<
+            Item = Result<
+                tokio_tungstenite::tungstenite::Message,
+                tokio_tungstenite::tungstenite::Error,
+            >,
+        > + Unpin,
+{
+    let msg = client.next().await.unwrap().unwrap();
+    serde_json::from_str(msg.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_lagging_client_is_resynced_not_disconnected() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let config = BroadcasterConfig {
+        broadcast_capacity: 4,
+        retention_capacity: 0,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+    client
+        .send(WsMessage::text(r#""SubscribeAll""#))
+        .await
+        .unwrap();
+    // Answered once SubscribeAll has been applied
+    client
+        .send(WsMessage::text(r#""GetSubscriptions""#))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Subscribed");
+    assert_eq!(next_json(&mut client).await["type"], "Subscriptions");
+
+    // Ingesting never yields, so the connection task has no chance to keep up with the flood
+    let outputs = Arc::new(new_outputs());
+    for _ in 0..20 {
+        ingest(&state, outputs.clone().into()).await;
+    }
+
+    let resync = next_json(&mut client).await;
+    assert_eq!(resync["type"], "Resync");
+    assert_eq!(resync["data"]["missed"], 16);
+    assert_eq!(resync["data"]["resume_seq"], 17);
+    for _ in 0..4 {
+        assert_eq!(next_json(&mut client).await["type"], "AccountActivity");
+    }
+
+    // Still served
+    ingest(&state, outputs.into()).await;
+    assert_eq!(next_json(&mut client).await["type"], "AccountActivity");
+    client.send(WsMessage::text(r#""GetStats""#)).await.unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Stats");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_send_queue_drops_oldest_updates_for_slow_socket() {
+    let queue = Arc::new(SendQueue::new(4, true));
+    let written = Arc::new(Mutex::new(vec![]));
+    // A socket that takes a second to write each frame
+    let sink = Box::pin(futures::sink::unfold(
+        written.clone(),
+        |written, frame: Message| async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            written.lock().push(frame.to_text().unwrap().to_string());
+            Ok::<_, std::convert::Infallible>(written)
+        },
+    ));
+    let sent = Arc::new(SentCounters::default());
+    let writer = tokio::spawn(write_frames(
+        queue.clone(),
+        sink,
+        sent.clone(),
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+
+    // Updates keep being queued without waiting on the socket, replacing the oldest ones
+    let start = tokio::time::Instant::now();
+    for i in 0..100 {
+        let update = Outbound {
+            frame: Message::text(i.to_string()),
+            message_type: Some("AccountActivity"),
+            droppable: true,
+        };
+        queue.push(update).await.unwrap();
+    }
+    assert_eq!(start.elapsed(), Duration::ZERO);
+    assert_eq!(queue.take_dropped(), 96);
+    assert_eq!(queue.take_dropped(), 0);
+
+    // Other frames wait for room instead
+    let report = Outbound {
+        frame: Message::text("dropped"),
+        message_type: Some("Dropped"),
+        droppable: false,
+    };
+    queue.push(report).await.unwrap();
+    queue.finish();
+    writer.await.unwrap();
+    assert_eq!(*written.lock(), ["96", "97", "98", "99", "dropped"]);
+    assert_eq!(sent.messages(), 5);
+}
+
+#[tokio::test]
+async fn test_shutdown_stops_server_and_ingestion() {
+    let config = BroadcasterConfig {
+        bind_address: std::net::Ipv4Addr::LOCALHOST.into(),
+        port: 0,
+        ..Default::default()
+    };
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    // The sender stays alive, so only the shutdown ends the ingestion loop
+    let (_tx, rx) = CustomBroadcaster::channel(config.channel_capacity, metrics.clone());
+    let handle =
+        CustomBroadcaster::spawn(config, None, None, KeyCodecRegistry::default(), rx, metrics);
+
+    tokio::time::timeout(Duration::from_secs(5), handle.shutdown())
+        .await
+        .expect("broadcaster did not shut down");
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_connections() {
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+
+    state.shutdown.cancel();
+    let msg = client.next().await.unwrap().unwrap();
+    match msg {
+        tokio_tungstenite::tungstenite::Message::Close(Some(frame)) => {
+            assert_eq!(u16::from(frame.code), CLOSE_GOING_AWAY)
+        }
+        other => panic!("unexpected message {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_unresponsive_client_is_closed_after_pong_timeout() {
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let config = BroadcasterConfig {
+        heartbeat_interval_ms: 0,
+        ping_interval_ms: 50,
+        pong_timeout_ms: 200,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    // A client that is never polled never answers the server's pings
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+    assert_eq!(state.connections.len(), 1);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while state.connections.len() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("connection was not closed");
+    assert_eq!(state.metrics.ping_timeouts.get(), 1);
+
+    // The pings sent before the timeout, then the close frame
+    let mut pings = 0;
+    let frame = loop {
+        match client.next().await.unwrap().unwrap() {
+            WsMessage::Ping(_) => pings += 1,
+            WsMessage::Close(frame) => break frame.unwrap(),
+            msg => panic!("unexpected message {msg:?}"),
+        }
+    };
+    assert!(pings >= 3);
+    assert_eq!(u16::from(frame.code), CLOSE_PING_TIMEOUT);
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_reports_connected_clients() {
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let url = serve(state).await;
+    let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    // Answered once the connection is registered
+    client.send(WsMessage::text(r#""GetStats""#)).await.unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Stats");
+
+    let metrics_url = url.replace("ws://", "http://").replace("/ws", "/metrics");
+    let response = reqwest::get(metrics_url).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("custom_broadcaster_connected_clients 1\n"));
+    assert!(body.contains(r#"custom_broadcaster_messages_sent{type="Stats"} 1"#));
+}
+
+#[test]
+fn test_retention_buffer_byte_budget() {
+    let mut outputs = new_outputs();
+    let id = ObjectID::random();
+    outputs
+        .written
+        .insert(id, new_object(id, Owner::AddressOwner(sender_of(&outputs))));
+    let outputs = Arc::new(outputs);
+    let size = outputs.written[&id].object_size_for_gas_metering();
+
+    // Room for two entries by bytes, even though the count limit allows more
+    let buffer = RetentionBuffer::new(10, Some(size * 2));
+    for seq in 1..=4 {
+        buffer.push(seq, outputs.clone().into());
+    }
+    assert_eq!(buffer.push(5, outputs.clone().into()), (2, size * 2));
+    assert_eq!(buffer.since(0)[0].0, 4);
+
+    // Retention disabled
+    let buffer = RetentionBuffer::new(0, None);
+    buffer.push(1, outputs.into());
+    assert!(buffer.since(0).is_empty());
+}
+
+#[test]
+fn test_payloads_serialized_once_per_transaction() {
+    let mut outputs = new_outputs();
+    let pool_id = ObjectID::random();
+    outputs
+        .written
+        .insert(pool_id, new_object(pool_id, Owner::Immutable));
+    let sender = sender_of(&outputs);
+    let ingested = IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(outputs),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    };
+
+    let mut pools = Subscriptions::default();
+    pools.apply(SubscriptionRequest::SubscribePool(pool_id));
+    pools.apply(SubscriptionRequest::SubscribeAccount(sender));
+    let mut firehose = Subscriptions::default();
+    firehose.apply(SubscriptionRequest::SubscribeAll);
+    firehose.apply(SubscriptionRequest::SubscribePool(pool_id));
+
+    let first = pools.payloads_for(&ingested);
+    let second = firehose.payloads_for(&ingested);
+    assert_eq!(first.len(), 2);
+    assert_eq!(second.len(), 2);
+    assert_eq!(ingested.payloads.entries.lock().len(), 2);
+
+    // The firehose connection reuses the buffers serialized for the first one
+    let (pool, activity) = (first[0].1.to_text().unwrap(), first[1].1.to_text().unwrap());
+    assert_eq!(second[0].1.to_text().unwrap().as_ptr(), activity.as_ptr());
+    assert_eq!(second[1].1.to_text().unwrap().as_ptr(), pool.as_ptr());
+    assert_eq!(first[0].0, "PoolUpdate");
+    assert_eq!(
+        pool,
+        serde_json::to_string(&pools.messages_for(&ingested.outputs, None)[0]).unwrap()
+    );
+}
+
+#[test]
+fn test_table_subscription_emits_written_children_only() {
+    let table_id = ObjectID::random();
+    let other_table = ObjectID::random();
+    let child = ObjectID::random();
+    let unrelated = ObjectID::random();
+
+    let mut outputs = new_outputs();
+    outputs.written.insert(
+        child,
+        new_object(child, Owner::ObjectOwner(table_id.into())),
+    );
+    outputs.written.insert(
+        unrelated,
+        new_object(unrelated, Owner::ObjectOwner(other_table.into())),
+    );
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Data,
+        windows: vec![],
+        snapshot: false,
+    });
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    match &messages[0] {
+        StreamMessage::TableFieldUpdate {
+            table_id: t,
+            field_id,
+            version,
+            object,
+            ..
+        } => {
+            assert_eq!(*t, table_id);
+            assert_eq!(*field_id, child);
+            assert_eq!(*version, OBJECT_START_VERSION.value());
+            assert!(!object.is_empty());
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_table_snapshot_holds_back_covered_updates() {
+    let table_id = ObjectID::random();
+    let child = ObjectID::random();
+    let mut outputs = new_outputs();
+    outputs.written.insert(
+        child,
+        new_object(child, Owner::ObjectOwner(table_id.into())),
+    );
+    let ingested = IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(outputs),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    };
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Data,
+        windows: vec![],
+        snapshot: false,
+    });
+    let version = OBJECT_START_VERSION.value();
+
+    // The snapshot already had this version of the field
+    subscriptions.snapshot_versions.insert(child, version);
+    assert!(subscriptions.payloads_for(&ingested).is_empty());
+
+    // An update newer than the snapshot is sent, and so is every later one
+    subscriptions.snapshot_versions.insert(child, version - 1);
+    assert_eq!(subscriptions.payloads_for(&ingested).len(), 1);
+    assert!(subscriptions.snapshot_versions.is_empty());
+    assert_eq!(subscriptions.payloads_for(&ingested).len(), 1);
+
+    let parsed: SubscriptionRequest = serde_json::from_str(
+        r#"{"SubscribeTable":{"table_id":"0x5","windows":[],"snapshot":true}}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        parsed,
+        SubscriptionRequest::SubscribeTable { snapshot: true, .. }
+    ));
+}
+
+#[test]
+fn test_digest_format_option() {
+    let outputs = new_outputs();
+    let digest = *outputs.transaction.digest();
+    let ingested = IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(outputs),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    };
+
+    let options: ConnectionOptions = serde_json::from_str(r#"{"digest":"hex"}"#).unwrap();
+    let mut hex = Subscriptions::new(&options, None, None);
+    hex.apply(SubscriptionRequest::SubscribeAll);
+    let mut base58 = Subscriptions::new(&ConnectionOptions::default(), None, None);
+    base58.apply(SubscriptionRequest::SubscribeAll);
+
+    let rendered = |subscriptions: &Subscriptions| match &subscriptions
+        .messages_for(&ingested.outputs, None)[0]
+    {
+        StreamMessage::AccountActivity { digest, .. } => digest.clone(),
+        other => panic!("unexpected message {:?}", other),
+    };
+    assert_eq!(rendered(&base58), digest.to_string());
+    assert_eq!(rendered(&hex), format!("0x{}", Hex::encode(digest.inner())));
+
+    // Connections with different formats must not share serialized payloads
+    let hex_payload = hex.payloads_for(&ingested).remove(0).1;
+    let base58_payload = base58.payloads_for(&ingested).remove(0).1;
+    let (hex_payload, base58_payload) = (
+        hex_payload.to_text().unwrap(),
+        base58_payload.to_text().unwrap(),
+    );
+    assert!(hex_payload.contains(&rendered(&hex)));
+    assert!(base58_payload.contains(&digest.to_string()));
+}
+
+#[test]
+fn test_byte_format_option() {
+    let table_id = ObjectID::random();
+    let child = ObjectID::random();
+    let mut outputs = new_outputs();
+    outputs.written.insert(
+        child,
+        new_object(child, Owner::ObjectOwner(table_id.into())),
+    );
+    let ingested = IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(outputs),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    };
+
+    let payload = |bytes: &str| {
+        let options: ConnectionOptions =
+            serde_json::from_str(&format!(r#"{{"bytes":"{bytes}"}}"#)).unwrap();
+        let mut subscriptions = Subscriptions::new(&options, None, None);
+        subscriptions.apply(SubscriptionRequest::SubscribeTable {
+            table_id,
+            mode: TableMode::Data,
+            windows: vec![],
+            snapshot: false,
+        });
+        let payload = subscriptions.payloads_for(&ingested).remove(0).1;
+        let json: serde_json::Value = serde_json::from_str(payload.to_text().unwrap()).unwrap();
+        json["data"]["object"].clone()
+    };
+
+    let contents = ingested.outputs.written[&child]
+        .data
+        .try_as_move()
+        .unwrap()
+        .contents()
+        .to_vec();
+    assert_eq!(payload("array"), serde_json::json!(contents));
+    assert_eq!(
+        payload("base64"),
+        serde_json::json!(Base64::encode(&contents))
+    );
+    assert_eq!(
+        payload("hex"),
+        serde_json::json!(format!("0x{}", Hex::encode(&contents)))
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_block_strategy_waits_for_slowest_receiver() {
+    let config = BroadcasterConfig {
+        broadcast_capacity: 2,
+        overflow_strategy: OverflowStrategy::Block,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let ingested = Arc::new(IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(new_outputs()),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    });
+
+    // Without connections there is nobody to wait for
+    wait_for_slowest_receiver(&state).await;
+
+    let mut rx = state.tx.subscribe();
+    state.tx.send(ingested.clone()).unwrap();
+    state.tx.send(ingested.clone()).unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_secs(1), wait_for_slowest_receiver(&state))
+            .await
+            .is_err()
+    );
+
+    // A receive by the connection wakes the wait
+    let waiting = tokio::spawn({
+        let state = state.clone();
+        async move { wait_for_slowest_receiver(&state).await }
+    });
+    rx.recv().await.unwrap();
+    state.receiver_advanced.notify_waiters();
+    tokio::time::timeout(Duration::from_secs(1), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // So does shutdown, however full the buffer
+    state.tx.send(ingested.clone()).unwrap();
+    state.shutdown.cancel();
+    wait_for_slowest_receiver(&state).await;
+}
+
+fn new_event(type_: &str) -> Event {
+    let type_ = sui_types::parse_sui_struct_tag(type_).unwrap();
+    Event {
+        package_id: ObjectID::from(type_.address),
+        transaction_module: type_.module.clone(),
+        sender: SuiAddress::ZERO,
+        type_,
+        contents: vec![],
+    }
+}
+
+#[test]
+fn test_event_filter_matches_type() {
+    let filter = EventFilter::new(None, None, Some("0x42::pool::SwapEvent"), vec![]).unwrap();
+    assert!(filter.matches(&new_event("0x42::pool::SwapEvent"), None));
+    assert!(!filter.matches(&new_event("0x42::pool::AddLiquidityEvent"), None));
+    assert!(!filter.matches(&new_event("0x43::pool::SwapEvent"), None));
+
+    // Without a layout the field filters cannot be evaluated, so only the type is checked
+    let filter = EventFilter::new(
+        None,
+        None,
+        Some("0x42::pool::SwapEvent"),
+        vec![FieldFilter {
+            path: "pool_id".to_string(),
+            value: serde_json::json!("0x1"),
+        }],
+    )
+    .unwrap();
+    assert!(filter.matches(&new_event("0x42::pool::SwapEvent"), None));
+
+    assert!(EventFilter::new(None, None, Some("not a type"), vec![]).is_err());
+}
+
+#[test]
+fn test_event_filter_matches_package_and_module() {
+    let package = ObjectID::from_single_byte(0x42);
+    let filter = EventFilter::new(Some(package), None, None, vec![]).unwrap();
+    assert!(filter.matches(&new_event("0x42::pool::SwapEvent"), None));
+    assert!(filter.matches(&new_event("0x42::router::RouteEvent"), None));
+    assert!(!filter.matches(&new_event("0x43::pool::SwapEvent"), None));
+
+    let filter = EventFilter::new(Some(package), Some("pool"), None, vec![]).unwrap();
+    assert!(filter.matches(&new_event("0x42::pool::SwapEvent"), None));
+    assert!(!filter.matches(&new_event("0x42::router::RouteEvent"), None));
+
+    // Package, module and type must all match
+    let filter = EventFilter::new(
+        Some(package),
+        Some("pool"),
+        Some("0x42::pool::SwapEvent"),
+        vec![],
+    )
+    .unwrap();
+    assert!(filter.matches(&new_event("0x42::pool::SwapEvent"), None));
+    assert!(!filter.matches(&new_event("0x42::pool::AddLiquidityEvent"), None));
+
+    // A filter must not match every event, and a module needs its package
+    assert!(EventFilter::new(None, None, None, vec![]).is_err());
+    assert!(EventFilter::new(None, Some("pool"), Some("0x42::pool::SwapEvent"), vec![]).is_err());
+    assert!(EventFilter::new(Some(package), Some("not a module"), None, vec![]).is_err());
+}
+
+#[test]
+fn test_field_filter_matches_decoded_event() {
+    let event = serde_json::json!({
+        "pool_id": "0xabc",
+        "amount": "100",
+        "tick": { "bits": 5 },
+    });
+    let filter = |path: &str, value: serde_json::Value| FieldFilter {
+        path: path.to_string(),
+        value,
+    };
+
+    assert!(filter("pool_id", serde_json::json!("0xabc")).matches(&event));
+    assert!(!filter("pool_id", serde_json::json!("0xdef")).matches(&event));
+    // u64 fields decode as strings but may be given as numbers
+    assert!(filter("amount", serde_json::json!(100)).matches(&event));
+    assert!(filter("tick.bits", serde_json::json!(5)).matches(&event));
+    assert!(!filter("tick.missing", serde_json::json!(5)).matches(&event));
+}
+
+#[test]
+fn test_subscribe_events_emits_matching_events_only() {
+    let mut outputs = new_outputs();
+    outputs.events.data = vec![
+        new_event("0x42::pool::SwapEvent"),
+        new_event("0x42::pool::AddLiquidityEvent"),
+    ];
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(
+        serde_json::from_str(r#"{"SubscribeEvents":{"event_type":"0x42::pool::SwapEvent"}}"#)
+            .unwrap(),
+    );
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    match &messages[0] {
+        StreamMessage::Event { type_, .. } => assert!(type_.ends_with("::pool::SwapEvent")),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_subscribe_events_by_package_skips_other_packages() {
+    let mut outputs = new_outputs();
+    outputs.events.data = vec![
+        new_event("0x42::pool::SwapEvent"),
+        new_event("0x43::pool::SwapEvent"),
+        new_event("0x42::router::RouteEvent"),
+    ];
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(
+        serde_json::from_str(r#"{"SubscribeEvents":{"package_id":"0x42","module":"pool"}}"#)
+            .unwrap(),
+    );
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    match &messages[0] {
+        StreamMessage::Event {
+            package_id,
+            transaction_module,
+            ..
+        } => {
+            assert_eq!(*package_id, ObjectID::from_single_byte(0x42));
+            assert_eq!(transaction_module, "pool");
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+
+    // Neither a package nor a type: rejected rather than matching everything
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(serde_json::from_str(r#"{"SubscribeEvents":{}}"#).unwrap());
+    assert!(subscriptions.messages_for(&outputs, None).is_empty());
+}
+
+#[tokio::test]
+async fn test_ingest_without_subscribers_skips_fan_out() {
+    let config = BroadcasterConfig {
+        retention_capacity: 0,
+        ..Default::default()
+    };
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    let state = AppState::new(&config, None, metrics.clone());
+    let outputs = Arc::new(new_outputs());
+
+    for _ in 0..1000 {
+        ingest(&state, outputs.clone().into()).await;
+    }
+    assert_eq!(metrics.ingested.get(), 1000);
+    assert_eq!(metrics.skipped_no_subscribers.get(), 1000);
+    assert_eq!(state.ingested_seq.load(Ordering::Relaxed), 1000);
+    // Nothing held on to the transaction
+    assert_eq!(Arc::strong_count(&outputs), 1);
+
+    let mut rx = state.tx.subscribe();
+    ingest(&state, outputs.clone().into()).await;
+    assert_eq!(metrics.skipped_no_subscribers.get(), 1000);
+    assert!(Arc::ptr_eq(&rx.recv().await.unwrap().outputs, &outputs));
+}
+
+#[tokio::test]
+async fn test_ready_reflects_ingestion_and_store() {
+    let body = |response: Response| async move {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+    };
+    let metrics = Arc::new(BroadcasterMetrics::new_for_tests());
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        metrics.clone(),
+    ));
+
+    let response = ready(State(state.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        body(response).await["last_ingest_ms"],
+        serde_json::Value::Null
+    );
+
+    // The ingestion loop ends once the execution pipeline drops its sender
+    let (tx, rx) = CustomBroadcaster::channel(10, metrics.clone());
+    tx.try_send(Arc::new(new_outputs()));
+    drop(tx);
+    run_ingestion(state.clone(), rx).await;
+    let response = ready(State(state)).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let reply = body(response).await;
+    assert_eq!(reply["ingesting"], false);
+    assert!(reply["last_ingest_ms"].as_u64().unwrap() > 0);
+
+    let config = BroadcasterConfig {
+        require_store: true,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(&config, None, metrics));
+    let response = ready(State(state)).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body(response).await["store"], false);
+}
+
+#[tokio::test]
+async fn test_health_reports_missing_store() {
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let response = router(state)
+        .oneshot(Request::get("/health").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(
+        reply,
+        serde_json::json!({ "store": false, "ingesting": true })
+    );
+}
+
+#[test]
+fn test_unsubscribe_stops_updates() {
+    let pool_id = ObjectID::random();
+    let other_pool = ObjectID::random();
+    let mut outputs = new_outputs();
+    let sender = sender_of(&outputs);
+    for id in [pool_id, other_pool] {
+        outputs.written.insert(
+            id,
+            new_object(
+                id,
+                Owner::Shared {
+                    initial_shared_version: OBJECT_START_VERSION,
+                },
+            ),
+        );
+    }
+
+    let mut subscriptions = Subscriptions::default();
+    for req in [
+        SubscriptionRequest::SubscribePool(pool_id),
+        SubscriptionRequest::SubscribePool(other_pool),
+        SubscriptionRequest::SubscribeAccount(sender),
+        SubscriptionRequest::SubscribeAll,
+    ] {
+        subscriptions.apply(req);
+    }
+    // Two pool updates, and the sender's activity once for each subscription
+    assert_eq!(subscriptions.messages_for(&outputs, None).len(), 4);
+
+    subscriptions.apply(SubscriptionRequest::UnsubscribePool { pool_id });
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 3);
+    assert!(!messages.iter().any(|msg| matches!(
+        msg,
+        StreamMessage::PoolUpdate { pool_id: id, .. } if *id == pool_id
+    )));
+
+    subscriptions.apply(SubscriptionRequest::UnsubscribeAll);
+    assert_eq!(subscriptions.messages_for(&outputs, None).len(), 2);
+    subscriptions.apply(SubscriptionRequest::UnsubscribeAccount { account: sender });
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    assert!(matches!(
+        &messages[0],
+        StreamMessage::PoolUpdate { pool_id: id, .. } if *id == other_pool
+    ));
+}
+
+#[test]
+fn test_get_subscriptions_reflects_state() {
+    let pool = ObjectID::random();
+    let account = SuiAddress::random_for_testing_only();
+    let mut subscriptions = Subscriptions::default();
+    for req in [
+        SubscriptionRequest::SubscribePool(pool),
+        SubscriptionRequest::SubscribeAccount(account),
+        SubscriptionRequest::SubscribeEvents {
+            package_id: None,
+            module: None,
+            event_type: Some("0x42::pool::SwapEvent".to_string()),
+            field_filters: vec![],
+        },
+        SubscriptionRequest::SubscribeEvents {
+            package_id: Some(ObjectID::from_single_byte(0x43)),
+            module: Some("pool".to_string()),
+            event_type: None,
+            field_filters: vec![],
+        },
+        SubscriptionRequest::GetSubscriptions,
+    ] {
+        subscriptions.apply(req);
+    }
+
+    match subscriptions.snapshot() {
+        StreamMessage::Subscriptions {
+            pools,
+            accounts,
+            owned_objects,
+            new_object_types,
+            tables,
+            table_windows,
+            table_bumps,
+            fields,
+            event_types,
+            event_packages,
+            all,
+            coin_flows,
+        } => {
+            assert_eq!(pools, vec![pool]);
+            assert_eq!(accounts, vec![account]);
+            assert!(owned_objects.is_empty());
+            assert!(new_object_types.is_empty());
+            assert!(tables.is_empty());
+            assert!(table_windows.is_empty());
+            assert!(table_bumps.is_empty());
+            assert!(fields.is_empty());
+            assert_eq!(event_types.len(), 1);
+            assert!(event_types[0].ends_with("::pool::SwapEvent"));
+            assert_eq!(
+                event_packages,
+                vec![format!("{}::pool", ObjectID::from_single_byte(0x43))]
+            );
+            assert!(!all);
+            assert!(!coin_flows);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_credited_queries_send_fields_within_credit() {
+    use futures::FutureExt;
+
+    let field = |index| {
+        Ok(FieldData {
+            index,
+            field_id: ObjectID::random(),
+            bcs_bytes: vec![],
+            version: OBJECT_START_VERSION,
+            tick: None,
+            value_id: None,
+        })
+    };
+    let items = vec![
+        field(1),
+        Err(SuiError::from(sui_types::error::SuiErrorKind::Unknown(
+            "read failed".to_string(),
+        ))),
+        field(2),
+    ];
+    let mut queries = HashMap::new();
+    queries.insert(
+        7,
+        CreditedQuery {
+            table_id: ObjectID::ZERO,
+            stream: futures::stream::iter(items).boxed().peekable(),
+            credit: 1,
+            fields: 0,
+            errors: 0,
+        },
+    );
+    let next =
+        |queries: &mut HashMap<u64, CreditedQuery>| next_credited_field(queries).now_or_never();
+
+    let (request_id, item) = next(&mut queries).unwrap();
+    assert_eq!(request_id, 7);
+    assert_eq!(item.unwrap().unwrap().index, 1);
+    queries.get_mut(&7).unwrap().credit = 0;
+
+    // Errors need no credit, fields do
+    assert!(next(&mut queries).unwrap().1.unwrap().is_err());
+    assert!(next(&mut queries).is_none());
+    queries.get_mut(&7).unwrap().credit = 1;
+    assert_eq!(next(&mut queries).unwrap().1.unwrap().unwrap().index, 2);
+
+    // Nor does the end of the scan
+    queries.get_mut(&7).unwrap().credit = 0;
+    assert!(next(&mut queries).unwrap().1.is_none());
+
+    let parsed: SubscriptionRequest =
+        serde_json::from_str(r#"{"QueryCredit":{"request_id":7,"n":100}}"#).unwrap();
+    assert!(matches!(
+        parsed,
+        SubscriptionRequest::QueryCredit {
+            request_id: 7,
+            n: 100
+        }
+    ));
+}
+
+#[test]
+fn test_query_cache_only_serves_exact_versions() {
+    let query = |parent_version| FieldRangeQuery {
+        table_id: ObjectID::ZERO,
+        windows: vec![IndexWindow {
+            center: 100,
+            range: 10,
+        }],
+        parent_version,
+        with_parent: false,
+        request_id: None,
+        key_type: None,
+        sparse: None,
+    };
+    let answer = || {
+        vec![StreamMessage::QueryComplete {
+            table_id: ObjectID::ZERO,
+            fields: 0,
+            errors: 0,
+            request_id: None,
+        }]
+    };
+
+    let mut cache = QueryCache::new(NonZeroUsize::new(2).unwrap(), Duration::from_secs(60));
+    cache.insert(query(SequenceNumber::from_u64(7)), answer());
+    cache.insert(query(SequenceNumber::MAX), answer());
+    assert_eq!(
+        cache
+            .get(&query(SequenceNumber::from_u64(7)))
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(cache.get(&query(SequenceNumber::from_u64(8))).is_none());
+    assert!(cache.get(&query(SequenceNumber::MAX)).is_none());
+
+    // Bounded to the most recently used entries
+    cache.insert(query(SequenceNumber::from_u64(8)), answer());
+    cache.insert(query(SequenceNumber::from_u64(9)), answer());
+    assert!(cache.get(&query(SequenceNumber::from_u64(7))).is_none());
+
+    // Expired entries are not served
+    let mut cache = QueryCache::new(NonZeroUsize::new(2).unwrap(), Duration::ZERO);
+    cache.insert(query(SequenceNumber::from_u64(7)), answer());
+    assert!(cache.get(&query(SequenceNumber::from_u64(7))).is_none());
+}
+
+#[test]
+fn test_field_subscription_watches_single_child() {
+    let table_id = ObjectID::random();
+    let key_bcs = bcs::to_bytes(&5u64).unwrap();
+    let watched = derive_dynamic_field_id(table_id, &TypeTag::U64, &key_bcs).unwrap();
+    let sibling = ObjectID::random();
+
+    let mut outputs = new_outputs();
+    for id in [watched, sibling] {
+        outputs
+            .written
+            .insert(id, new_object(id, Owner::ObjectOwner(table_id.into())));
+    }
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeField {
+        table_id,
+        key_bcs,
+        key_type: "u64".to_string(),
+    });
+
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1);
+    match &messages[0] {
+        StreamMessage::TableFieldUpdate { field_id, .. } => assert_eq!(*field_id, watched),
+        other => panic!("unexpected message {:?}", other),
+    }
+
+    // The same child is reported once when its table is subscribed as well
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Data,
+        windows: vec![],
+        snapshot: false,
+    });
+    assert_eq!(subscriptions.messages_for(&outputs, None).len(), 2);
+}
+
+#[test]
+fn test_table_windows_select_fields_by_key() {
+    use crate::field_data_query::{KeyCodec, U64Codec};
+    use sui_types::{
+        dynamic_field::{DynamicFieldInfo, Field},
+        id::UID,
+    };
+
+    let table_id = ObjectID::random();
+    let u64_field = |index: u64| {
+        let field = Field {
+            id: UID::new(U64Codec.field_id(table_id, index).unwrap()),
+            name: index,
+            value: index,
+        };
+        let type_ = DynamicFieldInfo::dynamic_field_type(TypeTag::U64, TypeTag::U64);
+        // SAFETY: the contents are a valid BCS value of `type_`
+        let move_obj = unsafe {
+            MoveObject::new_from_execution_with_limit(
+                type_.into(),
+                false,
+                OBJECT_START_VERSION,
+                bcs::to_bytes(&field).unwrap(),
+                u64::MAX,
+            )
+        }
+        .unwrap();
+        Object::new_move(
+            move_obj,
+            Owner::ObjectOwner(table_id.into()),
+            TransactionDigest::ZERO,
+        )
+    };
+
+    let mut outputs = new_outputs();
+    for index in [5, 50, 500, 900] {
+        let field = u64_field(index);
+        outputs.written.insert(field.id(), field);
+    }
+    // A child whose key does not decode
+    let other = ObjectID::random();
+    outputs.written.insert(
+        other,
+        new_object(other, Owner::ObjectOwner(table_id.into())),
+    );
+
+    let request: SubscriptionRequest = serde_json::from_str(&format!(
+        r#"{{"SubscribeTable":{{"table_id":"{table_id}","windows":[
+            {{"center":10,"range":5}},{{"center":500,"range":0}}]}}}}"#
+    ))
+    .unwrap();
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(request);
+
+    let mut sent: Vec<ObjectID> = subscriptions
+        .messages_for(&outputs, None)
+        .iter()
+        .map(|msg| match msg {
+            StreamMessage::TableFieldUpdate { field_id, .. } => *field_id,
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect();
+    sent.sort();
+    let mut expected = [5, 500].map(|index| U64Codec.field_id(table_id, index).unwrap());
+    expected.sort();
+    assert_eq!(sent, expected);
+
+    match subscriptions.snapshot() {
+        StreamMessage::Subscriptions { table_windows, .. } => {
+            assert_eq!(table_windows[&table_id].len(), 2)
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+
+    // Subscribing again without windows watches the whole table
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Data,
+        windows: vec![],
+        snapshot: false,
+    });
+    assert_eq!(subscriptions.messages_for(&outputs, None).len(), 5);
+}
+
+#[test]
+fn test_coin_flows_pair_senders_with_receivers() {
+    use sui_types::balance_change::BalanceChange;
+
+    let sui: TypeTag = "0x2::sui::SUI".parse().unwrap();
+    let usdc: TypeTag = "0x42::usdc::USDC".parse().unwrap();
+    let [a, b, c] = [1u8, 2, 3].map(|byte| SuiAddress::from_bytes([byte; 32]).unwrap());
+    let change = |address, coin_type: &TypeTag, amount| BalanceChange {
+        address,
+        coin_type: coin_type.clone(),
+        amount,
+    };
+
+    // `a` pays 70 SUI to `b` and 20 SUI to `c`, plus 10 SUI of gas; `b` mints 5 USDC
+    let flows = coin_flows::match_flows(vec![
+        change(a, &sui, -100),
+        change(b, &sui, 70),
+        change(c, &sui, 20),
+        change(b, &usdc, 5),
+    ]);
+    let summary: Vec<_> = flows
+        .iter()
+        .map(|f| (f.from, f.to, f.coin_type.ends_with("::sui::SUI"), f.amount))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Some(a), Some(b), true, 70),
+            (Some(a), Some(c), true, 20),
+            (Some(a), None, true, 10),
+            (None, Some(b), false, 5),
+        ]
+    );
+}
+
+#[test]
+fn test_subscribe_coin_flows_requires_store() {
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeCoinFlows);
+    assert!(!subscriptions.coin_flows);
+    assert!(subscriptions.messages_for(&new_outputs(), None).is_empty());
+}
+
+#[test]
+fn test_validate_request_reports_field_path() {
+    match validate_request_body(r#"{"SubscribeTable":{"table_id":"0x5"}}"#) {
+        ValidationReply::Valid { ok, parsed } => {
+            assert!(ok);
+            assert!(matches!(parsed, SubscriptionRequest::SubscribeTable { .. }));
+        }
+        other => panic!("unexpected reply {:?}", other),
+    }
+
+    match validate_request_body(
+        r#"{"QueryFieldRange":{"table_id":"0x5","current_index":"ten","range":1}}"#,
+    ) {
+        ValidationReply::Invalid { ok, path, line, .. } => {
+            assert!(!ok);
+            assert_eq!(path, "QueryFieldRange.current_index");
+            assert_eq!(line, 1);
+        }
+        other => panic!("unexpected reply {:?}", other),
+    }
+
+    for body in [
+        r#"{"SubscribeNothing":{}}"#,
+        r#""SubscribeAll" trailing"#,
+        "",
+    ] {
+        assert!(
+            matches!(
+                validate_request_body(body),
+                ValidationReply::Invalid { ok: false, .. }
+            ),
+            "{body}"
+        );
+    }
+}
+
+#[test]
+fn test_request_lists_are_capped() {
+    let events = |filters: usize| SubscriptionRequest::SubscribeEvents {
+        package_id: None,
+        module: None,
+        event_type: Some("0x2::coin::CoinEvent".to_string()),
+        field_filters: vec![
+            FieldFilter {
+                path: "amount".to_string(),
+                value: serde_json::json!(1),
+            };
+            filters
+        ],
+    };
+    assert_eq!(events(MAX_FIELD_FILTERS).check_list_lengths(), Ok(()));
+    assert_eq!(
+        events(MAX_FIELD_FILTERS + 1).check_list_lengths(),
+        Err(ListTooLong {
+            path: "SubscribeEvents.field_filters",
+            len: MAX_FIELD_FILTERS + 1,
+            max: MAX_FIELD_FILTERS,
+        })
+    );
+
+    let field = SubscriptionRequest::SubscribeField {
+        table_id: ObjectID::random(),
+        key_bcs: vec![0; MAX_KEY_BCS_LEN + 1],
+        key_type: "vector<u8>".to_string(),
+    };
+    assert!(field.check_list_lengths().is_err());
+    assert_eq!(
+        SubscriptionRequest::SubscribeAll.check_list_lengths(),
+        Ok(())
+    );
+
+    let body = serde_json::to_string(&events(MAX_FIELD_FILTERS + 1)).unwrap();
+    match validate_request_body(&body) {
+        ValidationReply::Invalid { ok, path, .. } => {
+            assert!(!ok);
+            assert_eq!(path, "SubscribeEvents.field_filters");
+        }
+        other => panic!("unexpected reply {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_query_field_rejects_bad_key_type() {
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let query = |key_type: &str| FieldValueQuery {
+        table: ObjectID::random(),
+        index: 1,
+        key_type: Some(key_type.to_string()),
+        version: None,
+    };
+
+    for key_type in ["not a type", "vector<u8>", "0x42::pool::Pool"] {
+        let response = query_field(Query(query(key_type)), State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{key_type}");
+    }
+    // A valid key type, but no store to read from
+    let response = query_field(Query(query("u64")), State(state)).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_query_field_range_streams_ndjson() {
+    use crate::field_data_query::{KeyCodec, U64Codec};
+
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let table_id = ObjectID::random();
+    for index in [3, 5] {
+        let field_id = U64Codec.field_id(table_id, index).unwrap();
+        store
+            .insert_object_test_only(new_object(field_id, Owner::ObjectOwner(table_id.into())))
+            .unwrap();
+    }
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        Some(Arc::new(store)),
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let request = || {
+        serde_json::from_value::<FieldRangeRequest>(serde_json::json!({
+            "table_id": table_id,
+            "current_index": 4,
+            "range": 2,
+            "bytes": "hex",
+        }))
+        .unwrap()
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT, NDJSON.parse().unwrap());
+    let response = query_field_range(State(state.clone()), headers, Json(request())).await;
+    assert_eq!(response.headers()[CONTENT_TYPE], NDJSON);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let lines: Vec<serde_json::Value> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let types: Vec<&str> = lines.iter().map(|l| l["type"].as_str().unwrap()).collect();
+    assert_eq!(types, vec!["FieldData", "FieldData", "QueryComplete"]);
+    assert_eq!(lines[0]["data"]["index"], 3);
+    assert_eq!(lines[2]["data"]["fields"], 2);
+
+    // The same messages as a single array
+    let response = query_field_range(State(state), HeaderMap::new(), Json(request())).await;
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let messages: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(messages, lines);
+}
+
+#[tokio::test]
+async fn test_get_fields_route() {
+    use crate::field_data_query::{KeyCodec, U64Codec};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let table_id = ObjectID::random();
+    for index in [5, 3] {
+        let field_id = U64Codec.field_id(table_id, index).unwrap();
+        store
+            .insert_object_test_only(new_object(field_id, Owner::ObjectOwner(table_id.into())))
+            .unwrap();
+    }
+    let config = BroadcasterConfig {
+        max_range: 10,
+        ..Default::default()
+    };
+    let app = router(Arc::new(AppState::new(
+        &config,
+        Some(Arc::new(store)),
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    )));
+    let get = |query: &str| {
+        let request = Request::get(format!("/fields/{table_id}?{query}"))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+
+    let response = get("index=4&range=2&bytes=hex").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let reply: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(reply["table_id"], table_id.to_string());
+    let indices: Vec<_> = reply["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["index"].as_u64().unwrap())
+        .collect();
+    assert_eq!(indices, [3, 5]);
+    assert!(
+        reply["fields"][0]["bcs_bytes"]
+            .as_str()
+            .unwrap()
+            .starts_with("0x")
+    );
+    assert_eq!(reply["errors"], serde_json::json!([]));
+
+    // Checked like `QueryFieldRange`
+    for query in [
+        "index=4&range=11",
+        "index=4&range=2&key_type=0x42::pool::Pool",
+    ] {
+        let response = get(query).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn test_query_field_range_key_type() {
+    use crate::field_data_query::{KeyCodec, SignedIntCodec, U64Codec};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let i32_type = "0x42::i32::I32";
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let u64_table = ObjectID::random();
+    let i32_table = ObjectID::random();
+    let i32_codec = SignedIntCodec::i32(i32_type.parse().unwrap());
+    for index in [3, 5] {
+        for (table_id, field_id) in [
+            (u64_table, U64Codec.field_id(u64_table, index).unwrap()),
+            (i32_table, i32_codec.field_id(i32_table, index).unwrap()),
+        ] {
+            store
+                .insert_object_test_only(new_object(field_id, Owner::ObjectOwner(table_id.into())))
+                .unwrap();
+        }
+    }
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        Some(Arc::new(store)),
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+    let request = |table_id: ObjectID, key_type: Option<&str>| {
+        let request = serde_json::json!({"QueryFieldRange": {
+            "table_id": table_id,
+            "current_index": 4,
+            "range": 2,
+            "parent_version": null,
+            "key_type": key_type,
+        }});
+        WsMessage::text(request.to_string())
+    };
+
+    // Derived as `u64` keys, the IDs of the wrapped keys' fields are not found
+    let cases = [
+        (u64_table, None, vec![3, 5]),
+        (u64_table, Some("u64"), vec![3, 5]),
+        (i32_table, None, vec![]),
+        (i32_table, Some(i32_type), vec![3, 5]),
+    ];
+    for (table_id, key_type, expected) in cases {
+        client.send(request(table_id, key_type)).await.unwrap();
+        let mut indices: Vec<u64> = vec![];
+        loop {
+            let msg = next_json(&mut client).await;
+            match msg["type"].as_str().unwrap() {
+                "FieldData" => indices.push(msg["data"]["index"].as_u64().unwrap()),
+                "QueryComplete" => break,
+                _ => panic!("unexpected message {msg}"),
+            }
+        }
+        assert_eq!(indices, expected, "{key_type:?}");
+    }
+
+    // Only types with a built-in key encoding
+    client
+        .send(request(i32_table, Some("0x42::pool::Pool")))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Error");
+}
+
+#[tokio::test]
+async fn test_query_field_range_sparse_stops_early() {
+    use crate::field_data_query::{KeyCodec, U64Codec};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let dir = tempfile::tempdir().unwrap();
+    let store = AuthorityPerpetualTables::open(dir.path(), None, None);
+    let table_id = ObjectID::random();
+    // Fields at the start of 100 ± 100 and one past a long gap
+    for index in [0, 1, 2, 150] {
+        let field_id = U64Codec.field_id(table_id, index).unwrap();
+        store
+            .insert_object_test_only(new_object(field_id, Owner::ObjectOwner(table_id.into())))
+            .unwrap();
+    }
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        Some(Arc::new(store)),
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+
+    for (sparse, expected) in [(None, vec![0, 1, 2, 150]), (Some(10), vec![0, 1, 2])] {
+        let request = serde_json::json!({"QueryFieldRange": {
+            "table_id": table_id,
+            "current_index": 100,
+            "range": 100,
+            "parent_version": null,
+            "sparse": sparse,
+        }});
+        client
+            .send(WsMessage::text(request.to_string()))
+            .await
+            .unwrap();
+        let mut indices: Vec<u64> = vec![];
+        let complete = loop {
+            let msg = next_json(&mut client).await;
+            match msg["type"].as_str().unwrap() {
+                "FieldData" => indices.push(msg["data"]["index"].as_u64().unwrap()),
+                "QueryComplete" => break msg,
+                _ => panic!("unexpected message {msg}"),
+            }
+        };
+        assert_eq!(indices, expected, "{sparse:?}");
+        assert_eq!(complete["data"]["fields"], expected.len());
+        assert_eq!(complete["data"]["errors"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_query_field_range_rejects_oversized_range() {
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state.clone()).await)
+        .await
+        .unwrap();
+    let request = serde_json::json!({"QueryFieldRange": {
+        "table_id": ObjectID::random(),
+        "current_index": 0,
+        "range": u64::MAX,
+        "parent_version": null,
+    }});
+    client
+        .send(WsMessage::text(request.to_string()))
+        .await
+        .unwrap();
+    let msg = tokio::time::timeout(Duration::from_secs(5), next_json(&mut client))
+        .await
+        .expect("oversized query was not refused");
+    assert_eq!(msg["type"], "Error");
+    assert_eq!(msg["data"]["code"], "RangeTooLarge");
+    assert!(
+        msg["data"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("exceeds the maximum of 100000")
+    );
+
+    // Further windows are bounded too, over HTTP as well
+    let request = serde_json::from_value::<FieldRangeRequest>(serde_json::json!({
+        "table_id": ObjectID::random(),
+        "current_index": 0,
+        "range": 10,
+        "windows": [{"center": 1000, "range": 100_001}],
+    }))
+    .unwrap();
+    let response = query_field_range(State(state), HeaderMap::new(), Json(request)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_query_without_store_reports_store_unavailable() {
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let (mut client, _) = tokio_tungstenite::connect_async(serve(state).await)
+        .await
+        .unwrap();
+    // Answered the same with and without credit
+    for credit in [None, Some(10)] {
+        let request = serde_json::json!({"QueryFieldRange": {
+            "table_id": ObjectID::random(),
+            "current_index": 0,
+            "range": 10,
+            "parent_version": null,
+            "request_id": 1,
+            "credit": credit,
+        }});
+        client
+            .send(WsMessage::text(request.to_string()))
+            .await
+            .unwrap();
+        let msg = next_json(&mut client).await;
+        assert_eq!(msg["type"], "Error", "{credit:?}");
+        assert_eq!(msg["data"]["code"], "StoreUnavailable", "{credit:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_queryable_tables_allowlist() {
+    let allowed = ObjectID::random();
+    let config = BroadcasterConfig {
+        queryable_tables: Some([allowed].into()),
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    assert!(state.may_query(&allowed));
+    assert!(!state.may_query(&ObjectID::random()));
+
+    let query = |table| FieldValueQuery {
+        table,
+        index: 1,
+        key_type: None,
+        version: None,
+    };
+    let response = query_field(Query(query(ObjectID::random())), State(state.clone())).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    // Allowed, but no store to read from
+    let response = query_field(Query(query(allowed)), State(state)).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let unrestricted = AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    );
+    assert!(unrestricted.may_query(&ObjectID::random()));
+}
+
+#[test]
+fn test_replay_on_subscribe_matches_new_subscription_only() {
+    let outputs = Arc::new(new_outputs());
+    let sender = sender_of(&outputs);
+    let retention = RetentionBuffer::new(10, None);
+    for seq in 1..=2 {
+        retention.push(seq, outputs.clone().into());
+    }
+
+    let options: ConnectionOptions = serde_json::from_str(r#"{"replay":true}"#).unwrap();
+    assert!(options.replay);
+    let mut subscriptions = Subscriptions::new(&options, None, None);
+    subscriptions.apply(SubscriptionRequest::SubscribePool(ObjectID::random()));
+
+    let replayed =
+        subscriptions.replay_for(&SubscriptionRequest::SubscribeAccount(sender), &retention);
+    let seqs: Vec<u64> = replayed
+        .iter()
+        .map(|msg| match msg {
+            StreamMessage::Replayed {
+                server_seq,
+                message,
+            } => {
+                let StreamMessage::AccountActivity { account, .. } = **message else {
+                    panic!("unexpected message {:?}", message);
+                };
+                assert_eq!(account, sender);
+                *server_seq
+            }
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect();
+    assert_eq!(seqs, vec![1, 2]);
+
+    let other = SuiAddress::random_for_testing_only();
+    assert!(
+        subscriptions
+            .replay_for(&SubscriptionRequest::SubscribeAccount(other), &retention)
+            .is_empty()
+    );
+    // Replaying leaves the connection's own subscriptions untouched
+    assert!(subscriptions.accounts.is_empty());
+}
+
+#[tokio::test]
+async fn test_late_joining_client_gets_retained_matches() {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let outputs = Arc::new(new_outputs());
+    let sender = sender_of(&outputs);
+    // Ingested while nobody is connected
+    for _ in 0..3 {
+        ingest(&state, outputs.clone().into()).await;
+    }
+
+    let url = format!("{}?replay=true", serve(state.clone()).await);
+    let (mut client, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let subscribe = serde_json::to_string(&SubscriptionRequest::SubscribeAccount(sender)).unwrap();
+    client.send(WsMessage::text(subscribe)).await.unwrap();
+    assert_eq!(next_json(&mut client).await["type"], "Subscribed");
+    for server_seq in 1..=3 {
+        let msg = next_json(&mut client).await;
+        assert_eq!(msg["type"], "Replayed");
+        assert_eq!(msg["data"]["server_seq"], server_seq);
+        assert_eq!(msg["data"]["message"]["type"], "AccountActivity");
+    }
+
+    // Then live
+    ingest(&state, outputs.into()).await;
+    assert_eq!(next_json(&mut client).await["type"], "AccountActivity");
+}
+
+#[test]
+fn test_disconnect_cancels_registered_connection() {
+    let config = BroadcasterConfig {
+        admin_token: Some("secret".to_string()),
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let first = ConnectionGuard::new(state.clone());
+    let second = ConnectionGuard::new(state.clone());
+    let ids: Vec<u64> = state.connections.list().iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![first.id, second.id]);
+
+    assert!(state.connections.disconnect(second.id));
+    assert!(second.cancel.is_cancelled());
+    assert!(!first.cancel.is_cancelled());
+
+    drop(second);
+    assert_eq!(state.connections.len(), 1);
+    assert!(!state.connections.disconnect(first.id + 1));
+
+    let mut headers = HeaderMap::new();
+    assert_eq!(
+        authorize_admin(&state, &headers),
+        Err(StatusCode::UNAUTHORIZED)
+    );
+    headers.insert(AUTHORIZATION, "Bearer wrong".parse().unwrap());
+    assert_eq!(
+        authorize_admin(&state, &headers),
+        Err(StatusCode::UNAUTHORIZED)
+    );
+    headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+    assert_eq!(authorize_admin(&state, &headers), Ok(()));
+
+    // Without a configured token the admin endpoints do not exist
+    let state = AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    );
+    assert_eq!(
+        authorize_admin(&state, &headers),
+        Err(StatusCode::NOT_FOUND)
+    );
+}
+
+#[tokio::test]
+async fn test_ws_upgrade_requires_configured_token() {
+    use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+    let config = BroadcasterConfig {
+        auth_token: Some("secret".to_string()),
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let url = serve(state.clone()).await;
+    let status = |result: Result<_, tungstenite::Error>| match result {
+        Ok(_) => StatusCode::SWITCHING_PROTOCOLS,
+        Err(tungstenite::Error::Http(response)) => response.status(),
+        Err(e) => panic!("unexpected error {e}"),
+    };
+
+    // Accepted as a query parameter or a bearer header
+    let accepted = tokio_tungstenite::connect_async(format!("{url}?token=secret")).await;
+    assert_eq!(status(accepted), StatusCode::SWITCHING_PROTOCOLS);
+    let mut request = url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+    let accepted = tokio_tungstenite::connect_async(request).await;
+    assert_eq!(status(accepted), StatusCode::SWITCHING_PROTOCOLS);
+
+    // Refused before upgrading with a wrong token or none
+    let rejected = tokio_tungstenite::connect_async(format!("{url}?token=wrong")).await;
+    assert_eq!(status(rejected), StatusCode::UNAUTHORIZED);
+    let mut request = url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert(AUTHORIZATION, "Bearer wrong".parse().unwrap());
+    let rejected = tokio_tungstenite::connect_async(request).await;
+    assert_eq!(status(rejected), StatusCode::UNAUTHORIZED);
+    let missing = tokio_tungstenite::connect_async(url.as_str()).await;
+    assert_eq!(status(missing), StatusCode::UNAUTHORIZED);
+
+    // Without a configured token connections stay open to all
+    let state = AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    );
+    assert_eq!(authorize_client(&state, None, &HeaderMap::new()), Ok(()));
+}
+
+#[test]
+fn test_rejections_use_documented_close_codes() {
+    let config = BroadcasterConfig {
+        max_connections: Some(1),
+        overflow_strategy: OverflowStrategy::DisconnectSlowest,
+        ..Default::default()
+    };
+    let state = Arc::new(AppState::new(
+        &config,
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+
+    assert_eq!(admit(&state), Ok(()));
+    let _guard = ConnectionGuard::new(state.clone());
+    assert_eq!(admit(&state), Err(CloseReason::OverCapacity));
+    assert_eq!(on_lagged(&state, 10), Some(CloseReason::SlowConsumer));
+
+    let state = AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    );
+    assert_eq!(admit(&state), Ok(()));
+    assert_eq!(on_lagged(&state, 10), None);
+
+    let expected = [
+        (CloseReason::OverCapacity, 1013),
+        (CloseReason::Disconnected, 1008),
+        (CloseReason::UnsupportedFrame, 1003),
+        (CloseReason::MessageTooBig, 1009),
+        (CloseReason::Unauthorized, CLOSE_UNAUTHORIZED),
+        (CloseReason::SlowConsumer, CLOSE_SLOW_CONSUMER),
+    ];
+    for (reason, code) in expected {
+        let frame = reason.frame();
+        assert_eq!(frame.code, code, "{reason:?}");
+        assert_eq!(frame.reason.as_str(), reason.reason());
+    }
+}
+
+#[cfg(feature = "pool-decoders")]
+#[test]
+fn test_decode_pools_option_emits_pool_state() {
+    use sui_types::{balance::Balance, collection_types::Table};
+
+    let table = || Table {
+        id: ObjectID::random(),
+        size: 0,
+    };
+    let tree = || deepbook::CritbitTree {
+        root: 0,
+        internal_nodes: table(),
+        leaves: table(),
+        min_leaf: 0,
+        max_leaf: 0,
+        next_internal_node_index: 0,
+        next_leaf_index: 0,
+    };
+    let custodian = || deepbook::Custodian {
+        id: ObjectID::random(),
+        account_balances: table(),
+    };
+    let pool_id = ObjectID::random();
+    let pool = deepbook::Pool {
+        id: pool_id,
+        bids: tree(),
+        asks: tree(),
+        next_bid_order_id: 0,
+        next_ask_order_id: 1 << 63,
+        usr_open_orders: table(),
+        taker_fee_rate: 2_500_000,
+        maker_rebate_rate: 1_500_000,
+        tick_size: 1000,
+        lot_size: 10,
+        base_custodian: custodian(),
+        quote_custodian: custodian(),
+        creation_fee: Balance::new(100),
+        base_asset_trading_fees: Balance::new(0),
+        quote_asset_trading_fees: Balance::new(7),
+    };
+    let pool_tag: StructTag = "0xdee9::clob_v2::Pool<0x2::sui::SUI, 0x2::sui::SUI>"
+        .parse()
+        .unwrap();
+    // SAFETY: the contents are a valid BCS value of `pool_type`
+    let object = unsafe {
+        MoveObject::new_from_execution_with_limit(
+            pool_tag.clone().into(),
+            true,
+            OBJECT_START_VERSION,
+            bcs::to_bytes(&pool).unwrap(),
+            u64::MAX,
+        )
+    }
+    .unwrap();
+    let mut outputs = new_outputs();
+    outputs.written.insert(
+        pool_id,
+        Object::new_move(object, Owner::Immutable, TransactionDigest::ZERO),
+    );
+
+    let message = |decode_pools: bool| {
+        let options = ConnectionOptions {
+            decode_pools,
+            ..Default::default()
+        };
+        let mut subscriptions = Subscriptions::new(&options, None, None);
+        if decode_pools {
+            subscriptions.pool_decoders = Some(Arc::new(PoolDecoderRegistry::default()));
+        }
+        subscriptions.apply(SubscriptionRequest::SubscribePool(pool_id));
+        subscriptions.messages_for(&outputs, None).remove(0)
+    };
+
+    let StreamMessage::PoolState {
+        pool_type, fields, ..
+    } = message(true)
+    else {
+        panic!("expected a decoded pool");
+    };
+    assert_eq!(pool_type, pool_tag.to_canonical_string(true));
+    assert_eq!(fields, serde_json::to_value(&pool).unwrap());
+    assert!(matches!(message(false), StreamMessage::PoolUpdate { .. }));
+
+    // Objects of other types still get raw updates
+    let mut registry = PoolDecoderRegistry::empty();
+    registry.register::<CetusClmmPool>();
+    assert!(registry.decode(&outputs.written[&pool_id]).is_none());
+}
+
+#[test]
+fn test_stats_count_sent_messages_per_connection() {
+    let state = Arc::new(AppState::new(
+        &BroadcasterConfig::default(),
+        None,
+        Arc::new(BroadcasterMetrics::new_for_tests()),
+    ));
+    let busy = ConnectionGuard::new(state.clone());
+    let quiet = ConnectionGuard::new(state.clone());
+    busy.sent.record(100);
+    busy.sent.record(20);
+
+    let StreamMessage::Stats(stats) = busy.stats() else {
+        panic!("expected stats");
+    };
+    assert_eq!(stats.id, busy.id);
+    assert_eq!((stats.messages_sent, stats.bytes_sent), (2, 120));
+    let StreamMessage::Stats(stats) = quiet.stats() else {
+        panic!("expected stats");
+    };
+    assert_eq!((stats.messages_sent, stats.bytes_sent), (0, 0));
+
+    let listed: Vec<(u64, u64)> = state
+        .connections
+        .list()
+        .iter()
+        .map(|c| (c.messages_sent, c.bytes_sent))
+        .collect();
+    assert_eq!(listed, vec![(2, 120), (0, 0)]);
+}
+
+#[test]
+fn test_invalidate_mode_sends_one_version_bump_per_table() {
+    let table_id = ObjectID::random();
+    let children = [ObjectID::random(), ObjectID::random()];
+    let mut outputs = new_outputs();
+    for child in children {
+        outputs.written.insert(
+            child,
+            new_object(child, Owner::ObjectOwner(table_id.into())),
+        );
+    }
+    let lamport_version = outputs.effects.lamport_version().value();
+
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Invalidate,
+        windows: vec![],
+        snapshot: false,
+    });
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 1, "{messages:?}");
+    let StreamMessage::TableVersionBump {
+        table_id: bumped,
+        new_version,
+        ..
+    } = &messages[0]
+    else {
+        panic!("unexpected message {:?}", messages[0]);
+    };
+    assert_eq!(*bumped, table_id);
+    assert_eq!(*new_version, lamport_version);
+
+    // Subscribing again in data mode switches back to field deltas
+    subscriptions.apply(SubscriptionRequest::SubscribeTable {
+        table_id,
+        mode: TableMode::Data,
+        windows: vec![],
+        snapshot: false,
+    });
+    let messages = subscriptions.messages_for(&outputs, None);
+    assert_eq!(messages.len(), 2);
+    assert!(
+        messages
+            .iter()
+            .all(|m| matches!(m, StreamMessage::TableFieldUpdate { .. }))
+    );
+
+    let parsed: SubscriptionRequest =
+        serde_json::from_str(r#"{"SubscribeTable":{"table_id":"0x5","mode":"invalidate"}}"#)
+            .unwrap();
+    assert!(matches!(
+        parsed,
+        SubscriptionRequest::SubscribeTable {
+            mode: TableMode::Invalidate,
+            ..
+        }
+    ));
+}
+
+/// A transaction writing pool `pool_id` at `version`, with the given contents
+fn pool_written_at(pool_id: ObjectID, version: u64, contents: Vec<u8>) -> IngestedTransaction {
+    // SAFETY: the contents are never decoded as the pool type
+    let object = unsafe {
+        MoveObject::new_from_execution_with_limit(
+            "0x42::pool::Pool".parse::<StructTag>().unwrap().into(),
+            false,
+            SequenceNumber::from_u64(version),
+            contents,
+            u64::MAX,
+        )
+    }
+    .unwrap();
+    let mut outputs = new_outputs();
+    outputs.written.insert(
+        pool_id,
+        Object::new_move(
+            object,
+            Owner::Shared {
+                initial_shared_version: OBJECT_START_VERSION,
+            },
+            TransactionDigest::ZERO,
+        ),
+    );
+    IngestedTransaction {
+        seq: 1,
+        outputs: Arc::new(outputs),
+        checkpoint: None,
+        payloads: PayloadCache::default(),
+    }
+}
+
+#[test]
+fn test_pool_diffs_option_sends_diffs_against_last_sent_version() {
+    let pool_id = ObjectID::random();
+    let pool_at = |version, contents| pool_written_at(pool_id, version, contents);
+    let mut first = pool_id.to_vec();
+    first.extend([7u8; 200]);
+    let mut second = first.clone();
+    second[40] = 1;
+    second[45] = 2;
+    second[200] = 3;
+
+    let options: ConnectionOptions = serde_json::from_str(r#"{"pool_diffs":true}"#).unwrap();
+    let mut subscriptions = Subscriptions::new(&options, None, None);
+    subscriptions.apply(SubscriptionRequest::SubscribePool(pool_id));
+    let update = |subscriptions: &mut Subscriptions, ingested: &IngestedTransaction| {
+        let payload = subscriptions.payloads_for(ingested).remove(0).1;
+        let json: serde_json::Value = serde_json::from_str(payload.to_text().unwrap()).unwrap();
+        json["data"].clone()
+    };
+
+    // Nothing to diff against yet
+    let data = update(&mut subscriptions, &pool_at(5, first.clone()));
+    assert_eq!(data["object"], serde_json::json!(first));
+    assert!(data.get("diff").is_none());
+
+    let data = update(&mut subscriptions, &pool_at(9, second.clone()));
+    assert!(data["object"].is_null());
+    assert_eq!(data["diff"]["base_version"], 5);
+    assert_eq!(data["diff"]["version"], 9);
+    assert_eq!(data["diff"]["splices"].as_array().unwrap().len(), 2);
+    let diff = PoolDiff::new(5, &first, 9, &second, ByteFormat::Array).unwrap();
+    assert_eq!(diff.apply(&first), second);
+
+    // A change of length is one splice between the common prefix and suffix
+    let mut longer = second.clone();
+    longer.insert(100, 0xff);
+    let diff = PoolDiff::new(9, &second, 10, &longer, ByteFormat::Array).unwrap();
+    assert_eq!(diff.splices.len(), 1);
+    assert_eq!(diff.apply(&second), longer);
+
+    // Without the option, every update carries the whole object
+    let mut full = Subscriptions::default();
+    full.apply(SubscriptionRequest::SubscribePool(pool_id));
+    update(&mut full, &pool_at(5, first.clone()));
+    let data = update(&mut full, &pool_at(9, second.clone()));
+    assert_eq!(data["object"], serde_json::json!(second));
+}
+
+#[test]
+fn test_skip_unchanged_drops_identical_pool_updates() {
+    let pool_id = ObjectID::random();
+    let mut contents = pool_id.to_vec();
+    contents.extend([7u8; 16]);
+    let mut changed = contents.clone();
+    changed[40] = 1;
+    let writes = [
+        pool_written_at(pool_id, 5, contents.clone()),
+        pool_written_at(pool_id, 6, contents.clone()),
+        pool_written_at(pool_id, 7, changed.clone()),
+        pool_written_at(pool_id, 8, changed),
+        pool_written_at(pool_id, 9, contents),
+    ];
+    let sent = |subscriptions: &mut Subscriptions| -> Vec<usize> {
+        writes
+            .iter()
+            .enumerate()
+            .filter(|(_, ingested)| !subscriptions.payloads_for(ingested).is_empty())
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    let request: SubscriptionRequest = serde_json::from_str(&format!(
+        r#"{{"SubscribePoolWithOptions":{{"pool_id":"{pool_id}","skip_unchanged":true}}}}"#
+    ))
+    .unwrap();
+    let mut subscriptions = Subscriptions::default();
+    subscriptions.apply(request.clone());
+    assert_eq!(sent(&mut subscriptions), vec![0, 2, 4]);
+
+    // Version bumps matter to plain subscriptions, which get every write
+    let mut plain = Subscriptions::default();
+    plain.apply(SubscriptionRequest::SubscribePool(pool_id));
+    assert_eq!(sent(&mut plain), vec![0, 1, 2, 3, 4]);
+
+    // Subscribing again without the option stops skipping
+    subscriptions.apply(SubscriptionRequest::SubscribePool(pool_id));
+    assert_eq!(sent(&mut subscriptions), vec![0, 1, 2, 3, 4]);
+
+    // Ordered connections get every write regardless
+    let options = ConnectionOptions {
+        ordered: true,
+        ..Default::default()
+    };
+    let mut ordered = Subscriptions::new(&options, None, None);
+    ordered.apply(request);
+    assert_eq!(sent(&mut ordered), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_ordered_resume_stops_at_live_delivery() {
+    let buffer = RetentionBuffer::new(10, None);
+    let outputs = Arc::new(new_outputs());
+    for seq in 1..=6 {
+        buffer.push(seq, outputs.clone().into());
+    }
+    let seqs = |resumed: Vec<(u64, ExecutedTransaction)>| {
+        resumed.into_iter().map(|(seq, _)| seq).collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        seqs(resumed_transactions(&buffer, 2, None)),
+        vec![3, 4, 5, 6]
+    );
+    // 5 and 6 are queued for live delivery after 4
+    assert_eq!(seqs(resumed_transactions(&buffer, 2, Some(4))), vec![3, 4]);
+    assert!(resumed_transactions(&buffer, 4, Some(4)).is_empty());
+}
+
+#[cfg(feature = "parquet-sink")]
+#[test]
+fn test_parquet_sink_writes_row_groups_and_rotates_files() {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use parquet_sink::ParquetSink;
+    use sui_config::broadcaster_config::ParquetSinkConfig;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut sink = ParquetSink::new(ParquetSinkConfig {
+        directory: dir.path().to_path_buf(),
+        requests: vec![r#""SubscribeAll""#.to_string()],
+        row_group_rows: 2,
+        flush_interval_ms: 1000,
+        rotate_interval_ms: 60_000,
+    });
+    let heartbeat = |timestamp_ms| StreamMessage::Heartbeat {
+        timestamp_ms,
+        server_seq: 1,
+    };
+    // 2026-10-15T10:00:00Z
+    let start_ms = 1_792_058_400_000;
+
+    for i in 0..3 {
+        sink.push(start_ms + i, "digest", &heartbeat(i));
+    }
+    sink.flush(start_ms).unwrap();
+    sink.push(start_ms + 10, "digest", &heartbeat(10));
+    sink.flush(start_ms + 10).unwrap();
+    // A file due for rotation is closed even without new rows, and the next one started with
+    // the next row
+    sink.flush(start_ms + 60_000).unwrap();
+    sink.push(start_ms + 60_001, "digest", &heartbeat(60_001));
+    sink.close(start_ms + 60_001).unwrap();
+
+    let hour = dir.path().join("date=2026-10-15").join("hour=10");
+    let mut files: Vec<_> = std::fs::read_dir(&hour)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        vec![
+            format!("part-{start_ms}.parquet"),
+            format!("part-{}.parquet", start_ms + 60_001),
+        ]
+    );
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(
+        std::fs::File::open(hour.join(&files[0])).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    let batches: Vec<_> = reader.build().unwrap().map(Result::unwrap).collect();
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 4);
+    let types = batches[0]
+        .column_by_name("message_type")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<arrow_array::StringArray>()
+        .unwrap();
+    assert_eq!(types.value(0), "Heartbeat");
+}
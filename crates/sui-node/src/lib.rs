@@ -534,8 +534,13 @@ impl SuiNode {
         let backpressure_manager =
             BackpressureManager::new_from_checkpoint_store(&checkpoint_store);
 
-        let store =
-            AuthorityStore::open(perpetual_tables, &genesis, &config, &prometheus_registry).await?;
+        let store = AuthorityStore::open(
+            perpetual_tables.clone(),
+            &genesis,
+            &config,
+            &prometheus_registry,
+        )
+        .await?;
 
         let cur_epoch = store.get_recovery_epoch_at_restart()?;
         let committee = committee_store
@@ -547,21 +552,21 @@ impl SuiNode {
         let cache_metrics = Arc::new(ResolverMetrics::new(&prometheus_registry));
         let signature_verifier_metrics = SignatureVerifierMetrics::new(&prometheus_registry);
 
-        // [NEW] Initialize Custom Broadcaster Channel
-        // "A separate async task... should receive updates from the channel"
-        // Channel capacity 1000
-        let (broadcaster_tx, broadcaster_rx) = tokio::sync::mpsc::channel(1000);
-
-        // Spawn the broadcaster
-        // "Spawn a new thread or task that runs the broadcaster"
-        CustomBroadcaster::spawn(broadcaster_rx, 9002);
+        // Custom broadcaster: when configured, the execution cache feeds transaction outputs
+        // into a bounded channel drained by the broadcaster's ingestion loop.
+        let broadcaster_tx = CustomBroadcaster::maybe_spawn(
+            config.broadcaster_config.clone(),
+            Some(perpetual_tables),
+            Some(checkpoint_store.clone()),
+            &prometheus_registry,
+        )?;
 
         let cache_traits = build_execution_cache(
             &config.execution_cache,
             &prometheus_registry,
             &store,
             backpressure_manager.clone(),
-            Some(broadcaster_tx),
+            broadcaster_tx,
         );
 
         let auth_agg = {
@@ -268,6 +268,7 @@ impl ValidatorConfigBuilder {
             validator_client_monitor_config: None,
             fork_recovery: None,
             transaction_driver_config: Some(TransactionDriverConfig::default()),
+            broadcaster_config: None,
         }
     }
 
@@ -608,6 +609,7 @@ impl FullnodeConfigBuilder {
             transaction_driver_config: self
                 .transaction_driver_config
                 .or(Some(TransactionDriverConfig::default())),
+            broadcaster_config: None,
         }
     }
 }
@@ -7,6 +7,7 @@ pub(crate) mod safe_iter;
 
 use crate::memstore::{InMemoryBatch, InMemoryDB};
 use crate::rocks::errors::typed_store_err_from_bcs_err;
+use crate::rocks::errors::typed_store_err_from_bincode_err;
 use crate::rocks::errors::typed_store_err_from_rocks_err;
 pub use crate::rocks::options::{
     DBMapTableConfigMap, DBOptions, ReadWriteOptions, default_db_options, read_size_from_env,
@@ -23,6 +24,7 @@ use crate::{
     traits::{Map, TableSummary},
 };
 use backoff::backoff::Backoff;
+use bincode::Options;
 use fastcrypto::hash::{Digest, HashFunction};
 use mysten_common::debug_fatal;
 use prometheus::{Histogram, HistogramTimer};
@@ -62,6 +64,14 @@ pub struct RocksDB {
     pub underlying: rocksdb::DBWithThreadMode<MultiThreaded>,
 }
 
+/// A point-in-time view of the database of a `DBMap`, taken by [`DBMap::snapshot`]
+///
+/// Reads through it see no write committed after it was taken. Only RocksDB has snapshots: on
+/// other backends it is empty and reads through it see the latest state.
+pub struct DBSnapshot<'a>(
+    Option<rocksdb::SnapshotWithThreadMode<'a, rocksdb::DBWithThreadMode<MultiThreaded>>>,
+);
+
 impl Drop for RocksDB {
     fn drop(&mut self) {
         self.underlying.cancel_all_background_work(/* wait */ true);
@@ -1112,6 +1122,70 @@ impl<K, V> DBMap<K, V> {
             },
         }
     }
+
+    /// Take a snapshot of the database, for reads that must all see the same point in time
+    pub fn snapshot(&self) -> DBSnapshot<'_> {
+        match &self.db.storage {
+            Storage::Rocks(db) => DBSnapshot(Some(db.underlying.snapshot())),
+            _ => DBSnapshot(None),
+        }
+    }
+
+    /// For each of `keys`, in the same order, the last entry at or before it: the first item
+    /// of `reversed_safe_iter_with_bounds(None, Some(key))`, as of `snapshot` if given.
+    ///
+    /// On RocksDB the keys are sought in ascending order with a single raw iterator, instead of
+    /// creating an iterator for each of them.
+    pub fn multi_get_lt_or_eq(
+        &self,
+        keys: &[K],
+        snapshot: Option<&DBSnapshot<'_>>,
+    ) -> Result<Vec<Option<(K, V)>>, TypedStoreError>
+    where
+        K: Serialize + DeserializeOwned + Clone,
+        V: Serialize + DeserializeOwned,
+    {
+        let Storage::Rocks(db) = &self.db.storage else {
+            return keys
+                .iter()
+                .map(|key| {
+                    self.reversed_safe_iter_with_bounds(None, Some(key.clone()))?
+                        .next()
+                        .transpose()
+                })
+                .collect();
+        };
+        let raw_keys: Vec<Vec<u8>> = keys.iter().map(|key| be_fix_int_ser(key)).collect();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|a, b| raw_keys[*a].cmp(&raw_keys[*b]));
+
+        let config = bincode::DefaultOptions::new()
+            .with_big_endian()
+            .with_fixint_encoding();
+        let mut readopts = self.opts.readopts();
+        if let Some(DBSnapshot(Some(snapshot))) = snapshot {
+            readopts.set_snapshot(snapshot);
+        }
+        let mut db_iter = db
+            .underlying
+            .raw_iterator_cf_opt(&rocks_cf(db, &self.cf), readopts);
+        let mut entries: Vec<Option<(K, V)>> = keys.iter().map(|_| None).collect();
+        for i in order {
+            db_iter.seek_for_prev(&raw_keys[i]);
+            if !db_iter.valid() {
+                db_iter.status().map_err(typed_store_err_from_rocks_err)?;
+                continue;
+            }
+            let raw_key = db_iter.key().expect("Valid iterator failed to get key");
+            let raw_value = db_iter.value().expect("Valid iterator failed to get value");
+            let key = config
+                .deserialize(raw_key)
+                .map_err(typed_store_err_from_bincode_err)?;
+            let value = bcs::from_bytes(raw_value).map_err(typed_store_err_from_bcs_err)?;
+            entries[i] = Some((key, value));
+        }
+        Ok(entries)
+    }
 }
 
 pub enum StorageWriteBatch {
@@ -217,6 +217,57 @@ async fn test_reverse_iter_with_bounds() {
     assert_eq!(iter.next().unwrap(), Ok((999, "999".to_string())));
 }
 
+#[tokio::test]
+async fn test_multi_get_lt_or_eq() {
+    let db = open_map(temp_dir(), None);
+    db.insert(&123, &"123".to_string())
+        .expect("Failed to insert");
+    db.insert(&456, &"456".to_string())
+        .expect("Failed to insert");
+
+    // Unsorted, with a key before every entry and one equal to an entry
+    let result = db
+        .multi_get_lt_or_eq(&[999, 100, 456, 300], None)
+        .expect("Failed to multi get");
+    assert_eq!(
+        result,
+        vec![
+            Some((456, "456".to_string())),
+            None,
+            Some((456, "456".to_string())),
+            Some((123, "123".to_string())),
+        ]
+    );
+    for (key, entry) in [999, 100, 456, 300].into_iter().zip(result) {
+        assert_eq!(
+            get_reverse_iter(&db, None, Some(key))
+                .next()
+                .transpose()
+                .unwrap(),
+            entry
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_multi_get_lt_or_eq_in_snapshot() {
+    let db = open_map(temp_dir(), None);
+    db.insert(&123, &"123".to_string())
+        .expect("Failed to insert");
+    let snapshot = db.snapshot();
+    db.insert(&456, &"456".to_string())
+        .expect("Failed to insert");
+
+    let result = db
+        .multi_get_lt_or_eq(&[999], Some(&snapshot))
+        .expect("Failed to multi get");
+    assert_eq!(result, vec![Some((123, "123".to_string()))]);
+    let result = db
+        .multi_get_lt_or_eq(&[999], None)
+        .expect("Failed to multi get");
+    assert_eq!(result, vec![Some((456, "456".to_string()))]);
+}
+
 #[tokio::test]
 async fn test_remove() {
     let db = open_map(temp_dir(), None);
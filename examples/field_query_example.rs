@@ -14,7 +14,8 @@ use std::sync::Arc;
 
 use sui_core::authority::AuthorityStore;
 use sui_core::field_data_query::{
-    decode_field_value, query_field_data_range, query_field_data_range_validated, FieldData,
+    decode_dynamic_field, query_field_data_range_ordered, query_field_data_range_parallel,
+    query_field_data_range_validated, FieldData, U64Codec,
 };
 use sui_types::base_types::{ObjectID, SequenceNumber};
 use sui_types::object::Object;
@@ -50,32 +51,35 @@ pub async fn handle_broadcaster_message(
 
     // Query ±100000 ticks around the current index
     let range = 100_000u64;
-    let key_type = TypeTag::U64; // Assuming your keys are u64 indices
+    let codec = U64Codec; // Assuming your keys are u64 indices
 
-    // Method 1: Direct query using AuthorityPerpetualTables
-    let field_data = query_field_data_range(
+    // Method 1: Direct query using AuthorityPerpetualTables, fields sorted by index
+    let result = query_field_data_range_ordered(
         store.perpetual_tables(),
         parent_id,
         current_index,
         range,
         parent_version,
-        &key_type,
+        &codec,
     )?;
 
-    println!("Found {} fields in range", field_data.len());
+    println!("Found {} fields in range", result.fields.len());
+    for (index, e) in &result.errors {
+        println!("Failed to read index {}: {}", index, e);
+    }
 
     // Process each field
-    for (index, data) in field_data.iter() {
+    for data in &result.fields {
         println!(
             "Index: {}, Field ID: {}, BCS size: {} bytes",
-            index,
+            data.index,
             data.field_id,
             data.bcs_bytes.len()
         );
 
-        // Decode the BCS bytes into your data structure
-        // Option 1: If the field stores the value directly
-        match decode_field_value::<TickData>(&data.bcs_bytes) {
+        // The bytes are those of the `Field<u64, TickData>` object: decode the value alone,
+        // skipping the field's UID and key, or the key and value together
+        match data.decode_value::<TickData>(&codec) {
             Ok(tick_data) => {
                 println!(
                     "  Decoded: price={}, volume={}, timestamp={}",
@@ -86,26 +90,8 @@ pub async fn handle_broadcaster_message(
                 println!("  Failed to decode tick data: {}", e);
             }
         }
-
-        // Option 2: If the field is a Field<K, V> wrapper (dynamic field structure)
-        // You need to decode the outer Field structure first
-        #[derive(serde::Deserialize)]
-        struct Field<K, V> {
-            id: sui_types::base_types::UID,
-            name: K,
-            value: V,
-        }
-
-        match decode_field_value::<Field<u64, TickData>>(&data.bcs_bytes) {
-            Ok(field) => {
-                println!(
-                    "  Field wrapper - name: {}, value: {:?}",
-                    field.name, field.value
-                );
-            }
-            Err(e) => {
-                println!("  Not a Field wrapper or decode failed: {}", e);
-            }
+        if let Ok((name, value)) = decode_dynamic_field::<u64, TickData>(&data.bcs_bytes) {
+            println!("  Field - name: {}, value: {:?}", name, value);
         }
     }
 
@@ -120,7 +106,6 @@ pub fn query_with_validation(
     parent_version: SequenceNumber,
 ) -> Result<HashMap<u64, FieldData>, Box<dyn std::error::Error>> {
     let range = 100_000u64;
-    let key_type = TypeTag::U64;
 
     // This method validates parent-child ownership
     let results = query_field_data_range_validated(
@@ -129,7 +114,7 @@ pub fn query_with_validation(
         current_index,
         range,
         parent_version,
-        &key_type,
+        &U64Codec,
     )?;
 
     Ok(results)
@@ -203,14 +188,41 @@ pub mod websocket_example {
         SubscribeAll,
     }
 
+    /// Messages are sent as `{"type": <variant>, "data": <fields>}`
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(tag = "type", content = "data")]
+    pub enum BroadcastMessage {
+        PoolUpdate(PoolUpdate),
+        #[serde(other)]
+        Other,
+    }
+
     #[derive(Debug, serde::Deserialize)]
     pub struct PoolUpdate {
         pub pool_id: ObjectID,
-        pub current_tick: u64,
-        pub version: SequenceNumber,
+        /// Version of the pool after the write, to read its fields at
+        pub version: u64,
+        /// Version the pool was read at, `None` if it was created
+        pub previous_version: Option<u64>,
+        /// BCS contents of the pool object
+        pub object: Option<Vec<u8>>,
         // ... other fields
     }
 
+    /// Example: The leading fields of your pool object. BCS decodes structs field by field,
+    /// so a prefix of the layout is enough to read the current tick.
+    #[derive(serde::Deserialize)]
+    struct PoolHeader {
+        _id: sui_types::base_types::UID,
+        current_tick: u64,
+    }
+
+    fn current_tick(object: &[u8]) -> Option<u64> {
+        let mut deserializer = bcs::Deserializer::new(object, bcs::MAX_CONTAINER_DEPTH);
+        let header = <PoolHeader as serde::Deserialize>::deserialize(&mut deserializer).ok()?;
+        Some(header.current_tick)
+    }
+
     pub async fn subscribe_and_query(
         broadcaster_url: &str,
         pool_id: ObjectID,
@@ -232,18 +244,21 @@ pub mod websocket_example {
             let msg = msg?;
             if let Message::Text(text) = msg {
                 // Parse the pool update message
-                if let Ok(update) = serde_json::from_str::<PoolUpdate>(&text) {
+                if let Ok(BroadcastMessage::PoolUpdate(update)) = serde_json::from_str(&text) {
+                    let Some(tick) = update.object.as_deref().and_then(current_tick) else {
+                        continue;
+                    };
                     println!(
-                        "Pool update: pool={}, tick={}, version={}",
-                        update.pool_id, update.current_tick, update.version
+                        "Pool update: pool={}, tick={}, version={} (was {:?})",
+                        update.pool_id, tick, update.version, update.previous_version
                     );
 
-                    // Query field data around the current tick
+                    // Query field data around the current tick, at the version just written
                     match handle_broadcaster_message(
                         store.clone(),
                         update.pool_id,
-                        update.current_tick,
-                        update.version,
+                        tick,
+                        SequenceNumber::from_u64(update.version),
                     )
                     .await
                     {
@@ -258,7 +273,7 @@ pub mod websocket_example {
     }
 }
 
-/// Performance optimization: Parallel query with tokio
+/// Performance optimization: Parallel query, with at most 8 chunks of the range read at once
 pub async fn parallel_query_range(
     store: Arc<AuthorityStore>,
     parent_id: ObjectID,
@@ -266,65 +281,23 @@ pub async fn parallel_query_range(
     range: u64,
     parent_version: SequenceNumber,
 ) -> Result<HashMap<u64, FieldData>, Box<dyn std::error::Error>> {
-    let lower_index = current_index.saturating_sub(range);
-    let upper_index = current_index.saturating_add(range);
-    let key_type = TypeTag::U64;
-
-    // Split the range into chunks for parallel processing
-    let chunk_size = 10_000u64;
-    let mut tasks = vec![];
-
-    for chunk_start in (lower_index..=upper_index).step_by(chunk_size as usize) {
-        let chunk_end = (chunk_start + chunk_size - 1).min(upper_index);
-        let store_clone = store.clone();
-
-        let task = tokio::spawn(async move {
-            let mut chunk_results = HashMap::new();
-
-            for index in chunk_start..=chunk_end {
-                let key_bytes = bcs::to_bytes(&index)?;
-                let field_id = sui_types::dynamic_field::derive_dynamic_field_id(
-                    parent_id,
-                    &key_type,
-                    &key_bytes,
-                )?;
-
-                if let Some(obj) =
-                    store_clone
-                        .perpetual_tables()
-                        .find_object_lt_or_eq_version(field_id, parent_version)?
-                {
-                    if let Some(move_obj) = obj.data.try_as_move() {
-                        let field_data = FieldData {
-                            index,
-                            field_id,
-                            bcs_bytes: move_obj.contents().to_vec(),
-                            version: obj.version(),
-                        };
-                        chunk_results.insert(index, field_data);
-                    }
-                }
-            }
-
-            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(chunk_results)
-        });
-
-        tasks.push(task);
-    }
-
-    // Collect results from all chunks
-    let mut all_results = HashMap::new();
-    for task in tasks {
-        let chunk_results = task.await??;
-        all_results.extend(chunk_results);
-    }
-
-    Ok(all_results)
+    let fields = query_field_data_range_parallel(
+        store.perpetual_tables().clone(),
+        parent_id,
+        current_index,
+        range,
+        parent_version,
+        Arc::new(U64Codec),
+        8,
+    )
+    .await?;
+    Ok(fields)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sui_core::field_data_query::decode_field_value;
 
     #[test]
     fn test_tick_data_serialization() {